@@ -0,0 +1,70 @@
+//! CPU throughput benchmark, in instructions per second.
+//!
+//! Runs nestest CPU tests (automation mode, from $C000) in a loop, without PPU rendering.
+//!
+//! ```bash
+//! $ cargo run --release --example cpu_benchmark [-- --trace]
+//! ```
+//! With `--trace`, each instruction is also traced (as in trace log generation).
+
+use std::{cell::RefCell, env, path::PathBuf, rc::Rc, time::Instant};
+
+use emultendo_core::{
+    bus::cpu_bus::CpuBus,
+    cartridge::Cartridge,
+    cpu::{trace::Trace, Cpu},
+    ppu::Ppu,
+};
+
+/// Instructions run by the benchmark.
+const INSTRUCTIONS: u64 = 20_000_000;
+
+/// nestest automation start address.
+const START: u16 = 0xc000;
+
+/// Last instruction of nestest (RTS to the test harness).
+const END: u16 = 0xc66e;
+
+fn main() {
+    let trace = env::args().any(|arg| arg == "--trace");
+
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("res/nestest.nes");
+    let cartridge = Cartridge::from_file(path).unwrap();
+
+    let bus = Rc::new(RefCell::new(CpuBus::new()));
+    bus.borrow_mut().connect_cartridge(&cartridge);
+    bus.borrow_mut()
+        .connect_ppu(&Rc::new(RefCell::new(Ppu::new())));
+    let mut cpu = Cpu::new(bus);
+
+    let mut instructions = 0;
+    let start = Instant::now();
+    while instructions < INSTRUCTIONS {
+        // Restart tests when done
+        if cpu.program_counter() == END || instructions == 0 {
+            cpu.reset();
+            cpu.tick().unwrap();
+            while !cpu.instruction_changed() {
+                cpu.tick().unwrap();
+            }
+            cpu.set_program_counter(START);
+        }
+        if trace {
+            cpu.trace();
+        }
+        cpu.tick().unwrap();
+        while !cpu.instruction_changed() {
+            cpu.tick().unwrap();
+        }
+        instructions += 1;
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    println!(
+        "{} instructions in {:.2}s: {:.0} instructions/s",
+        instructions,
+        elapsed,
+        instructions as f64 / elapsed
+    );
+}
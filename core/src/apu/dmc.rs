@@ -0,0 +1,196 @@
+// DMC rates (NTSC), in CPU cycles.
+// Source: https://www.nesdev.org/wiki/APU_DMC
+const RATE_TABLE: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+
+/// CPU cycles stolen by a DMC sample fetch.
+pub const DMA_CYCLES: u8 = 4;
+
+/// Delta modulation channel.
+/// Reference: https://www.nesdev.org/wiki/APU_DMC.
+#[derive(Debug, Clone)]
+pub struct DmcChannel {
+    irq_enabled: bool,
+    loop_flag: bool,
+    timer_period: u16,
+    timer: u16,
+    output_level: u8,
+    sample_address: u16,
+    sample_length: u16,
+    current_address: u16,
+    bytes_remaining: u16,
+    sample_buffer: Option<u8>,
+    shift_register: u8,
+    bits_remaining: u8,
+    silence: bool,
+    irq_flag: bool,
+}
+
+impl Default for DmcChannel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DmcChannel {
+    /// Creates a DMC channel.
+    pub fn new() -> Self {
+        Self {
+            irq_enabled: false,
+            loop_flag: false,
+            timer_period: RATE_TABLE[0],
+            timer: 0,
+            output_level: 0,
+            sample_address: 0xC000,
+            sample_length: 1,
+            current_address: 0xC000,
+            bytes_remaining: 0,
+            sample_buffer: None,
+            shift_register: 0,
+            bits_remaining: 8,
+            silence: true,
+            irq_flag: false,
+        }
+    }
+
+    pub fn output_level(&self) -> u8 {
+        self.output_level
+    }
+
+    pub fn timer_period(&self) -> u16 {
+        self.timer_period
+    }
+
+    pub fn bytes_remaining(&self) -> u16 {
+        self.bytes_remaining
+    }
+
+    pub fn irq_flag(&self) -> bool {
+        self.irq_flag
+    }
+
+    /// Indicates if sample bytes remain to be played ($4015 bit 4).
+    pub fn is_active(&self) -> bool {
+        self.bytes_remaining > 0
+    }
+
+    /// Writes to flags and rate register ($4010).
+    pub fn write_control(&mut self, value: u8) {
+        self.irq_enabled = value & 0b1000_0000 != 0;
+        self.loop_flag = value & 0b0100_0000 != 0;
+        self.timer_period = RATE_TABLE[(value & 0b1111) as usize];
+        if !self.irq_enabled {
+            self.irq_flag = false;
+        }
+    }
+
+    /// Writes to direct load register ($4011).
+    pub fn write_direct_load(&mut self, value: u8) {
+        self.output_level = value & 0b0111_1111;
+    }
+
+    /// Writes to sample address register ($4012).
+    /// Sample address is %11AAAAAA.AA000000.
+    pub fn write_sample_address(&mut self, value: u8) {
+        self.sample_address = 0xC000 | ((value as u16) << 6);
+    }
+
+    /// Writes to sample length register ($4013).
+    /// Sample length is %LLLL.LLLL0001.
+    pub fn write_sample_length(&mut self, value: u8) {
+        self.sample_length = ((value as u16) << 4) | 1;
+    }
+
+    /// Enables or disables the channel (from $4015 write).
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    /// Clears interrupt flag.
+    pub fn clear_irq_flag(&mut self) {
+        self.irq_flag = false;
+    }
+
+    /// Restarts sample from its start address.
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    /// Address the memory reader needs to fetch, if any.
+    /// A fetch is required when the sample buffer is empty and sample bytes remain.
+    pub fn dma_address(&self) -> Option<u16> {
+        if self.sample_buffer.is_none() && self.bytes_remaining > 0 {
+            Some(self.current_address)
+        } else {
+            None
+        }
+    }
+
+    /// Fills the sample buffer with a byte fetched by the memory reader.
+    pub fn load_sample(&mut self, data: u8) {
+        if self.bytes_remaining == 0 {
+            return;
+        }
+
+        self.sample_buffer = Some(data);
+
+        // Address wraps around to $8000
+        self.current_address = if self.current_address == 0xFFFF {
+            0x8000
+        } else {
+            self.current_address + 1
+        };
+
+        self.bytes_remaining -= 1;
+        if self.bytes_remaining == 0 {
+            if self.loop_flag {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq_flag = true;
+            }
+        }
+    }
+
+    /// Processes next CPU cycle.
+    pub fn tick(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period - 1;
+            self.clock_output_unit();
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// Clocks output unit: applies next delta bit to output level.
+    fn clock_output_unit(&mut self) {
+        if !self.silence {
+            if self.shift_register & 1 == 1 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            // New output cycle
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(sample) => {
+                    self.silence = false;
+                    self.shift_register = sample;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+}
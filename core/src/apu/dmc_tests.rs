@@ -0,0 +1,88 @@
+use super::dmc::DmcChannel;
+
+#[test]
+fn test_sample_address_and_length() {
+    let mut dmc = DmcChannel::new();
+    dmc.write_sample_address(0x01);
+    dmc.write_sample_length(0x01);
+    dmc.set_enabled(true);
+    assert_eq!(dmc.dma_address(), Some(0xC040));
+    assert_eq!(dmc.bytes_remaining(), 17);
+}
+
+#[test]
+fn test_disable_clears_bytes_remaining() {
+    let mut dmc = DmcChannel::new();
+    dmc.set_enabled(true);
+    assert!(dmc.is_active());
+    dmc.set_enabled(false);
+    assert!(!dmc.is_active());
+    assert_eq!(dmc.dma_address(), None);
+}
+
+#[test]
+fn test_address_wraps_to_0x8000() {
+    let mut dmc = DmcChannel::new();
+    dmc.write_sample_address(0xFF);
+    dmc.write_sample_length(0x04);
+    dmc.set_enabled(true);
+    assert_eq!(dmc.dma_address(), Some(0xFFC0));
+    for _ in 0..0x40 {
+        dmc.load_sample(0);
+        // Empty sample buffer by playing 8 bits
+        for _ in 0..(8 * 428) {
+            dmc.tick();
+        }
+    }
+    assert_eq!(dmc.dma_address(), Some(0x8000));
+}
+
+#[test]
+fn test_irq_at_sample_end() {
+    let mut dmc = DmcChannel::new();
+    dmc.write_control(0b1000_0000);
+    dmc.set_enabled(true);
+    dmc.load_sample(0);
+    assert!(!dmc.is_active());
+    assert!(dmc.irq_flag());
+
+    // Disabling IRQ clears the flag
+    dmc.write_control(0);
+    assert!(!dmc.irq_flag());
+}
+
+#[test]
+fn test_loop_restarts_sample() {
+    let mut dmc = DmcChannel::new();
+    dmc.write_control(0b1100_0000);
+    dmc.set_enabled(true);
+    dmc.load_sample(0);
+    assert!(dmc.is_active());
+    assert!(!dmc.irq_flag());
+}
+
+#[test]
+fn test_output_unit() {
+    let mut dmc = DmcChannel::new();
+    // Fastest rate: 54 cycles per bit
+    dmc.write_control(0x0F);
+    dmc.write_direct_load(64);
+    dmc.set_enabled(true);
+    dmc.load_sample(0b0000_1111);
+
+    // First output cycle is silent and loads the sample buffer in the shift register
+    for _ in 0..(8 * 54) {
+        dmc.tick();
+    }
+    assert_eq!(dmc.output_level(), 64);
+
+    // Then 4 bits up, 4 bits down
+    for _ in 0..(4 * 54) {
+        dmc.tick();
+    }
+    assert_eq!(dmc.output_level(), 72);
+    for _ in 0..(4 * 54) {
+        dmc.tick();
+    }
+    assert_eq!(dmc.output_level(), 64);
+}
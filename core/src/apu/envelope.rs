@@ -0,0 +1,70 @@
+/// Envelope generator (pulse and noise channels).
+/// Reference: https://www.nesdev.org/wiki/APU_Envelope.
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    start: bool,
+    loop_flag: bool,
+    constant_volume: bool,
+    volume: u8,
+    divider: u8,
+    decay_level: u8,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Envelope {
+    /// Creates an envelope.
+    pub fn new() -> Self {
+        Self {
+            start: false,
+            loop_flag: false,
+            constant_volume: false,
+            volume: 0,
+            divider: 0,
+            decay_level: 0,
+        }
+    }
+
+    /// Updates envelope from channel control register (--LC VVVV).
+    pub fn write_control(&mut self, value: u8) {
+        self.loop_flag = value & 0b0010_0000 != 0;
+        self.constant_volume = value & 0b0001_0000 != 0;
+        self.volume = value & 0b1111;
+    }
+
+    /// Restarts envelope on next quarter frame.
+    pub fn restart(&mut self) {
+        self.start = true;
+    }
+
+    /// Clocks envelope (quarter frame).
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay_level = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+            if self.decay_level > 0 {
+                self.decay_level -= 1;
+            } else if self.loop_flag {
+                self.decay_level = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    /// Current envelope volume (0-15).
+    pub fn output(&self) -> u8 {
+        if self.constant_volume {
+            self.volume
+        } else {
+            self.decay_level
+        }
+    }
+}
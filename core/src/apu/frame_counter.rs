@@ -0,0 +1,122 @@
+// Sequencer steps (NTSC), in CPU cycles.
+// Source: https://www.nesdev.org/wiki/APU_Frame_Counter
+const STEP_1: u32 = 7457;
+const STEP_2: u32 = 14913;
+const STEP_3: u32 = 22371;
+const STEP_4: u32 = 29829;
+const FOUR_STEP_END: u32 = 29830;
+const STEP_5: u32 = 37281;
+const FIVE_STEP_END: u32 = 37282;
+
+/// Units to clock after a frame counter step.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FrameEvent {
+    None,
+    /// Envelopes and triangle linear counter.
+    QuarterFrame,
+    /// Quarter frame units, plus length counters and sweep units.
+    HalfFrame,
+}
+
+/// Frame counter ($4017).
+/// Reference: https://www.nesdev.org/wiki/APU_Frame_Counter.
+#[derive(Debug, Clone)]
+pub struct FrameCounter {
+    five_step_mode: bool,
+    irq_inhibit: bool,
+    interrupt_flag: bool,
+    /// CPU cycles since sequence start.
+    cycle: u32,
+    /// CPU cycles before sequence reset after a write.
+    reset_delay: u8,
+}
+
+impl Default for FrameCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameCounter {
+    /// Creates a frame counter.
+    pub fn new() -> Self {
+        Self {
+            five_step_mode: false,
+            irq_inhibit: false,
+            interrupt_flag: false,
+            cycle: 0,
+            reset_delay: 0,
+        }
+    }
+
+    pub fn five_step_mode(&self) -> bool {
+        self.five_step_mode
+    }
+
+    pub fn interrupt_flag(&self) -> bool {
+        self.interrupt_flag
+    }
+
+    /// Clears frame interrupt flag (on $4015 read).
+    pub fn clear_interrupt_flag(&mut self) {
+        self.interrupt_flag = false;
+    }
+
+    /// Writes to frame counter register (MI-- ----).
+    /// Sequencer is reset 3 or 4 CPU cycles after the write, depending on CPU cycle parity.
+    /// In 5-step mode, quarter and half frame units are clocked immediately.
+    pub fn write_control(&mut self, value: u8, odd_cycle: bool) -> FrameEvent {
+        self.five_step_mode = value & 0b1000_0000 != 0;
+        self.irq_inhibit = value & 0b0100_0000 != 0;
+        if self.irq_inhibit {
+            self.interrupt_flag = false;
+        }
+
+        self.reset_delay = if odd_cycle { 4 } else { 3 };
+
+        if self.five_step_mode {
+            FrameEvent::HalfFrame
+        } else {
+            FrameEvent::None
+        }
+    }
+
+    /// Processes next CPU cycle.
+    pub fn tick(&mut self) -> FrameEvent {
+        if self.reset_delay > 0 {
+            self.reset_delay -= 1;
+            if self.reset_delay == 0 {
+                self.cycle = 0;
+                return FrameEvent::None;
+            }
+        }
+
+        self.cycle += 1;
+
+        if self.five_step_mode {
+            match self.cycle {
+                STEP_1 | STEP_3 => FrameEvent::QuarterFrame,
+                STEP_2 | STEP_5 => FrameEvent::HalfFrame,
+                FIVE_STEP_END => {
+                    self.cycle = 0;
+                    FrameEvent::None
+                }
+                _ => FrameEvent::None,
+            }
+        } else {
+            // Frame interrupt flag is raised over the last 3 cycles of the sequence
+            if (STEP_4 - 1..=FOUR_STEP_END).contains(&self.cycle) && !self.irq_inhibit {
+                self.interrupt_flag = true;
+            }
+            match self.cycle {
+                STEP_1 | STEP_3 => FrameEvent::QuarterFrame,
+                STEP_2 | STEP_4 => FrameEvent::HalfFrame,
+                FOUR_STEP_END => {
+                    self.cycle = 0;
+                    FrameEvent::None
+                }
+                _ => FrameEvent::None,
+            }
+        }
+    }
+}
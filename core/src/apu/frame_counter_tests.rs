@@ -0,0 +1,81 @@
+use super::frame_counter::{FrameCounter, FrameEvent};
+
+fn run_sequence(frame_counter: &mut FrameCounter, cycles: u32) -> Vec<(u32, FrameEvent)> {
+    let mut events = vec![];
+    for cycle in 1..=cycles {
+        let event = frame_counter.tick();
+        if event != FrameEvent::None {
+            events.push((cycle, event));
+        }
+    }
+    events
+}
+
+#[test]
+fn test_four_step_sequence() {
+    let mut frame_counter = FrameCounter::new();
+    let events = run_sequence(&mut frame_counter, 29830);
+    assert_eq!(
+        events,
+        vec![
+            (7457, FrameEvent::QuarterFrame),
+            (14913, FrameEvent::HalfFrame),
+            (22371, FrameEvent::QuarterFrame),
+            (29829, FrameEvent::HalfFrame),
+        ]
+    );
+    assert!(frame_counter.interrupt_flag());
+
+    // Sequence loops
+    frame_counter.clear_interrupt_flag();
+    let events = run_sequence(&mut frame_counter, 7457);
+    assert_eq!(events, vec![(7457, FrameEvent::QuarterFrame)]);
+    assert!(!frame_counter.interrupt_flag());
+}
+
+#[test]
+fn test_five_step_sequence() {
+    let mut frame_counter = FrameCounter::new();
+    assert_eq!(
+        frame_counter.write_control(0b1000_0000, false),
+        FrameEvent::HalfFrame
+    );
+    // Sequencer reset after 3 cycles
+    assert!(run_sequence(&mut frame_counter, 3).is_empty());
+
+    let events = run_sequence(&mut frame_counter, 37282);
+    assert_eq!(
+        events,
+        vec![
+            (7457, FrameEvent::QuarterFrame),
+            (14913, FrameEvent::HalfFrame),
+            (22371, FrameEvent::QuarterFrame),
+            (37281, FrameEvent::HalfFrame),
+        ]
+    );
+    // No IRQ in 5-step mode
+    assert!(!frame_counter.interrupt_flag());
+}
+
+#[test]
+fn test_reset_delay_depends_on_cycle_parity() {
+    let mut frame_counter = FrameCounter::new();
+    frame_counter.write_control(0, true);
+    let events = run_sequence(&mut frame_counter, 4 + 7457);
+    assert_eq!(events, vec![(4 + 7457, FrameEvent::QuarterFrame)]);
+}
+
+#[test]
+fn test_irq_inhibit() {
+    let mut frame_counter = FrameCounter::new();
+    frame_counter.write_control(0b0100_0000, false);
+    run_sequence(&mut frame_counter, 3 + 29830);
+    assert!(!frame_counter.interrupt_flag());
+
+    // Setting inhibit clears the flag
+    frame_counter.write_control(0, false);
+    run_sequence(&mut frame_counter, 3 + 29830);
+    assert!(frame_counter.interrupt_flag());
+    frame_counter.write_control(0b0100_0000, false);
+    assert!(!frame_counter.interrupt_flag());
+}
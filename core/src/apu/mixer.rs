@@ -0,0 +1,26 @@
+// Non linear mixer lookup tables.
+// Source: https://www.nesdev.org/wiki/APU_Mixer
+lazy_static! {
+    static ref PULSE_TABLE: [f32; 31] = {
+        let mut table = [0.0; 31];
+        for (n, value) in table.iter_mut().enumerate().skip(1) {
+            *value = 95.52 / (8128.0 / n as f32 + 100.0);
+        }
+        table
+    };
+    static ref TND_TABLE: [f32; 203] = {
+        let mut table = [0.0; 203];
+        for (n, value) in table.iter_mut().enumerate().skip(1) {
+            *value = 163.67 / (24329.0 / n as f32 + 100.0);
+        }
+        table
+    };
+}
+
+/// Mixes channel outputs into an amplitude between 0.0 and 1.0.
+/// Pulse, triangle and noise outputs are in 0-15, DMC output is in 0-127.
+pub fn mix(pulse1: u8, pulse2: u8, triangle: u8, noise: u8, dmc: u8) -> f32 {
+    let pulse_out = PULSE_TABLE[(pulse1 + pulse2) as usize];
+    let tnd_out = TND_TABLE[3 * triangle as usize + 2 * noise as usize + dmc as usize];
+    pulse_out + tnd_out
+}
@@ -0,0 +1,349 @@
+use self::{
+    dmc::DmcChannel,
+    frame_counter::{FrameCounter, FrameEvent},
+    noise::NoiseChannel,
+    pulse::PulseChannel,
+    triangle::TriangleChannel,
+};
+
+pub mod dmc;
+#[cfg(test)]
+mod dmc_tests;
+
+pub mod envelope;
+
+pub mod frame_counter;
+#[cfg(test)]
+mod frame_counter_tests;
+
+pub mod length_counter;
+pub mod mixer;
+pub mod noise;
+pub mod pulse;
+pub mod triangle;
+
+#[cfg(test)]
+mod mod_tests;
+
+/// APU sound channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Pulse1,
+    Pulse2,
+    Triangle,
+    Noise,
+    Dmc,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 5] = [
+        Channel::Pulse1,
+        Channel::Pulse2,
+        Channel::Triangle,
+        Channel::Noise,
+        Channel::Dmc,
+    ];
+
+    /// Short lowercase name (for file names, labels...).
+    pub fn name(&self) -> &'static str {
+        match self {
+            Channel::Pulse1 => "pulse1",
+            Channel::Pulse2 => "pulse2",
+            Channel::Triangle => "triangle",
+            Channel::Noise => "noise",
+            Channel::Dmc => "dmc",
+        }
+    }
+}
+
+/// APU.
+/// Reference: https://www.nesdev.org/wiki/APU.
+#[derive(Debug, Clone)]
+pub struct Apu {
+    pulse1: PulseChannel,
+    pulse2: PulseChannel,
+    triangle: TriangleChannel,
+    noise: NoiseChannel,
+    dmc: DmcChannel,
+    frame_counter: FrameCounter,
+    cycle: u64,
+    /// Last values written to registers $4000-$4017.
+    registers: [u8; 0x18],
+    muted: [bool; 5],
+    solo: [bool; 5],
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Apu {
+    /// Creates an APU instance.
+    pub fn new() -> Self {
+        Self {
+            pulse1: PulseChannel::new_pulse1(),
+            pulse2: PulseChannel::new_pulse2(),
+            triangle: TriangleChannel::new(),
+            noise: NoiseChannel::new(),
+            dmc: DmcChannel::new(),
+            frame_counter: FrameCounter::new(),
+            cycle: 0,
+            registers: [0; 0x18],
+            muted: [false; 5],
+            solo: [false; 5],
+        }
+    }
+
+    pub fn pulse1(&self) -> &PulseChannel {
+        &self.pulse1
+    }
+
+    pub fn pulse2(&self) -> &PulseChannel {
+        &self.pulse2
+    }
+
+    pub fn triangle(&self) -> &TriangleChannel {
+        &self.triangle
+    }
+
+    pub fn noise(&self) -> &NoiseChannel {
+        &self.noise
+    }
+
+    pub fn dmc(&self) -> &DmcChannel {
+        &self.dmc
+    }
+
+    pub fn frame_counter(&self) -> &FrameCounter {
+        &self.frame_counter
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycle
+    }
+
+    /// Last value written to an APU register ($4000-$4017), 0 if never written.
+    /// Registers are write only: this is meant for debugging.
+    pub fn register(&self, addr: u16) -> u8 {
+        match addr {
+            0x4000..=0x4017 => self.registers[(addr - 0x4000) as usize],
+            _ => 0,
+        }
+    }
+
+    /// Mutes (or unmutes) a channel in the mixer.
+    /// Channel still runs: only its contribution to output is removed.
+    pub fn set_channel_muted(&mut self, channel: Channel, muted: bool) {
+        self.muted[channel as usize] = muted;
+    }
+
+    pub fn is_channel_muted(&self, channel: Channel) -> bool {
+        self.muted[channel as usize]
+    }
+
+    /// Solos (or unsolos) a channel in the mixer.
+    /// When at least one channel is soloed, only soloed channels are mixed.
+    pub fn set_channel_solo(&mut self, channel: Channel, solo: bool) {
+        self.solo[channel as usize] = solo;
+    }
+
+    pub fn is_channel_solo(&self, channel: Channel) -> bool {
+        self.solo[channel as usize]
+    }
+
+    /// Indicates if a channel is mixed to output, according to mute and solo settings.
+    pub fn is_channel_audible(&self, channel: Channel) -> bool {
+        if self.solo.iter().any(|s| *s) {
+            self.solo[channel as usize]
+        } else {
+            !self.muted[channel as usize]
+        }
+    }
+
+    /// Writes to an APU register ($4000-$4013, $4015, $4017).
+    pub fn write_register(&mut self, addr: u16, data: u8) {
+        if let 0x4000..=0x4017 = addr {
+            self.registers[(addr - 0x4000) as usize] = data;
+        }
+        match addr {
+            0x4000 => self.pulse1.write_control(data),
+            0x4001 => self.pulse1.write_sweep(data),
+            0x4002 => self.pulse1.write_timer_low(data),
+            0x4003 => self.pulse1.write_timer_high(data),
+            0x4004 => self.pulse2.write_control(data),
+            0x4005 => self.pulse2.write_sweep(data),
+            0x4006 => self.pulse2.write_timer_low(data),
+            0x4007 => self.pulse2.write_timer_high(data),
+            0x4008 => self.triangle.write_linear_counter(data),
+            0x400A => self.triangle.write_timer_low(data),
+            0x400B => self.triangle.write_timer_high(data),
+            0x400C => self.noise.write_control(data),
+            0x400E => self.noise.write_period(data),
+            0x400F => self.noise.write_length(data),
+            0x4010 => self.dmc.write_control(data),
+            0x4011 => self.dmc.write_direct_load(data),
+            0x4012 => self.dmc.write_sample_address(data),
+            0x4013 => self.dmc.write_sample_length(data),
+            0x4015 => self.write_to_status(data),
+            0x4017 => {
+                let event = self.frame_counter.write_control(data, self.cycle % 2 == 1);
+                self.clock_frame_event(event);
+            }
+            _ => {
+                // Ignore unused registers
+            }
+        }
+    }
+
+    /// Writes to status register ($4015).
+    fn write_to_status(&mut self, data: u8) {
+        self.pulse1.set_enabled(data & 0b0000_0001 != 0);
+        self.pulse2.set_enabled(data & 0b0000_0010 != 0);
+        self.triangle.set_enabled(data & 0b0000_0100 != 0);
+        self.noise.set_enabled(data & 0b0000_1000 != 0);
+        self.dmc.clear_irq_flag();
+        self.dmc.set_enabled(data & 0b0001_0000 != 0);
+    }
+
+    /// Reads status register ($4015).
+    /// Clears frame interrupt flag.
+    ///
+    ///  7 6 5 4 3 2 1 0
+    ///  I F _ D N T 2 1
+    ///  | |   | | | | +--- Pulse 1 length counter > 0
+    ///  | |   | | | +----- Pulse 2 length counter > 0
+    ///  | |   | | +------- Triangle length counter > 0
+    ///  | |   | +--------- Noise length counter > 0
+    ///  | |   +----------- DMC active
+    ///  | +--------------- Frame interrupt
+    ///  +----------------- DMC interrupt
+    ///
+    pub fn read_status(&mut self) -> u8 {
+        let data = self.peek_status();
+        self.frame_counter.clear_interrupt_flag();
+        data
+    }
+
+    /// Status register value, without side effects (frame interrupt flag is kept).
+    pub fn peek_status(&self) -> u8 {
+        let mut data = 0;
+        if !self.pulse1.length_counter().is_silenced() {
+            data |= 0b0000_0001;
+        }
+        if !self.pulse2.length_counter().is_silenced() {
+            data |= 0b0000_0010;
+        }
+        if !self.triangle.length_counter().is_silenced() {
+            data |= 0b0000_0100;
+        }
+        if !self.noise.length_counter().is_silenced() {
+            data |= 0b0000_1000;
+        }
+        if self.dmc.is_active() {
+            data |= 0b0001_0000;
+        }
+        if self.frame_counter.interrupt_flag() {
+            data |= 0b0100_0000;
+        }
+        if self.dmc.irq_flag() {
+            data |= 0b1000_0000;
+        }
+        data
+    }
+
+    /// Address of the pending DMC sample fetch, if any.
+    pub fn dmc_dma_address(&self) -> Option<u16> {
+        self.dmc.dma_address()
+    }
+
+    /// Delivers a DMC sample byte fetched through DMA.
+    pub fn load_dmc_sample(&mut self, data: u8) {
+        self.dmc.load_sample(data);
+    }
+
+    /// Indicates if APU is asserting an interrupt (frame counter or DMC).
+    pub fn irq(&self) -> bool {
+        self.frame_counter.interrupt_flag() || self.dmc.irq_flag()
+    }
+
+    /// Current mixed output, between 0.0 and 1.0.
+    /// Muted channels are left out.
+    pub fn output(&self) -> f32 {
+        let level = |channel, output| {
+            if self.is_channel_audible(channel) {
+                output
+            } else {
+                0
+            }
+        };
+        mixer::mix(
+            level(Channel::Pulse1, self.pulse1.output()),
+            level(Channel::Pulse2, self.pulse2.output()),
+            level(Channel::Triangle, self.triangle.output()),
+            level(Channel::Noise, self.noise.output()),
+            level(Channel::Dmc, self.dmc.output_level()),
+        )
+    }
+
+    /// Current output level of a channel, before mixing (mute settings are ignored).
+    pub fn channel_level(&self, channel: Channel) -> u8 {
+        match channel {
+            Channel::Pulse1 => self.pulse1.output(),
+            Channel::Pulse2 => self.pulse2.output(),
+            Channel::Triangle => self.triangle.output(),
+            Channel::Noise => self.noise.output(),
+            Channel::Dmc => self.dmc.output_level(),
+        }
+    }
+
+    /// Output of a single channel through the mixer, other channels being silent.
+    /// Mute settings are ignored.
+    pub fn channel_output(&self, channel: Channel) -> f32 {
+        let level = self.channel_level(channel);
+        match channel {
+            Channel::Pulse1 => mixer::mix(level, 0, 0, 0, 0),
+            Channel::Pulse2 => mixer::mix(0, level, 0, 0, 0),
+            Channel::Triangle => mixer::mix(0, 0, level, 0, 0),
+            Channel::Noise => mixer::mix(0, 0, 0, level, 0),
+            Channel::Dmc => mixer::mix(0, 0, 0, 0, level),
+        }
+    }
+
+    /// Clocks channel units according to frame counter event.
+    fn clock_frame_event(&mut self, event: FrameEvent) {
+        if event == FrameEvent::None {
+            return;
+        }
+
+        self.pulse1.clock_quarter_frame();
+        self.pulse2.clock_quarter_frame();
+        self.triangle.clock_quarter_frame();
+        self.noise.clock_quarter_frame();
+
+        if event == FrameEvent::HalfFrame {
+            self.pulse1.clock_half_frame();
+            self.pulse2.clock_half_frame();
+            self.triangle.clock_half_frame();
+            self.noise.clock_half_frame();
+        }
+    }
+
+    /// Processes next CPU cycle.
+    pub fn tick(&mut self) {
+        let event = self.frame_counter.tick();
+        self.clock_frame_event(event);
+
+        // Pulse timers are clocked every APU cycle (every other CPU cycle)
+        if self.cycle % 2 == 1 {
+            self.pulse1.tick();
+            self.pulse2.tick();
+        }
+        self.triangle.tick();
+        self.noise.tick();
+        self.dmc.tick();
+
+        self.cycle += 1;
+    }
+}
@@ -0,0 +1,127 @@
+use super::{mixer, Apu, Channel};
+
+#[test]
+fn test_status_length_counters() {
+    let mut apu = Apu::new();
+    apu.write_register(0x4015, 0b0000_1111);
+    apu.write_register(0x4003, 0b0000_1000);
+    apu.write_register(0x4007, 0b0000_1000);
+    apu.write_register(0x400B, 0b0000_1000);
+    apu.write_register(0x400F, 0b0000_1000);
+    assert_eq!(apu.read_status(), 0b0000_1111);
+
+    // Disabling channels clears length counters
+    apu.write_register(0x4015, 0);
+    assert_eq!(apu.read_status(), 0);
+}
+
+#[test]
+fn test_length_counter_not_loaded_when_disabled() {
+    let mut apu = Apu::new();
+    apu.write_register(0x4003, 0b0000_1000);
+    assert_eq!(apu.read_status(), 0);
+}
+
+#[test]
+fn test_length_counter_clocked_by_frame_counter() {
+    let mut apu = Apu::new();
+    apu.write_register(0x4015, 0b0000_0001);
+    // Length index 3: 2
+    apu.write_register(0x4003, 0b0001_1000);
+    assert_eq!(apu.pulse1().length_counter().counter(), 2);
+
+    // 5-step mode clocks half frame immediately
+    apu.write_register(0x4017, 0b1000_0000);
+    assert_eq!(apu.pulse1().length_counter().counter(), 1);
+
+    // Next half frame
+    for _ in 0..(3 + 14913) {
+        apu.tick();
+    }
+    assert_eq!(apu.pulse1().length_counter().counter(), 0);
+    assert_eq!(apu.read_status(), 0);
+}
+
+#[test]
+fn test_status_read_clears_frame_interrupt() {
+    let mut apu = Apu::new();
+    for _ in 0..29830 {
+        apu.tick();
+    }
+    assert!(apu.irq());
+    assert_eq!(apu.read_status(), 0b0100_0000);
+    assert!(!apu.irq());
+    assert_eq!(apu.read_status(), 0);
+}
+
+#[test]
+fn test_envelope_decay() {
+    let mut apu = Apu::new();
+    apu.write_register(0x4015, 0b0000_1000);
+    // Envelope period 0: decay level decrements on each quarter frame
+    apu.write_register(0x400C, 0);
+    apu.write_register(0x400F, 0b0000_1000);
+    apu.write_register(0x4017, 0b1000_0000);
+    assert_eq!(apu.noise().envelope().output(), 15);
+    for _ in 0..(3 + 7457) {
+        apu.tick();
+    }
+    assert_eq!(apu.noise().envelope().output(), 14);
+}
+
+#[test]
+fn test_triangle_linear_counter() {
+    let mut apu = Apu::new();
+    apu.write_register(0x4015, 0b0000_0100);
+    apu.write_register(0x4008, 0x05);
+    apu.write_register(0x400B, 0b0000_1000);
+    apu.write_register(0x4017, 0b1000_0000);
+    assert_eq!(apu.triangle().linear_counter(), 5);
+    for _ in 0..(3 + 7457) {
+        apu.tick();
+    }
+    assert_eq!(apu.triangle().linear_counter(), 4);
+}
+
+#[test]
+fn test_pulse_sweep() {
+    let mut apu = Apu::new();
+    apu.write_register(0x4015, 0b0000_0011);
+    // Sweep enabled, period 0, negate, shift 1
+    apu.write_register(0x4001, 0b1000_1001);
+    apu.write_register(0x4002, 0x00);
+    apu.write_register(0x4003, 0b0000_1001);
+    apu.write_register(0x4005, 0b1000_1001);
+    apu.write_register(0x4006, 0x00);
+    apu.write_register(0x4007, 0b0000_1001);
+    apu.write_register(0x4017, 0b1000_0000);
+    // Pulse 1 negates with one's complement, pulse 2 with two's complement
+    assert_eq!(apu.pulse1().timer_period(), 0x7F);
+    assert_eq!(apu.pulse2().timer_period(), 0x80);
+}
+
+#[test]
+fn test_mute_and_solo() {
+    let mut apu = Apu::new();
+    // Triangle outputs 15 at power up
+    apu.write_register(0x4011, 64);
+    assert_eq!(apu.register(0x4011), 64);
+    assert_eq!(apu.output(), mixer::mix(0, 0, 15, 0, 64));
+
+    apu.set_channel_muted(Channel::Triangle, true);
+    assert!(!apu.is_channel_audible(Channel::Triangle));
+    assert_eq!(apu.output(), mixer::mix(0, 0, 0, 0, 64));
+    // Muted channels are still available separately
+    assert_eq!(apu.channel_level(Channel::Triangle), 15);
+
+    // Solo overrides mute
+    apu.set_channel_solo(Channel::Triangle, true);
+    assert_eq!(apu.output(), mixer::mix(0, 0, 15, 0, 0));
+    apu.set_channel_solo(Channel::Dmc, true);
+    assert_eq!(apu.output(), mixer::mix(0, 0, 15, 0, 64));
+
+    apu.set_channel_solo(Channel::Triangle, false);
+    apu.set_channel_solo(Channel::Dmc, false);
+    apu.set_channel_muted(Channel::Triangle, false);
+    assert_eq!(apu.output(), mixer::mix(0, 0, 15, 0, 64));
+}
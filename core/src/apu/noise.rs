@@ -0,0 +1,110 @@
+use super::{envelope::Envelope, length_counter::LengthCounter};
+
+// Noise periods (NTSC), in CPU cycles.
+// Source: https://www.nesdev.org/wiki/APU_Noise
+const PERIOD_TABLE: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+
+/// Noise channel.
+/// Reference: https://www.nesdev.org/wiki/APU_Noise.
+#[derive(Debug, Clone)]
+pub struct NoiseChannel {
+    mode: bool,
+    timer_period: u16,
+    timer: u16,
+    shift_register: u16,
+    envelope: Envelope,
+    length_counter: LengthCounter,
+}
+
+impl Default for NoiseChannel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NoiseChannel {
+    /// Creates a noise channel.
+    pub fn new() -> Self {
+        Self {
+            mode: false,
+            timer_period: PERIOD_TABLE[0],
+            timer: 0,
+            // Shift register is loaded with 1 on power-up
+            shift_register: 1,
+            envelope: Envelope::new(),
+            length_counter: LengthCounter::new(),
+        }
+    }
+
+    pub fn mode(&self) -> bool {
+        self.mode
+    }
+
+    pub fn timer_period(&self) -> u16 {
+        self.timer_period
+    }
+
+    pub fn length_counter(&self) -> &LengthCounter {
+        &self.length_counter
+    }
+
+    pub fn envelope(&self) -> &Envelope {
+        &self.envelope
+    }
+
+    /// Writes to envelope register (--LC VVVV).
+    pub fn write_control(&mut self, value: u8) {
+        self.length_counter.set_halt(value & 0b0010_0000 != 0);
+        self.envelope.write_control(value);
+    }
+
+    /// Writes to mode and period register (M--- PPPP).
+    pub fn write_period(&mut self, value: u8) {
+        self.mode = value & 0b1000_0000 != 0;
+        self.timer_period = PERIOD_TABLE[(value & 0b1111) as usize];
+    }
+
+    /// Writes to length counter load register (LLLL L---).
+    pub fn write_length(&mut self, value: u8) {
+        self.length_counter.load(value);
+        self.envelope.restart();
+    }
+
+    /// Enables or disables the channel (from $4015 write).
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.length_counter.set_enabled(enabled);
+    }
+
+    /// Processes next CPU cycle.
+    pub fn tick(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period - 1;
+            let other_bit = if self.mode { 6 } else { 1 };
+            let feedback = (self.shift_register & 1) ^ ((self.shift_register >> other_bit) & 1);
+            self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// Clocks envelope (quarter frame).
+    pub fn clock_quarter_frame(&mut self) {
+        self.envelope.clock();
+    }
+
+    /// Clocks length counter (half frame).
+    pub fn clock_half_frame(&mut self) {
+        self.length_counter.clock();
+    }
+
+    /// Current channel output (0-15).
+    pub fn output(&self) -> u8 {
+        if self.length_counter.is_silenced() || self.shift_register & 1 == 1 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use super::{envelope::Envelope, length_counter::LengthCounter};
+
+// Source: https://www.nesdev.org/wiki/APU_Pulse
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+/// Pulse channel.
+/// Reference: https://www.nesdev.org/wiki/APU_Pulse.
+#[derive(Debug, Clone)]
+pub struct PulseChannel {
+    /// Pulse 1 negates sweep with one's complement, pulse 2 with two's complement.
+    ones_complement: bool,
+    duty: u8,
+    sequence_step: u8,
+    timer_period: u16,
+    timer: u16,
+    envelope: Envelope,
+    length_counter: LengthCounter,
+    sweep_enabled: bool,
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_divider: u8,
+    sweep_reload: bool,
+}
+
+impl PulseChannel {
+    /// Creates pulse channel 1 ($4000-$4003).
+    pub fn new_pulse1() -> Self {
+        Self::new(true)
+    }
+
+    /// Creates pulse channel 2 ($4004-$4007).
+    pub fn new_pulse2() -> Self {
+        Self::new(false)
+    }
+
+    fn new(ones_complement: bool) -> Self {
+        Self {
+            ones_complement,
+            duty: 0,
+            sequence_step: 0,
+            timer_period: 0,
+            timer: 0,
+            envelope: Envelope::new(),
+            length_counter: LengthCounter::new(),
+            sweep_enabled: false,
+            sweep_period: 0,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_divider: 0,
+            sweep_reload: false,
+        }
+    }
+
+    pub fn duty(&self) -> u8 {
+        self.duty
+    }
+
+    pub fn timer_period(&self) -> u16 {
+        self.timer_period
+    }
+
+    pub fn length_counter(&self) -> &LengthCounter {
+        &self.length_counter
+    }
+
+    pub fn envelope(&self) -> &Envelope {
+        &self.envelope
+    }
+
+    /// Writes to duty and envelope register (DDLC VVVV).
+    pub fn write_control(&mut self, value: u8) {
+        self.duty = value >> 6;
+        self.length_counter.set_halt(value & 0b0010_0000 != 0);
+        self.envelope.write_control(value);
+    }
+
+    /// Writes to sweep register (EPPP NSSS).
+    pub fn write_sweep(&mut self, value: u8) {
+        self.sweep_enabled = value & 0b1000_0000 != 0;
+        self.sweep_period = (value >> 4) & 0b111;
+        self.sweep_negate = value & 0b1000 != 0;
+        self.sweep_shift = value & 0b111;
+        self.sweep_reload = true;
+    }
+
+    /// Writes to timer low register (TTTT TTTT).
+    pub fn write_timer_low(&mut self, value: u8) {
+        self.timer_period = (self.timer_period & 0xFF00) | value as u16;
+    }
+
+    /// Writes to length counter load and timer high register (LLLL LTTT).
+    pub fn write_timer_high(&mut self, value: u8) {
+        self.timer_period = (self.timer_period & 0x00FF) | (((value & 0b111) as u16) << 8);
+        self.length_counter.load(value);
+        self.sequence_step = 0;
+        self.envelope.restart();
+    }
+
+    /// Enables or disables the channel (from $4015 write).
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.length_counter.set_enabled(enabled);
+    }
+
+    /// Processes next APU cycle (every other CPU cycle).
+    pub fn tick(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.sequence_step = (self.sequence_step + 1) % 8;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// Clocks envelope (quarter frame).
+    pub fn clock_quarter_frame(&mut self) {
+        self.envelope.clock();
+    }
+
+    /// Clocks length counter and sweep unit (half frame).
+    pub fn clock_half_frame(&mut self) {
+        self.length_counter.clock();
+
+        if self.sweep_divider == 0
+            && self.sweep_enabled
+            && self.sweep_shift > 0
+            && !self.is_sweep_muting()
+        {
+            self.timer_period = self.sweep_target_period();
+        }
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    /// Computes sweep unit target period.
+    fn sweep_target_period(&self) -> u16 {
+        let change = self.timer_period >> self.sweep_shift;
+        if self.sweep_negate {
+            let change = if self.ones_complement {
+                change + 1
+            } else {
+                change
+            };
+            self.timer_period.saturating_sub(change)
+        } else {
+            self.timer_period + change
+        }
+    }
+
+    /// Sweep unit mutes channel if current period is too low or target period overflows.
+    fn is_sweep_muting(&self) -> bool {
+        self.timer_period < 8 || self.sweep_target_period() > 0x7FF
+    }
+
+    /// Current channel output (0-15).
+    pub fn output(&self) -> u8 {
+        if self.length_counter.is_silenced()
+            || self.is_sweep_muting()
+            || DUTY_TABLE[self.duty as usize][self.sequence_step as usize] == 0
+        {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
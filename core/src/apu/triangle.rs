@@ -0,0 +1,114 @@
+use super::length_counter::LengthCounter;
+
+// Source: https://www.nesdev.org/wiki/APU_Triangle
+const SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12,
+    13, 14, 15,
+];
+
+/// Triangle channel.
+/// Reference: https://www.nesdev.org/wiki/APU_Triangle.
+#[derive(Debug, Clone)]
+pub struct TriangleChannel {
+    control_flag: bool,
+    linear_counter_reload_value: u8,
+    linear_counter: u8,
+    linear_counter_reload: bool,
+    sequence_step: u8,
+    timer_period: u16,
+    timer: u16,
+    length_counter: LengthCounter,
+}
+
+impl Default for TriangleChannel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TriangleChannel {
+    /// Creates a triangle channel.
+    pub fn new() -> Self {
+        Self {
+            control_flag: false,
+            linear_counter_reload_value: 0,
+            linear_counter: 0,
+            linear_counter_reload: false,
+            sequence_step: 0,
+            timer_period: 0,
+            timer: 0,
+            length_counter: LengthCounter::new(),
+        }
+    }
+
+    pub fn timer_period(&self) -> u16 {
+        self.timer_period
+    }
+
+    pub fn linear_counter(&self) -> u8 {
+        self.linear_counter
+    }
+
+    pub fn length_counter(&self) -> &LengthCounter {
+        &self.length_counter
+    }
+
+    /// Writes to linear counter register (CRRR RRRR).
+    pub fn write_linear_counter(&mut self, value: u8) {
+        self.control_flag = value & 0b1000_0000 != 0;
+        self.length_counter.set_halt(self.control_flag);
+        self.linear_counter_reload_value = value & 0b0111_1111;
+    }
+
+    /// Writes to timer low register (TTTT TTTT).
+    pub fn write_timer_low(&mut self, value: u8) {
+        self.timer_period = (self.timer_period & 0xFF00) | value as u16;
+    }
+
+    /// Writes to length counter load and timer high register (LLLL LTTT).
+    pub fn write_timer_high(&mut self, value: u8) {
+        self.timer_period = (self.timer_period & 0x00FF) | (((value & 0b111) as u16) << 8);
+        self.length_counter.load(value);
+        self.linear_counter_reload = true;
+    }
+
+    /// Enables or disables the channel (from $4015 write).
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.length_counter.set_enabled(enabled);
+    }
+
+    /// Processes next CPU cycle.
+    pub fn tick(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            if self.linear_counter > 0 && !self.length_counter.is_silenced() {
+                self.sequence_step = (self.sequence_step + 1) % 32;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// Clocks linear counter (quarter frame).
+    pub fn clock_quarter_frame(&mut self) {
+        if self.linear_counter_reload {
+            self.linear_counter = self.linear_counter_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+        if !self.control_flag {
+            self.linear_counter_reload = false;
+        }
+    }
+
+    /// Clocks length counter (half frame).
+    pub fn clock_half_frame(&mut self) {
+        self.length_counter.clock();
+    }
+
+    /// Current channel output (0-15).
+    /// Triangle is never silenced: it keeps its last sequence value when halted.
+    pub fn output(&self) -> u8 {
+        SEQUENCE[self.sequence_step as usize]
+    }
+}
@@ -0,0 +1,126 @@
+use std::f64::consts::PI;
+
+/// Kernel width, in output samples.
+const KERNEL_WIDTH: usize = 16;
+/// Number of sub-sample kernel phases.
+const PHASE_COUNT: usize = 32;
+/// Kernel cutoff, relative to output Nyquist frequency.
+const CUTOFF: f64 = 0.9;
+
+lazy_static! {
+    /// Band-limited impulse kernels (windowed sinc), one per sub-sample phase.
+    /// Each kernel sums to 1 so that integrated steps settle on the exact delta.
+    static ref KERNELS: Vec<[f32; KERNEL_WIDTH]> = {
+        let mut kernels = vec![];
+        for phase in 0..PHASE_COUNT {
+            let offset = phase as f64 / PHASE_COUNT as f64;
+            let mut kernel = [0.0; KERNEL_WIDTH];
+            let mut sum = 0.0;
+            let mut values = [0.0f64; KERNEL_WIDTH];
+            for (i, value) in values.iter_mut().enumerate() {
+                let x = i as f64 - (KERNEL_WIDTH / 2) as f64 - offset;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    (PI * CUTOFF * x).sin() / (PI * CUTOFF * x)
+                };
+                // Blackman window
+                let w = (x + (KERNEL_WIDTH / 2) as f64) / KERNEL_WIDTH as f64;
+                let window = if (0.0..=1.0).contains(&w) {
+                    0.42 - 0.5 * (2.0 * PI * w).cos() + 0.08 * (4.0 * PI * w).cos()
+                } else {
+                    0.0
+                };
+                *value = sinc * window;
+                sum += *value;
+            }
+            for (k, value) in kernel.iter_mut().zip(values.iter()) {
+                *k = (value / sum) as f32;
+            }
+            kernels.push(kernel);
+        }
+        kernels
+    };
+}
+
+/// Band-limited step synthesis buffer.
+/// Amplitude changes clocked at the source rate are added as band-limited steps
+/// at their exact fractional position in the output sample stream, which avoids
+/// aliasing when down-sampling the APU output to a host sample rate.
+/// Inspired by blip_buf: http://www.slack.net/~ant/libs/audio.html.
+#[derive(Debug, Clone)]
+pub struct BlipBuffer {
+    /// Output samples per source clock.
+    factor: f64,
+    /// Output position of the current frame start (relative to the first unread sample).
+    frame_start: f64,
+    /// Output sample deltas, integrated on read.
+    deltas: Vec<f32>,
+    /// Running integration of deltas.
+    integrator: f64,
+}
+
+impl BlipBuffer {
+    /// Creates a buffer converting from clock_rate (Hz) to sample_rate (Hz).
+    pub fn new(clock_rate: f64, sample_rate: f64) -> Self {
+        Self {
+            factor: sample_rate / clock_rate,
+            frame_start: 0.0,
+            deltas: vec![0.0; KERNEL_WIDTH],
+            integrator: 0.0,
+        }
+    }
+
+    /// Changes conversion rates.
+    /// Takes effect for deltas added after the call: should be called between frames.
+    pub fn set_rates(&mut self, clock_rate: f64, sample_rate: f64) {
+        self.factor = sample_rate / clock_rate;
+    }
+
+    /// Number of samples ready to be read.
+    pub fn samples_available(&self) -> usize {
+        self.frame_start as usize
+    }
+
+    /// Adds an amplitude change at a clock time relative to current frame start.
+    pub fn add_delta(&mut self, time: u32, delta: f32) {
+        let pos = self.frame_start + time as f64 * self.factor;
+        let index = pos as usize;
+        let phase = ((pos - index as f64) * PHASE_COUNT as f64) as usize;
+
+        let end = index + KERNEL_WIDTH;
+        if self.deltas.len() < end {
+            self.deltas.resize(end, 0.0);
+        }
+
+        let kernel = &KERNELS[phase.min(PHASE_COUNT - 1)];
+        for (sample, k) in self.deltas[index..end].iter_mut().zip(kernel.iter()) {
+            *sample += delta * k;
+        }
+    }
+
+    /// Ends current frame after a given number of clocks.
+    /// Samples before the end of the frame become available.
+    pub fn end_frame(&mut self, time: u32) {
+        self.frame_start += time as f64 * self.factor;
+        let required = self.samples_available() + KERNEL_WIDTH;
+        if self.deltas.len() < required {
+            self.deltas.resize(required, 0.0);
+        }
+    }
+
+    /// Reads available samples, appending them to output.
+    /// Returns the number of samples read.
+    pub fn read_samples(&mut self, output: &mut Vec<f32>) -> usize {
+        let count = self.samples_available();
+        for delta in self.deltas.drain(..count) {
+            self.integrator += delta as f64;
+            output.push(self.integrator as f32);
+        }
+        if self.deltas.len() < KERNEL_WIDTH {
+            self.deltas.resize(KERNEL_WIDTH, 0.0);
+        }
+        self.frame_start -= count as f64;
+        count
+    }
+}
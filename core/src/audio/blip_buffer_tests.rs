@@ -0,0 +1,59 @@
+use super::blip_buffer::BlipBuffer;
+
+#[test]
+fn test_samples_available() {
+    let mut blip = BlipBuffer::new(1000.0, 100.0);
+    blip.end_frame(1000);
+    assert_eq!(blip.samples_available(), 100);
+    blip.end_frame(5);
+    assert_eq!(blip.samples_available(), 100);
+    blip.end_frame(5);
+    assert_eq!(blip.samples_available(), 101);
+
+    let mut output = vec![];
+    assert_eq!(blip.read_samples(&mut output), 101);
+    assert_eq!(output.len(), 101);
+    assert_eq!(blip.samples_available(), 0);
+}
+
+#[test]
+fn test_step_settles_on_delta() {
+    let mut blip = BlipBuffer::new(1000.0, 100.0);
+    blip.add_delta(15, 0.5);
+    blip.end_frame(1000);
+    let mut output = vec![];
+    blip.read_samples(&mut output);
+    assert!(output[0].abs() < 0.001);
+    assert!((output[99] - 0.5).abs() < 0.001);
+
+    // Steps spanning frames
+    blip.add_delta(0, -0.5);
+    blip.end_frame(1000);
+    output.clear();
+    blip.read_samples(&mut output);
+    assert!(output[99].abs() < 0.001);
+}
+
+#[test]
+fn test_high_frequency_is_filtered() {
+    // Square wave far above output Nyquist frequency
+    let mut blip = BlipBuffer::new(1_000_000.0, 44_100.0);
+    let mut amplitude = 0.0;
+    for time in 0..100_000 {
+        let next = if time % 2 == 0 { 1.0 } else { 0.0 };
+        blip.add_delta(time, next - amplitude);
+        amplitude = next;
+    }
+    blip.end_frame(100_000);
+    let mut output = vec![];
+    blip.read_samples(&mut output);
+
+    // Output settles on the average level
+    for sample in output[100..].iter() {
+        assert!(
+            (sample - 0.5).abs() < 0.05,
+            "sample {} is not filtered",
+            sample
+        );
+    }
+}
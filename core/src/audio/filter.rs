@@ -0,0 +1,57 @@
+use std::f32::consts::PI;
+
+/// First-order filter kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterKind {
+    LowPass,
+    HighPass,
+}
+
+/// First-order RC filter.
+/// Reference: https://en.wikipedia.org/wiki/Low-pass_filter#Simple_infinite_impulse_response_filter.
+#[derive(Debug, Clone)]
+pub struct FirstOrderFilter {
+    kind: FilterKind,
+    alpha: f32,
+    prev_input: f32,
+    prev_output: f32,
+}
+
+impl FirstOrderFilter {
+    /// Creates a filter with a cutoff frequency (Hz) for a given sample rate (Hz).
+    pub fn new(kind: FilterKind, cutoff: f32, sample_rate: f32) -> Self {
+        let rc = 1.0 / (2.0 * PI * cutoff);
+        let dt = 1.0 / sample_rate;
+        let alpha = match kind {
+            FilterKind::LowPass => dt / (rc + dt),
+            FilterKind::HighPass => rc / (rc + dt),
+        };
+        Self {
+            kind,
+            alpha,
+            prev_input: 0.0,
+            prev_output: 0.0,
+        }
+    }
+
+    /// Filters next sample.
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = match self.kind {
+            FilterKind::LowPass => self.prev_output + self.alpha * (input - self.prev_output),
+            FilterKind::HighPass => self.alpha * (self.prev_output + input - self.prev_input),
+        };
+        self.prev_input = input;
+        self.prev_output = output;
+        output
+    }
+}
+
+/// NES output filter chain: two high-pass filters (90 Hz and 440 Hz) and a low-pass filter (14 kHz).
+/// Source: https://www.nesdev.org/wiki/APU_Mixer
+pub(crate) fn nes_filters(sample_rate: f32) -> Vec<FirstOrderFilter> {
+    vec![
+        FirstOrderFilter::new(FilterKind::HighPass, 90.0, sample_rate),
+        FirstOrderFilter::new(FilterKind::HighPass, 440.0, sample_rate),
+        FirstOrderFilter::new(FilterKind::LowPass, 14000.0, sample_rate),
+    ]
+}
@@ -0,0 +1,214 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::nes::CPU_MHZ;
+
+use self::{
+    blip_buffer::BlipBuffer,
+    filter::{nes_filters, FirstOrderFilter},
+};
+
+pub mod blip_buffer;
+#[cfg(test)]
+mod blip_buffer_tests;
+
+pub mod filter;
+pub mod recorder;
+
+pub mod wav;
+#[cfg(test)]
+mod wav_tests;
+
+#[cfg(test)]
+mod mod_tests;
+
+/// Audio sample format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    F32,
+    I16,
+}
+
+/// Buffer of mono audio samples.
+#[derive(Debug)]
+pub enum Samples<'a> {
+    /// Samples between -1.0 and 1.0.
+    F32(&'a [f32]),
+    /// Samples between i16::MIN and i16::MAX.
+    I16(&'a [i16]),
+}
+
+/// Audio samples consumer (audio device, file, memory...).
+pub trait AudioSink {
+    /// Receives next buffer of samples, in the format requested by the audio configuration.
+    fn write_samples(&mut self, samples: Samples);
+
+    /// Fill level of the sink buffer, from 0.0 (empty) to 1.0 (full), if the sink has one.
+    /// When provided, the output sample rate is slightly adjusted to keep the buffer half full
+    /// (dynamic rate control), so that emulation can be paced by video without audio underruns.
+    fn fill_level(&self) -> Option<f32> {
+        None
+    }
+}
+
+impl AudioSink for Vec<f32> {
+    fn write_samples(&mut self, samples: Samples) {
+        match samples {
+            Samples::F32(s) => self.extend_from_slice(s),
+            Samples::I16(s) => self.extend(s.iter().map(|&x| x as f32 / i16::MAX as f32)),
+        }
+    }
+}
+
+impl AudioSink for Vec<i16> {
+    fn write_samples(&mut self, samples: Samples) {
+        match samples {
+            Samples::F32(s) => self.extend(s.iter().map(|&x| to_i16(x))),
+            Samples::I16(s) => self.extend_from_slice(s),
+        }
+    }
+}
+
+impl<T: AudioSink> AudioSink for Rc<RefCell<T>> {
+    fn write_samples(&mut self, samples: Samples) {
+        self.borrow_mut().write_samples(samples);
+    }
+
+    fn fill_level(&self) -> Option<f32> {
+        self.borrow().fill_level()
+    }
+}
+
+/// Maximum sample rate adjustment applied by dynamic rate control.
+/// Source: https://docs.libretro.com/development/cores/dynamic-rate-control/
+const MAX_RATE_DELTA: f64 = 0.005;
+
+/// Converts f32 sample to i16.
+fn to_i16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+}
+
+/// Audio output configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioConfig {
+    /// Output sample rate (Hz).
+    pub sample_rate: u32,
+    /// Output sample format.
+    pub format: SampleFormat,
+    /// Number of samples per buffer delivered to the sink.
+    pub buffer_size: usize,
+}
+
+impl AudioConfig {
+    /// Creates f32 configuration for a given sample rate, with 1024 samples buffers.
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            format: SampleFormat::F32,
+            buffer_size: 1024,
+        }
+    }
+}
+
+/// Audio output.
+/// Converts APU output clocked at CPU rate to sample buffers at the configured rate,
+/// applying NES output filters.
+pub struct AudioOutput {
+    config: AudioConfig,
+    blip: BlipBuffer,
+    filters: Vec<FirstOrderFilter>,
+    sink: Box<dyn AudioSink>,
+    clock_rate: f64,
+    /// Current dynamic rate control ratio.
+    rate_ratio: f64,
+    /// CPU cycles since last flush.
+    clock: u32,
+    clocks_per_buffer: u32,
+    amplitude: f32,
+    samples: Vec<f32>,
+    samples_i16: Vec<i16>,
+}
+
+impl AudioOutput {
+    /// Creates an audio output delivering samples to a sink.
+    pub fn new(config: AudioConfig, sink: Box<dyn AudioSink>) -> Self {
+        let clock_rate = CPU_MHZ as f64 * 1_000_000.0;
+        let clocks_per_buffer =
+            (clock_rate * config.buffer_size as f64 / config.sample_rate as f64) as u32;
+        Self {
+            config,
+            blip: BlipBuffer::new(clock_rate, config.sample_rate as f64),
+            filters: nes_filters(config.sample_rate as f32),
+            sink,
+            clock_rate,
+            rate_ratio: 1.0,
+            clock: 0,
+            clocks_per_buffer: clocks_per_buffer.max(1),
+            amplitude: 0.0,
+            samples: Vec::with_capacity(config.buffer_size + 1),
+            samples_i16: Vec::with_capacity(config.buffer_size + 1),
+        }
+    }
+
+    pub fn config(&self) -> &AudioConfig {
+        &self.config
+    }
+
+    /// Current dynamic rate control ratio (1.0 when the sink does not report its fill level).
+    pub fn rate_ratio(&self) -> f64 {
+        self.rate_ratio
+    }
+
+    /// Processes APU output for next CPU cycle.
+    pub fn clock(&mut self, amplitude: f32) {
+        if amplitude != self.amplitude {
+            self.blip.add_delta(self.clock, amplitude - self.amplitude);
+            self.amplitude = amplitude;
+        }
+        self.clock += 1;
+        if self.clock >= self.clocks_per_buffer {
+            self.flush();
+        }
+    }
+
+    /// Delivers samples produced so far to the sink.
+    pub fn flush(&mut self) {
+        self.blip.end_frame(self.clock);
+        self.clock = 0;
+
+        self.samples.clear();
+        self.blip.read_samples(&mut self.samples);
+        if self.samples.is_empty() {
+            return;
+        }
+
+        for sample in self.samples.iter_mut() {
+            for filter in self.filters.iter_mut() {
+                *sample = filter.process(*sample);
+            }
+        }
+
+        match self.config.format {
+            SampleFormat::F32 => self.sink.write_samples(Samples::F32(&self.samples)),
+            SampleFormat::I16 => {
+                self.samples_i16.clear();
+                self.samples_i16
+                    .extend(self.samples.iter().map(|&x| to_i16(x)));
+                self.sink.write_samples(Samples::I16(&self.samples_i16));
+            }
+        }
+
+        self.adjust_rate();
+    }
+
+    /// Adjusts output rate according to sink fill level (dynamic rate control).
+    fn adjust_rate(&mut self) {
+        if let Some(fill_level) = self.sink.fill_level() {
+            let fill_level = fill_level.clamp(0.0, 1.0) as f64;
+            self.rate_ratio = 1.0 + (1.0 - 2.0 * fill_level) * MAX_RATE_DELTA;
+            self.blip.set_rates(
+                self.clock_rate,
+                self.config.sample_rate as f64 * self.rate_ratio,
+            );
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::nes::CPU_MHZ;
+
+use super::{AudioConfig, AudioOutput, AudioSink, SampleFormat, Samples};
+
+const CPU_HZ: u32 = (CPU_MHZ * 1_000_000.0) as u32;
+
+#[test]
+fn test_render_to_memory_buffer() {
+    let buffer = Rc::new(RefCell::new(Vec::<f32>::new()));
+    let mut output = AudioOutput::new(AudioConfig::new(44_100), Box::new(Rc::clone(&buffer)));
+
+    // 1 second of silence
+    for _ in 0..CPU_HZ {
+        output.clock(0.0);
+    }
+    output.flush();
+
+    let buffer = buffer.borrow();
+    assert!((buffer.len() as i32 - 44_100).abs() <= 1);
+    assert!(buffer.iter().all(|s| *s == 0.0));
+}
+
+#[test]
+fn test_buffer_size() {
+    let buffer = Rc::new(RefCell::new(Vec::<f32>::new()));
+    let config = AudioConfig {
+        sample_rate: 48_000,
+        format: SampleFormat::F32,
+        buffer_size: 512,
+    };
+    let mut output = AudioOutput::new(config, Box::new(Rc::clone(&buffer)));
+
+    // Samples are delivered once a buffer is full
+    for _ in 0..(CPU_HZ / 48_000 * 500) {
+        output.clock(0.0);
+    }
+    assert!(buffer.borrow().is_empty());
+    for _ in 0..(CPU_HZ / 48_000 * 20) {
+        output.clock(0.0);
+    }
+    assert!((buffer.borrow().len() as i32 - 512).abs() <= 1);
+}
+
+#[test]
+fn test_high_pass_filters_dc() {
+    let buffer = Rc::new(RefCell::new(Vec::<i16>::new()));
+    let mut config = AudioConfig::new(44_100);
+    config.format = SampleFormat::I16;
+    let mut output = AudioOutput::new(config, Box::new(Rc::clone(&buffer)));
+
+    // Constant level after a step decays to silence
+    for _ in 0..(CPU_HZ / 10) {
+        output.clock(0.5);
+    }
+    output.flush();
+
+    let buffer = buffer.borrow();
+    let peak = buffer.iter().map(|s| s.abs()).max().unwrap();
+    assert!(peak > i16::MAX / 4);
+    assert!(buffer.last().unwrap().abs() < 100);
+}
+
+/// Sink reporting a constant fill level.
+struct FillLevelSink {
+    samples: Vec<f32>,
+    fill_level: f32,
+}
+
+impl AudioSink for FillLevelSink {
+    fn write_samples(&mut self, samples: Samples) {
+        self.samples.write_samples(samples);
+    }
+
+    fn fill_level(&self) -> Option<f32> {
+        Some(self.fill_level)
+    }
+}
+
+fn render_with_fill_level(fill_level: f32) -> (usize, f64) {
+    let sink = Rc::new(RefCell::new(FillLevelSink {
+        samples: vec![],
+        fill_level,
+    }));
+    let mut output = AudioOutput::new(AudioConfig::new(44_100), Box::new(Rc::clone(&sink)));
+    for _ in 0..CPU_HZ {
+        output.clock(0.0);
+    }
+    output.flush();
+    let count = sink.borrow().samples.len();
+    (count, output.rate_ratio())
+}
+
+#[test]
+fn test_dynamic_rate_control() {
+    // Half full buffer: nominal rate
+    let (count, ratio) = render_with_fill_level(0.5);
+    assert_eq!(ratio, 1.0);
+    assert!((count as i32 - 44_100).abs() <= 1);
+
+    // Empty buffer: more samples
+    let (count, ratio) = render_with_fill_level(0.0);
+    assert!(ratio > 1.0);
+    assert!(count > 44_200);
+
+    // Full buffer: less samples
+    let (count, ratio) = render_with_fill_level(1.0);
+    assert!(ratio < 1.0);
+    assert!(count < 44_000);
+}
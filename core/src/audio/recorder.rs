@@ -0,0 +1,136 @@
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::apu::{Apu, Channel};
+
+use super::{wav::WavWriter, AudioConfig, AudioOutput};
+
+/// Audio recording options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordingOptions {
+    /// Output sample rate (Hz).
+    pub sample_rate: u32,
+    /// Also records each APU channel to its own WAV file.
+    pub split_channels: bool,
+}
+
+impl RecordingOptions {
+    /// Creates options recording mixed output only.
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            split_channels: false,
+        }
+    }
+}
+
+impl Default for RecordingOptions {
+    fn default() -> Self {
+        Self::new(44_100)
+    }
+}
+
+/// Recorded WAV file.
+struct Track {
+    /// Recorded channel (mixed output if none).
+    channel: Option<Channel>,
+    path: PathBuf,
+    output: AudioOutput,
+    wav: Rc<RefCell<WavWriter<BufWriter<File>>>>,
+}
+
+impl Track {
+    fn create(path: PathBuf, channel: Option<Channel>, sample_rate: u32) -> io::Result<Self> {
+        let wav = Rc::new(RefCell::new(WavWriter::new(
+            BufWriter::new(File::create(&path)?),
+            sample_rate,
+        )?));
+        Ok(Self {
+            channel,
+            path,
+            output: AudioOutput::new(AudioConfig::new(sample_rate), Box::new(Rc::clone(&wav))),
+            wav,
+        })
+    }
+}
+
+/// Records APU output to WAV files.
+/// Each channel file is named after the mixed output file: "music.wav" gives "music.pulse1.wav",
+/// "music.triangle.wav"...
+pub struct Recorder {
+    tracks: Vec<Track>,
+}
+
+impl Recorder {
+    /// Creates WAV files and starts recording.
+    pub fn new<P: AsRef<Path>>(path: P, options: RecordingOptions) -> io::Result<Self> {
+        let path = path.as_ref();
+        let mut tracks = vec![Track::create(
+            path.to_path_buf(),
+            None,
+            options.sample_rate,
+        )?];
+        if options.split_channels {
+            for channel in Channel::ALL {
+                tracks.push(Track::create(
+                    channel_path(path, channel),
+                    Some(channel),
+                    options.sample_rate,
+                )?);
+            }
+        }
+        Ok(Self { tracks })
+    }
+
+    /// Recorded files, mixed output first.
+    pub fn paths(&self) -> Vec<&Path> {
+        self.tracks.iter().map(|t| t.path.as_path()).collect()
+    }
+
+    /// Records APU output for next CPU cycle.
+    pub fn clock(&mut self, apu: &Apu) {
+        for track in self.tracks.iter_mut() {
+            let amplitude = match track.channel {
+                Some(channel) => apu.channel_output(channel),
+                None => apu.output(),
+            };
+            track.output.clock(amplitude);
+        }
+    }
+
+    /// Writes pending samples and completes WAV files.
+    /// All files are completed, even if one fails: the first error is returned.
+    pub fn finish(mut self) -> io::Result<()> {
+        let mut result = Ok(());
+        for mut track in std::mem::take(&mut self.tracks) {
+            track.output.flush();
+            let finished = track.wav.borrow_mut().finish();
+            result = result.and(finished);
+        }
+        result
+    }
+}
+
+/// Completes WAV files if recording is not finished (see finish), ignoring errors.
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        for track in self.tracks.iter_mut() {
+            track.output.flush();
+            let _ = track.wav.borrow_mut().finish();
+        }
+    }
+}
+
+/// Channel file path: channel name is inserted before extension.
+fn channel_path(path: &Path, channel: Channel) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map_or("wav".into(), |e| e.to_string_lossy());
+    path.with_file_name(format!("{}.{}.{}", stem, channel.name(), extension))
+}
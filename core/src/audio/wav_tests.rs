@@ -0,0 +1,58 @@
+use std::io::Cursor;
+
+use super::{wav::WavWriter, AudioSink, Samples};
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+#[test]
+fn test_header() {
+    let mut wav = WavWriter::new(Cursor::new(vec![]), 44_100).unwrap();
+    wav.finish().unwrap();
+    let bytes = wav.get_ref().get_ref();
+
+    assert_eq!(bytes.len(), 44);
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(u32_at(bytes, 4), 36);
+    assert_eq!(&bytes[8..16], b"WAVEfmt ");
+    assert_eq!(u32_at(bytes, 16), 16);
+    // PCM, mono
+    assert_eq!(u16_at(bytes, 20), 1);
+    assert_eq!(u16_at(bytes, 22), 1);
+    assert_eq!(u32_at(bytes, 24), 44_100);
+    assert_eq!(u32_at(bytes, 28), 88_200);
+    assert_eq!(u16_at(bytes, 32), 2);
+    assert_eq!(u16_at(bytes, 34), 16);
+    assert_eq!(&bytes[36..40], b"data");
+    assert_eq!(u32_at(bytes, 40), 0);
+}
+
+#[test]
+fn test_samples() {
+    let mut wav = WavWriter::new(Cursor::new(vec![]), 48_000).unwrap();
+    wav.write_samples(Samples::I16(&[1, -2]));
+    wav.write_samples(Samples::F32(&[1.0, -1.0, 2.0]));
+    wav.finish().unwrap();
+    let bytes = wav.get_ref().get_ref();
+
+    assert_eq!(bytes.len(), 44 + 10);
+    assert_eq!(u32_at(bytes, 4), 36 + 10);
+    assert_eq!(u32_at(bytes, 40), 10);
+    let samples: Vec<i16> = bytes[44..]
+        .chunks(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect();
+    assert_eq!(samples, vec![1, -2, i16::MAX, -i16::MAX, i16::MAX]);
+
+    // Writing goes on after finish
+    wav.write_samples(Samples::I16(&[3]));
+    wav.finish().unwrap();
+    let bytes = wav.get_ref().get_ref();
+    assert_eq!(bytes.len(), 44 + 12);
+    assert_eq!(u32_at(bytes, 40), 12);
+}
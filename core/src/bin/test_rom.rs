@@ -0,0 +1,137 @@
+//! Runs test ROMs reporting their status at $6000 (blargg's test ROMs), headlessly.
+//! Directories are searched recursively for .nes files, so a ROM directory can be run as a regression suite.
+//!
+//! ```bash
+//! $ cargo run --release -p emultendo-core --bin test_rom -- path/to/nrom-test-roms
+//! ```
+//! Only mapper 0 (NROM) ROMs are supported, so most blargg suites (e.g. instr_test-v5 uses MMC1) can't run yet.
+//! Options:
+//! - `--timeout <seconds>`: emulated time after which a ROM fails (30 by default)
+//! - `--verbose`: prints messages of passed ROMs too
+//!
+//! Exit status is 0 if all ROMs pass, 1 if any fails, 2 on usage error.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::exit,
+    time::Duration,
+};
+
+use emultendo_core::{
+    cartridge::Cartridge,
+    nes::{
+        test_rom::{run_test_rom, TestRomOptions, TestRomResult},
+        Nes,
+    },
+};
+
+const USAGE: &str = "Usage: test_rom <rom or directory>... [--timeout <seconds>] [--verbose]";
+
+/// Parses command line: ROM files or directories, options and verbose flag.
+fn parse_args(args: &[String]) -> Result<(Vec<PathBuf>, TestRomOptions, bool), String> {
+    let mut paths = vec![];
+    let mut options = TestRomOptions::new();
+    let mut verbose = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--timeout" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("missing value for {}", arg))?;
+                let seconds: f64 = value
+                    .parse()
+                    .map_err(|_| format!("invalid timeout '{}'", value))?;
+                options.timeout = Duration::try_from_secs_f64(seconds)
+                    .map_err(|_| format!("invalid timeout '{}'", value))?;
+            }
+            "--verbose" => verbose = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        return Err(String::from("expected ROM files or directories"));
+    }
+    Ok((paths, options, verbose))
+}
+
+/// ROM files of path: path itself if it is a file, .nes files below it if it is a directory.
+fn rom_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let entries = fs::read_dir(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            rom_files(&path, files)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("nes"))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Runs a ROM file.
+fn run(rom: &Path, options: &TestRomOptions) -> Result<TestRomResult, String> {
+    let cartridge =
+        Cartridge::from_file(rom).map_err(|error| format!("cannot load ROM: {:?}", error))?;
+    if cartridge.mapper() != 0 {
+        return Err(format!("mapper {} is not supported", cartridge.mapper()));
+    }
+    let mut nes = Nes::new(None, None);
+    nes.insert(cartridge);
+    nes.reset();
+    run_test_rom(&mut nes, options).map_err(|error| format!("emulation error: {:?}", error))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (paths, options, verbose) = parse_args(&args).unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        exit(2);
+    });
+    let mut roms = vec![];
+    for path in &paths {
+        if let Err(error) = rom_files(path, &mut roms) {
+            eprintln!("{}", error);
+            exit(2);
+        }
+    }
+
+    let mut failed = 0;
+    for rom in &roms {
+        match run(rom, &options) {
+            Ok(result) => {
+                let label = if result.passed() { "PASS" } else { "FAIL" };
+                println!("{} {} ({})", label, rom.display(), result.status);
+                if (verbose || !result.passed()) && !result.message.is_empty() {
+                    for line in result.message.lines() {
+                        println!("    {}", line);
+                    }
+                }
+                if !result.passed() {
+                    failed += 1;
+                }
+            }
+            Err(error) => {
+                println!("ERROR {}: {}", rom.display(), error);
+                failed += 1;
+            }
+        }
+    }
+
+    println!("{} passed, {} failed", roms.len() - failed, failed);
+    if failed > 0 {
+        exit(1);
+    }
+}
@@ -0,0 +1,99 @@
+//! Runs a ROM headlessly and compares its trace to a reference log (nestest.log format).
+//! Reports the first mismatching line, with context and differing columns.
+//!
+//! ```bash
+//! $ cargo run -p emultendo-core --bin trace_diff -- res/nestest.nes res/nestest.log --start-at C000
+//! ```
+//! Options:
+//! - `--start-at <hex address>`: program counter at first instruction
+//! - `--ignore <columns>`: comma separated columns that may differ (pc, bytes, asm, a, x, y, p, sp, ppu, cyc)
+//! - `--context <lines>`: lines shown around the mismatch (3 by default)
+//!
+//! Exit status is 0 if traces match, 1 on mismatch, 2 on error.
+
+use std::{env, process::exit};
+
+use emultendo_core::{
+    cartridge::Cartridge,
+    nes::{
+        tools::load_trace,
+        trace_diff::{diff_trace, TraceDiffOptions},
+        Nes,
+    },
+};
+
+const USAGE: &str = "Usage: trace_diff <rom> <reference log> [--start-at <hex address>] \
+                     [--ignore <column,...>] [--context <lines>]";
+
+/// Parses command line: ROM file, reference log file and options.
+fn parse_args(args: &[String]) -> Result<(String, String, TraceDiffOptions), String> {
+    let mut files = vec![];
+    let mut options = TraceDiffOptions::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {}", arg))
+        };
+        match arg.as_str() {
+            "--start-at" => {
+                let value = value()?;
+                let address = u16::from_str_radix(value.trim_start_matches('$'), 16)
+                    .map_err(|_| format!("invalid address '{}'", value))?;
+                options.start_at = Some(address);
+            }
+            "--ignore" => {
+                for column in value()?.split(',') {
+                    options.ignored_columns.push(column.trim().parse()?);
+                }
+            }
+            "--context" => {
+                let value = value()?;
+                options.context = value
+                    .parse()
+                    .map_err(|_| format!("invalid line count '{}'", value))?;
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ => files.push(arg.clone()),
+        }
+    }
+    match <[String; 2]>::try_from(files) {
+        Ok([rom, reference]) => Ok((rom, reference, options)),
+        Err(_) => Err(String::from("expected ROM and reference log files")),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (rom, reference, options) = match parse_args(&args) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            exit(2);
+        }
+    };
+
+    let reference = load_trace(&reference).unwrap_or_else(|error| {
+        eprintln!("Cannot load {}: {}", reference, error);
+        exit(2);
+    });
+    let cartridge = Cartridge::from_file(&rom).unwrap_or_else(|error| {
+        eprintln!("Cannot load {}: {:?}", rom, error);
+        exit(2);
+    });
+
+    let mut nes = Nes::new(None, None);
+    nes.insert(cartridge);
+    nes.reset();
+    match diff_trace(&mut nes, &reference, &options) {
+        Ok(None) => println!("{} lines match", reference.len()),
+        Ok(Some(mismatch)) => {
+            print!("{}", mismatch);
+            exit(1);
+        }
+        Err(error) => {
+            eprintln!("Emulation error: {:?}", error);
+            exit(2);
+        }
+    }
+}
@@ -1,6 +1,14 @@
+use bitflags::bitflags;
 use std::{cell::RefCell, rc::Rc};
 
-use crate::{cartridge::Cartridge, controller::Joypad, memory::Memory, ppu::Ppu};
+use super::{oam_dma::OamDma, Bus, BusError};
+use crate::{
+    apu::{dmc, Apu},
+    cartridge::Cartridge,
+    controller::Joypad,
+    memory::Memory,
+    ppu::Ppu,
+};
 
 //  _______________ $10000  _______________
 // | PRG-ROM       |       |               |
@@ -32,17 +40,42 @@ use crate::{cartridge::Cartridge, controller::Joypad, memory::Memory, ppu::Ppu};
 const RAM: u16 = 0x0000;
 const RAM_MIRRORS_END: u16 = 0x1FFF;
 const PPU_REGISTERS_MIRRORS_END: u16 = 0x3FFF;
+const PRG_RAM: u16 = 0x6000;
+const PRG_RAM_END: u16 = 0x7FFF;
 const PRG_ROM: u16 = 0x8000;
 const PRG_ROM_END: u16 = 0xFFFF;
 
+bitflags! {
+    /// IRQ sources.
+    /// IRQ line is level triggered and shared (wired-OR): it stays asserted while any source is.
+    pub struct IrqSource: u8 {
+        const APU_FRAME_COUNTER = 0b0000_0001;
+        const APU_DMC           = 0b0000_0010;
+        const MAPPER            = 0b0000_0100;
+        const EXTERNAL          = 0b0000_1000;
+    }
+}
+
 /// NES CPU connection bus.
 #[derive(Debug, Clone)]
 pub struct CpuBus {
     vram: [u8; 2048],
+    /// Cartridge PRG RAM (SRAM), always present.
+    prg_ram: [u8; 0x2000],
     prg_rom: Vec<u8>,
     ppu: Option<Rc<RefCell<Ppu>>>,
+    apu: Option<Rc<RefCell<Apu>>>,
     joypad1: Option<Rc<RefCell<Joypad>>>,
     joypad2: Option<Rc<RefCell<Joypad>>>,
+    /// IRQ lines asserted through set_irq.
+    irq_lines: IrqSource,
+    /// OAM DMA in progress.
+    oam_dma: Option<OamDma>,
+    /// Last value on the data bus, read back from unmapped addresses (open bus).
+    /// Source: https://www.nesdev.org/wiki/Open_bus_behavior
+    open_bus: u8,
+    /// First unexpected access since last take_error.
+    error: Option<BusError>,
 }
 
 impl CpuBus {
@@ -50,13 +83,29 @@ impl CpuBus {
     pub fn new() -> Self {
         CpuBus {
             vram: [0; 2048],
+            prg_ram: [0; 0x2000],
             prg_rom: vec![],
             ppu: None,
+            apu: None,
             joypad1: None,
             joypad2: None,
+            irq_lines: IrqSource::empty(),
+            oam_dma: None,
+            open_bus: 0,
+            error: None,
         }
     }
 
+    /// Last value on the data bus.
+    pub fn open_bus(&self) -> u8 {
+        self.open_bus
+    }
+
+    /// Takes unexpected access, if any occurred since last call.
+    pub fn take_error(&mut self) -> Option<BusError> {
+        self.error.take()
+    }
+
     /// Checks if a cartridge is connected
     pub fn cartridge_connected(&self) -> bool {
         self.prg_rom.len() > 0
@@ -72,6 +121,11 @@ impl CpuBus {
         self.ppu = Some(Rc::clone(ppu));
     }
 
+    /// Connects APU to the bus.
+    pub fn connect_apu(&mut self, apu: &Rc<RefCell<Apu>>) {
+        self.apu = Some(Rc::clone(apu));
+    }
+
     /// Connects Joypad 1 to the bus
     pub fn connect_joypad1(&mut self, joypad: &Rc<RefCell<Joypad>>) {
         self.joypad1 = Some(Rc::clone(joypad));
@@ -92,83 +146,250 @@ impl CpuBus {
         self.prg_rom[addr as usize]
     }
 
-    pub fn poll_nmi_status(&self) -> bool {
+    /// Asserts (or releases) IRQ line for a source without its own device on the bus
+    /// (mapper, expansion port...).
+    /// APU sources are read from APU, and cannot be set.
+    pub fn set_irq(&mut self, source: IrqSource, asserted: bool) {
+        self.irq_lines.set(source, asserted);
+    }
+
+    /// Sources currently asserting IRQ line.
+    pub fn irq_sources(&self) -> IrqSource {
+        let mut sources = self.irq_lines;
+        sources.remove(IrqSource::APU_FRAME_COUNTER | IrqSource::APU_DMC);
+        if let Some(apu) = &self.apu {
+            let apu = apu.borrow();
+            sources.set(
+                IrqSource::APU_FRAME_COUNTER,
+                apu.frame_counter().interrupt_flag(),
+            );
+            sources.set(IrqSource::APU_DMC, apu.dmc().irq_flag());
+        }
+        sources
+    }
+
+    /// Fetches DMC sample byte if needed.
+    /// Returns true if a fetch occurred.
+    fn fetch_dmc_sample(&mut self) -> bool {
+        let addr = match &self.apu {
+            Some(apu) => apu.borrow().dmc_dma_address(),
+            None => None,
+        };
+
+        if let Some(addr) = addr {
+            let data = self.mem_read(addr);
+            if let Some(apu) = &self.apu {
+                apu.borrow_mut().load_dmc_sample(data);
+            }
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Bus for CpuBus {
+    /// Indicates if IRQ line is asserted.
+    /// Unlike NMI, IRQ is not acknowledged by polling: sources release the line themselves.
+    fn poll_irq_status(&self) -> bool {
+        !self.irq_sources().is_empty()
+    }
+
+    fn poll_nmi_status(&mut self) -> bool {
         if let Some(ppu) = &self.ppu {
             return ppu.borrow_mut().poll_nmi_status();
         } else {
             panic!("PPU is not connected to CPU bus");
         }
     }
+
+    /// Performs pending DMC sample fetch.
+    /// Returns the number of CPU cycles stolen by the DMA (0 if no fetch occurred).
+    /// During OAM DMA, DMC sample is fetched by dma_cycle instead.
+    fn poll_dmc_dma(&mut self) -> u8 {
+        if self.oam_dma.is_none() && self.fetch_dmc_sample() {
+            dmc::DMA_CYCLES
+        } else {
+            0
+        }
+    }
+
+    /// Indicates if OAM DMA is pending or in progress.
+    fn dma_in_progress(&self) -> bool {
+        self.oam_dma.is_some()
+    }
+
+    /// Performs next OAM DMA cycle, if OAM DMA is in progress.
+    /// DMA reads on get cycles and writes on put cycles (CPU cycle parity).
+    /// Returns true if the CPU is halted on this cycle.
+    fn dma_cycle(&mut self, get_cycle: bool) -> bool {
+        let mut dma = match self.oam_dma.take() {
+            Some(dma) => dma,
+            None => return false,
+        };
+
+        if !dma.halted() {
+            dma.halt();
+        } else if get_cycle {
+            // DMC fetch takes over the get cycle: OAM DMA realigns on next one
+            if !self.fetch_dmc_sample() {
+                let data = self.mem_read(dma.address());
+                dma.load(data);
+            }
+        } else if let Some(data) = dma.take() {
+            self.mem_write(0x2004, data);
+        }
+
+        if !dma.is_done() {
+            self.oam_dma = Some(dma);
+        }
+        true
+    }
+
+    fn video_position(&self) -> Option<(u16, u16)> {
+        self.ppu.as_ref().map(|ppu| {
+            let ppu = ppu.borrow();
+            (ppu.scanline(), ppu.cycles() as u16)
+        })
+    }
+
+    /// 16KB PRG ROM bank.
+    fn bank(&self, addr: u16) -> Option<u16> {
+        match addr {
+            PRG_ROM..=PRG_ROM_END if !self.prg_rom.is_empty() => {
+                let offset = (addr - PRG_ROM) as usize % self.prg_rom.len();
+                Some((offset / 0x4000) as u16)
+            }
+            _ => None,
+        }
+    }
 }
 
 impl Memory for CpuBus {
     /// Reads memory address.
+    /// Unmapped and write-only addresses return last value on the data bus (open bus).
     fn mem_read(&mut self, addr: u16) -> u8 {
-        match addr {
+        let data = match addr {
             RAM..=RAM_MIRRORS_END => {
                 let mirror_down_addr = addr & 0b00000111_11111111;
                 self.vram[mirror_down_addr as usize]
             }
-            0x2000 | 0x2001 | 0x2003 | 0x2005 | 0x2006 | 0x4014 => {
-                // Commented to keep the CPU trace working
-                //panic!("attempt to read from write-only PPU address {:x}", addr);
-                0
+            0x2000 | 0x2001 | 0x2003 | 0x2005 | 0x2006 => {
+                // Write-only PPU registers: PPU I/O latch
+                if let Some(ppu) = &self.ppu {
+                    ppu.borrow().io_latch()
+                } else {
+                    panic!("PPU is not connected to CPU bus");
+                }
             }
             0x2002 => {
                 if let Some(ppu) = &self.ppu {
-                    return ppu.borrow_mut().read_status();
+                    ppu.borrow_mut().read_status()
                 } else {
                     panic!("PPU is not connected to CPU bus");
                 }
             }
             0x2004 => {
                 if let Some(ppu) = &self.ppu {
-                    return ppu.borrow_mut().read_oam_data();
+                    ppu.borrow_mut().read_oam_data()
                 } else {
                     panic!("PPU is not connected to CPU bus");
                 }
             }
             0x2007 => {
                 if let Some(ppu) = &self.ppu {
-                    return ppu.borrow_mut().read_data();
+                    ppu.borrow_mut().read_data()
                 } else {
                     panic!("PPU is not connected to CPU bus");
                 }
             }
-            0x4000..=0x4015 => {
-                //Ignore APU
-                0
+            0x4015 => {
+                // Read inside the CPU: data bus is not driven, bit 5 is open bus
+                let status = match &self.apu {
+                    Some(apu) => apu.borrow_mut().read_status(),
+                    None => 0,
+                };
+                return (status & 0b1101_1111) | (self.open_bus & 0b0010_0000);
             }
 
+            // Joypads drive low bits only, others are open bus (usually $40 or $41)
             0x4016 => {
-                if let Some(joypad1) = &self.joypad1 {
-                    joypad1.borrow_mut().read()
-                } else {
-                    0
-                }
+                let data = match &self.joypad1 {
+                    Some(joypad1) => joypad1.borrow_mut().read(),
+                    None => 0,
+                };
+                (self.open_bus & 0b1110_0000) | (data & 0b0001_1111)
             }
             0x4017 => {
-                if let Some(joypad2) = &self.joypad2 {
-                    joypad2.borrow_mut().read()
-                } else {
-                    0
-                }
+                let data = match &self.joypad2 {
+                    Some(joypad2) => joypad2.borrow_mut().read(),
+                    None => 0,
+                };
+                (self.open_bus & 0b1110_0000) | (data & 0b0001_1111)
             }
 
             0x2008..=PPU_REGISTERS_MIRRORS_END => {
                 let mirror_down_addr = addr & 0b00100000_00000111;
                 self.mem_read(mirror_down_addr)
             }
+            PRG_RAM..=PRG_RAM_END => self.prg_ram[(addr - PRG_RAM) as usize],
             PRG_ROM..=PRG_ROM_END => self.read_prg_rom(addr),
             _ => {
-                // Ignore access
-                0
+                // Write-only APU registers, OAM DMA, unmapped addresses
+                self.open_bus
+            }
+        };
+        self.open_bus = data;
+        data
+    }
+
+    /// Reads memory address without side effects: PPU, APU and joypad registers are peeked,
+    /// and open bus is not updated.
+    fn mem_peek(&self, addr: u16) -> u8 {
+        match addr {
+            RAM..=RAM_MIRRORS_END => self.vram[(addr & 0b00000111_11111111) as usize],
+            0x2000..=PPU_REGISTERS_MIRRORS_END => {
+                let ppu = match &self.ppu {
+                    Some(ppu) => ppu.borrow(),
+                    None => panic!("PPU is not connected to CPU bus"),
+                };
+                match addr & 0b00100000_00000111 {
+                    0x2002 => ppu.peek_status(),
+                    0x2004 => ppu.peek_oam_data(),
+                    0x2007 => ppu.peek_data(),
+                    _ => ppu.io_latch(),
+                }
+            }
+            0x4015 => {
+                let status = match &self.apu {
+                    Some(apu) => apu.borrow().peek_status(),
+                    None => 0,
+                };
+                (status & 0b1101_1111) | (self.open_bus & 0b0010_0000)
+            }
+            0x4016 => {
+                let data = match &self.joypad1 {
+                    Some(joypad1) => joypad1.borrow().peek(),
+                    None => 0,
+                };
+                (self.open_bus & 0b1110_0000) | (data & 0b0001_1111)
             }
+            0x4017 => {
+                let data = match &self.joypad2 {
+                    Some(joypad2) => joypad2.borrow().peek(),
+                    None => 0,
+                };
+                (self.open_bus & 0b1110_0000) | (data & 0b0001_1111)
+            }
+            PRG_RAM..=PRG_RAM_END => self.prg_ram[(addr - PRG_RAM) as usize],
+            PRG_ROM..=PRG_ROM_END => self.read_prg_rom(addr),
+            _ => self.open_bus,
         }
     }
 
     /// Writes to memory address.
     fn mem_write(&mut self, addr: u16, data: u8) {
+        self.open_bus = data;
         match addr {
             RAM..=RAM_MIRRORS_END => {
                 let mirror_down_addr = addr & 0b11111111111;
@@ -188,7 +409,13 @@ impl Memory for CpuBus {
                     panic!("PPU is not connected to CPU bus");
                 }
             }
-            0x2002 => panic!("attempt to write to PPU status register"),
+            0x2002 => {
+                if let Some(ppu) = &self.ppu {
+                    return ppu.borrow_mut().write_to_status(data);
+                } else {
+                    panic!("PPU is not connected to CPU bus");
+                }
+            }
             0x2003 => {
                 if let Some(ppu) = &self.ppu {
                     return ppu.borrow_mut().write_to_oam_addr(data);
@@ -224,43 +451,39 @@ impl Memory for CpuBus {
                     panic!("PPU is not connected to CPU bus");
                 }
             }
-            0x4000..=0x4013 | 0x4015 => {
-                // Ignore APU
+            // $4017 writes go to APU frame counter, while $4017 reads come from joypad 2
+            0x4000..=0x4013 | 0x4015 | 0x4017 => {
+                if let Some(apu) = &self.apu {
+                    apu.borrow_mut().write_register(addr, data);
+                }
             }
 
+            // Strobe is shared by both joypads
             0x4016 => {
                 if let Some(joypad1) = &self.joypad1 {
                     joypad1.borrow_mut().write(data)
                 }
-            }
-
-            0x4017 => {
                 if let Some(joypad2) = &self.joypad2 {
                     joypad2.borrow_mut().write(data)
                 }
             }
 
             // https://wiki.nesdev.com/w/index.php/PPU_programmer_reference#OAM_DMA_.28.244014.29_.3E_write
+            // Transfer is performed by dma_cycle, while the CPU is halted
             0x4014 => {
-                let mut buffer: [u8; 256] = [0; 256];
-                let hi: u16 = (data as u16) << 8;
-                for i in 0..256u16 {
-                    buffer[i as usize] = self.mem_read(hi + i);
-                }
-
-                if let Some(ppu) = &self.ppu {
-                    return ppu.borrow_mut().write_oam_dma(&buffer);
-                } else {
-                    panic!("PPU is not connected to CPU bus");
-                }
+                self.oam_dma = Some(OamDma::new(data));
             }
             0x2008..=PPU_REGISTERS_MIRRORS_END => {
                 let mirror_down_addr = addr & 0b00100000_00000111;
                 self.mem_write(mirror_down_addr, data);
             }
+            PRG_RAM..=PRG_RAM_END => {
+                self.prg_ram[(addr - PRG_RAM) as usize] = data;
+            }
             PRG_ROM..=PRG_ROM_END => {
                 // Attempt to write to Cartridge ROM space
-                panic!("attempt to write to cartridge PRG ROM space");
+                self.error
+                    .get_or_insert(BusError::PrgRomWrite { addr, data });
             }
             _ => {
                 // Ignore access
@@ -1,4 +1,17 @@
-use crate::{bus::cpu_bus::CpuBus, cartridge::Cartridge, memory::Memory};
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    apu::Apu,
+    bus::{
+        cpu_bus::{CpuBus, IrqSource},
+        ppu_bus::PpuBus,
+        Bus, BusError,
+    },
+    cartridge::Cartridge,
+    controller::{Joypad, JoypadButton},
+    memory::Memory,
+    ppu::Ppu,
+};
 
 #[test]
 fn test_ram_read_write() {
@@ -9,6 +22,16 @@ fn test_ram_read_write() {
     assert_eq!(bus.mem_read_u16(0x0200), 0x01);
 }
 
+#[test]
+fn test_prg_ram_read_write() {
+    let mut bus = CpuBus::new();
+    bus.mem_write(0x6000, 0x80);
+    bus.mem_write(0x7fff, 0x01);
+    assert_eq!(bus.mem_read(0x6000), 0x80);
+    assert_eq!(bus.mem_peek(0x7fff), 0x01);
+    assert_eq!(bus.mem_read(0x6001), 0);
+}
+
 #[test]
 fn test_prg_rom_read() {
     let mut bus = CpuBus::new();
@@ -23,9 +46,255 @@ fn test_prg_rom_read() {
 }
 
 #[test]
-#[should_panic]
 fn test_prg_rom_not_writable() {
     let mut bus = CpuBus::new();
+    bus.connect_cartridge(&Cartridge::from_code(&[0x00, 0x00]));
     bus.mem_write_u16(0x8000, 0x001);
     assert_eq!(bus.mem_read_u16(0x8000), 0);
+
+    // First write is reported
+    assert_eq!(
+        bus.take_error(),
+        Some(BusError::PrgRomWrite {
+            addr: 0x8000,
+            data: 0x01
+        })
+    );
+    assert_eq!(bus.take_error(), None);
+}
+
+#[test]
+fn test_dmc_dma() {
+    let mut cartridge = Cartridge::from_code(&[]);
+    cartridge.prg_rom[0x4000] = 0x55;
+    let apu = Rc::new(RefCell::new(Apu::new()));
+    let mut bus = CpuBus::new();
+    bus.connect_cartridge(&cartridge);
+    bus.connect_apu(&apu);
+
+    // No sample playing: no DMA
+    assert_eq!(bus.poll_dmc_dma(), 0);
+
+    // Enable DMC: fetches first sample byte at $C000
+    bus.mem_write(0x4015, 0b0001_0000);
+    assert_eq!(bus.mem_read(0x4015), 0b0001_0000);
+    assert_eq!(bus.poll_dmc_dma(), 4);
+    assert_eq!(apu.borrow().dmc().bytes_remaining(), 0);
+    assert_eq!(bus.mem_read(0x4015), 0);
+
+    // Sample buffer is full: no DMA
+    assert_eq!(bus.poll_dmc_dma(), 0);
+}
+
+#[test]
+fn test_0x4017_routing() {
+    let apu = Rc::new(RefCell::new(Apu::new()));
+    let joypad2 = Rc::new(RefCell::new(Joypad::new()));
+    joypad2
+        .borrow_mut()
+        .set_button_pressed_status(JoypadButton::BUTTON_A, true);
+    let mut bus = CpuBus::new();
+    bus.connect_apu(&apu);
+    bus.connect_joypad2(&joypad2);
+
+    // $4017 write goes to APU frame counter, not joypad 2 strobe
+    // (upper bits are open bus: last written value)
+    bus.mem_write(0x4017, 0b1000_0001);
+    assert!(apu.borrow().frame_counter().five_step_mode());
+    assert_eq!(bus.mem_read(0x4017), 0b1000_0001);
+    assert_eq!(bus.mem_read(0x4017), 0b1000_0000);
+
+    // Joypad 2 is strobed through $4016
+    bus.mem_write(0x4016, 1);
+    bus.mem_write(0x4016, 0);
+    assert_eq!(bus.mem_read(0x4017), 1);
+}
+
+#[test]
+fn test_irq_sources() {
+    let mut bus = CpuBus::new();
+    let apu = Rc::new(RefCell::new(Apu::new()));
+    bus.connect_apu(&apu);
+    assert!(!bus.poll_irq_status());
+
+    // Line stays asserted while any source asserts it
+    bus.set_irq(IrqSource::MAPPER, true);
+    bus.set_irq(IrqSource::EXTERNAL, true);
+    assert_eq!(bus.irq_sources(), IrqSource::MAPPER | IrqSource::EXTERNAL);
+    bus.set_irq(IrqSource::MAPPER, false);
+    assert!(bus.poll_irq_status());
+    bus.set_irq(IrqSource::EXTERNAL, false);
+    assert!(!bus.poll_irq_status());
+
+    // Frame counter interrupt (4-step mode, IRQ enabled)
+    for _ in 0..29830 {
+        apu.borrow_mut().tick();
+    }
+    assert_eq!(bus.irq_sources(), IrqSource::APU_FRAME_COUNTER);
+    // Not acknowledged by polling
+    assert!(bus.poll_irq_status());
+    assert!(bus.poll_irq_status());
+    // Acknowledged by reading $4015
+    bus.mem_read(0x4015);
+    assert!(!bus.poll_irq_status());
+
+    // APU sources cannot be set
+    bus.set_irq(IrqSource::APU_DMC, true);
+    assert!(!bus.poll_irq_status());
+}
+
+/// Runs OAM DMA of page $02, first cycle being a get or put cycle.
+/// Returns the number of cycles the CPU is halted.
+fn run_oam_dma(bus: &mut CpuBus, first_get_cycle: bool) -> usize {
+    bus.mem_write(0x4014, 0x02);
+    let mut cycles = 0;
+    while bus.dma_cycle((cycles % 2 == 0) == first_get_cycle) {
+        cycles += 1;
+    }
+    cycles
+}
+
+#[test]
+fn test_oam_dma() {
+    let ppu = Rc::new(RefCell::new(Ppu::new()));
+    let mut bus = CpuBus::new();
+    bus.connect_ppu(&ppu);
+    for i in 0..=255u8 {
+        bus.mem_write(0x0200 + i as u16, i);
+    }
+
+    // No transfer: CPU is not halted
+    assert!(!bus.dma_cycle(true));
+
+    // Halt cycle, then transfer starts on a get cycle
+    assert_eq!(run_oam_dma(&mut bus, false), 513);
+    assert!(!bus.dma_in_progress());
+    for i in 0..=255u8 {
+        assert_eq!(ppu.borrow().oam_data()[i as usize], i);
+    }
+
+    // Halt cycle, then alignment cycle
+    assert_eq!(run_oam_dma(&mut bus, true), 514);
+}
+
+#[test]
+fn test_oam_dma_with_dmc_dma() {
+    let cartridge = Cartridge::from_code(&[]);
+    let ppu = Rc::new(RefCell::new(Ppu::new()));
+    let apu = Rc::new(RefCell::new(Apu::new()));
+    let mut bus = CpuBus::new();
+    bus.connect_cartridge(&cartridge);
+    bus.connect_ppu(&ppu);
+    bus.connect_apu(&apu);
+
+    // DMC sample fetch is pending: it is done by OAM DMA
+    bus.mem_write(0x4015, 0b0001_0000);
+    bus.mem_write(0x4014, 0x02);
+    assert_eq!(bus.poll_dmc_dma(), 0);
+
+    // DMC fetch takes a get cycle, and OAM DMA realigns
+    let mut cycles = 0;
+    while bus.dma_cycle(cycles % 2 == 1) {
+        cycles += 1;
+    }
+    assert_eq!(cycles, 515);
+    assert_eq!(apu.borrow().dmc().bytes_remaining(), 0);
+}
+
+#[test]
+fn test_open_bus() {
+    let ppu = Rc::new(RefCell::new(Ppu::new()));
+    let apu = Rc::new(RefCell::new(Apu::new()));
+    let joypad1 = Rc::new(RefCell::new(Joypad::new()));
+    joypad1
+        .borrow_mut()
+        .set_button_pressed_status(JoypadButton::BUTTON_A, true);
+    let mut bus = CpuBus::new();
+    bus.connect_ppu(&ppu);
+    bus.connect_apu(&apu);
+    bus.connect_joypad1(&joypad1);
+
+    // Unmapped and write-only APU addresses return last value on the bus
+    bus.mem_write(0x0010, 0x42);
+    bus.mem_read(0x0010);
+    assert_eq!(bus.mem_read(0x5000), 0x42);
+    assert_eq!(bus.mem_read(0x4000), 0x42);
+    assert_eq!(bus.mem_read(0x4014), 0x42);
+
+    // Joypad upper bits are open bus: LDA $4016 leaves $40 (operand high byte) on the bus
+    bus.mem_write(0x4016, 1);
+    bus.mem_write(0x4016, 0);
+    bus.mem_write(0x0000, 0x40);
+    bus.mem_read(0x0000);
+    assert_eq!(bus.mem_read(0x4016), 0x41);
+
+    // $4015 read does not drive the bus, bit 5 is open bus
+    bus.mem_write(0x0000, 0xff);
+    bus.mem_read(0x0000);
+    assert_eq!(bus.mem_read(0x4015), 0b0010_0000);
+    assert_eq!(bus.open_bus(), 0xff);
+}
+
+#[test]
+fn test_ppu_open_bus() {
+    let ppu = Rc::new(RefCell::new(Ppu::new()));
+    let mut bus = CpuBus::new();
+    bus.connect_ppu(&ppu);
+
+    // Write-only registers return PPU I/O latch, filled by last register write
+    bus.mem_write(0x2003, 0x5a);
+    assert_eq!(bus.mem_read(0x2000), 0x5a);
+    assert_eq!(bus.mem_read(0x2005), 0x5a);
+
+    // Status low bits come from latch
+    assert_eq!(bus.mem_read(0x2002), 0x1a);
+
+    // Status register is read-only, writes only fill latch
+    bus.mem_write(0x2002, 0x07);
+    assert_eq!(bus.mem_read(0x2000), 0x07);
+    assert_eq!(bus.take_error(), None);
+}
+
+#[test]
+fn test_peek() {
+    let ppu = Rc::new(RefCell::new(Ppu::new()));
+    ppu.borrow_mut()
+        .connect_bus(&Rc::new(RefCell::new(PpuBus::new())));
+    let joypad1 = Rc::new(RefCell::new(Joypad::new()));
+    joypad1
+        .borrow_mut()
+        .set_button_pressed_status(JoypadButton::BUTTON_A, true);
+    let mut bus = CpuBus::new();
+    bus.connect_ppu(&ppu);
+    bus.connect_joypad1(&joypad1);
+
+    // Peeking status keeps vblank flag, and does not change open bus
+    ppu.borrow_mut().set_vblank();
+    bus.mem_write(0x0000, 0x42);
+    assert_eq!(bus.mem_peek(0x2002), 0x80);
+    assert_eq!(bus.mem_peek(0x200a), 0x80);
+    assert_eq!(bus.open_bus(), 0x42);
+    assert_eq!(bus.mem_read(0x2002), 0x80);
+    assert_eq!(bus.mem_peek(0x2002), 0x00);
+
+    // Peeking data returns read buffer, without advancing VRAM address
+    bus.mem_write(0x2006, 0x20);
+    bus.mem_write(0x2006, 0x00);
+    bus.mem_write(0x2007, 0x11);
+    bus.mem_write(0x2007, 0x22);
+    bus.mem_write(0x2006, 0x20);
+    bus.mem_write(0x2006, 0x00);
+    bus.mem_read(0x2007);
+    assert_eq!(bus.mem_peek(0x2007), 0x11);
+    assert_eq!(bus.mem_peek(0x2007), 0x11);
+    assert_eq!(bus.mem_read(0x2007), 0x11);
+    assert_eq!(bus.mem_read(0x2007), 0x22);
+
+    // Peeking joypad does not shift to next button
+    bus.mem_write(0x4016, 1);
+    bus.mem_write(0x4016, 0);
+    assert_eq!(bus.mem_peek(0x4016) & 1, 1);
+    assert_eq!(bus.mem_peek(0x4016) & 1, 1);
+    assert_eq!(bus.mem_read(0x4016) & 1, 1);
+    assert_eq!(bus.mem_read(0x4016) & 1, 0);
 }
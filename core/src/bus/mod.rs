@@ -1,8 +1,97 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::memory::Memory;
+
 pub mod cpu_bus;
+pub mod oam_dma;
 pub mod ppu_bus;
 
+/// Unexpected bus access, ignored by the bus and reported to the console error policy
+/// (see nes::ErrorPolicy).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusError {
+    /// CPU write to cartridge PRG ROM.
+    PrgRomWrite { addr: u16, data: u8 },
+    /// PPU write to cartridge CHR ROM.
+    ChrRomWrite { addr: u16, data: u8 },
+}
+
 #[cfg(test)]
 mod cpu_bus_tests;
 
 #[cfg(test)]
-mod ppu_bus_tests;
\ No newline at end of file
+mod ppu_bus_tests;
+
+/// Bus driven by the CPU: memory, interrupt lines and DMA.
+/// Only memory access is required, interrupt lines are never asserted and DMA never occurs by default.
+pub trait Bus: Memory {
+    /// Polls NMI line (NMI is edge triggered: polling acknowledges it).
+    fn poll_nmi_status(&mut self) -> bool {
+        false
+    }
+
+    /// Indicates if IRQ line is asserted.
+    fn poll_irq_status(&self) -> bool {
+        false
+    }
+
+    /// Performs pending DMA triggered by this cycle (e.g. NES DMC sample fetch).
+    /// Returns the number of CPU cycles stolen by the DMA.
+    fn poll_dmc_dma(&mut self) -> u8 {
+        0
+    }
+
+    /// Performs next DMA cycle, if a DMA halting the CPU is in progress (e.g. NES OAM DMA).
+    /// Returns true if the CPU is halted on this cycle.
+    fn dma_cycle(&mut self, _get_cycle: bool) -> bool {
+        false
+    }
+
+    /// Indicates if a DMA halting the CPU is pending or in progress.
+    fn dma_in_progress(&self) -> bool {
+        false
+    }
+
+    /// Position (scanline, dot) of the video chip clocked with the CPU, if any (e.g. NES PPU).
+    /// Only used by traces.
+    fn video_position(&self) -> Option<(u16, u16)> {
+        None
+    }
+
+    /// Memory bank mapped at address, if address is banked (e.g. NES cartridge PRG ROM).
+    /// Only used by traces.
+    fn bank(&self, _addr: u16) -> Option<u16> {
+        None
+    }
+}
+
+/// Bus shared with other components (e.g. NES CPU bus, shared with the console).
+impl<B: Bus> Bus for Rc<RefCell<B>> {
+    fn poll_nmi_status(&mut self) -> bool {
+        self.borrow_mut().poll_nmi_status()
+    }
+
+    fn poll_irq_status(&self) -> bool {
+        self.borrow().poll_irq_status()
+    }
+
+    fn poll_dmc_dma(&mut self) -> u8 {
+        self.borrow_mut().poll_dmc_dma()
+    }
+
+    fn dma_cycle(&mut self, get_cycle: bool) -> bool {
+        self.borrow_mut().dma_cycle(get_cycle)
+    }
+
+    fn dma_in_progress(&self) -> bool {
+        self.borrow().dma_in_progress()
+    }
+
+    fn video_position(&self) -> Option<(u16, u16)> {
+        self.borrow().video_position()
+    }
+
+    fn bank(&self, addr: u16) -> Option<u16> {
+        self.borrow().bank(addr)
+    }
+}
@@ -0,0 +1,58 @@
+/// OAM DMA ($4014 write): copies a 256 bytes page to PPU OAM through $2004.
+/// The CPU is halted during the transfer: 1 halt cycle, 1 alignment cycle if needed,
+/// then 256 get (read) and put (write) cycles, i.e. 513 or 514 cycles.
+/// Source: https://www.nesdev.org/wiki/DMA#OAM_DMA
+#[derive(Debug, Clone)]
+pub struct OamDma {
+    page: u8,
+    halted: bool,
+    /// Next byte to copy.
+    index: u16,
+    /// Byte read on last get cycle, written on next put cycle.
+    data: Option<u8>,
+}
+
+impl OamDma {
+    /// Starts a transfer of page $XX00-$XXFF.
+    pub fn new(page: u8) -> Self {
+        Self {
+            page,
+            halted: false,
+            index: 0,
+            data: None,
+        }
+    }
+
+    /// CPU has been halted.
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn halt(&mut self) {
+        self.halted = true;
+    }
+
+    /// Address to read on next get cycle.
+    pub fn address(&self) -> u16 {
+        (self.page as u16) << 8 | self.index
+    }
+
+    /// Stores byte read on get cycle.
+    pub fn load(&mut self, data: u8) {
+        self.data = Some(data);
+    }
+
+    /// Takes byte to write on put cycle (none on alignment cycles), and moves to next byte.
+    pub fn take(&mut self) -> Option<u8> {
+        let data = self.data.take();
+        if data.is_some() {
+            self.index += 1;
+        }
+        data
+    }
+
+    /// Indicates if the whole page was copied.
+    pub fn is_done(&self) -> bool {
+        self.index == 0x100
+    }
+}
@@ -1,5 +1,7 @@
 use crate::cartridge::{Cartridge, Mirroring};
 
+use super::BusError;
+
 /// NES PPU connection bus.
 #[derive(Debug, Clone)]
 pub struct PpuBus {
@@ -8,6 +10,8 @@ pub struct PpuBus {
     vram: [u8; 2048],
     mirroring: Mirroring,
     internal_data_buf: u8,
+    /// First unexpected access since last take_error.
+    error: Option<BusError>,
 }
 
 impl PpuBus {
@@ -19,6 +23,7 @@ impl PpuBus {
             vram: [0; 2048],
             palette_table: [0; 32],
             internal_data_buf: 0,
+            error: None,
         }
     }
 
@@ -48,6 +53,11 @@ impl PpuBus {
         &self.mirroring
     }
 
+    /// Takes unexpected access, if any occurred since last call.
+    pub fn take_error(&mut self) -> Option<BusError> {
+        self.error.take()
+    }
+
     /// Connects a cartridge to the bus.
     pub fn connect_cartridge(&mut self, cartridge: &Cartridge) {
         self.chr_rom = cartridge.chr_rom.clone();
@@ -67,46 +77,59 @@ impl PpuBus {
         }
     }
 
+    /// Palette table index of address.
+    /// $3F20-$3FFF mirror $3F00-$3F1F, and $3F10/$3F14/$3F18/$3F1C mirror $3F00/$3F04/$3F08/$3F0C.
+    fn palette_index(addr: u16) -> usize {
+        let index = (addr & 0x1f) as usize;
+        if index & 0x13 == 0x10 {
+            index - 0x10
+        } else {
+            index
+        }
+    }
+
     /// Reads data.
+    /// Address space is 14 bits, $3000-$3EFF mirrors $2000-$2EFF.
     pub fn read_data(&mut self, addr: u16) -> u8 {
+        let addr = addr & 0x3fff;
         match addr {
             0..=0x1fff => {
                 let result = self.internal_data_buf;
                 self.internal_data_buf = self.chr_rom[addr as usize];
                 result
             }
-            0x2000..=0x2fff => {
+            0x2000..=0x3eff => {
                 let result = self.internal_data_buf;
                 self.internal_data_buf = self.vram[self.mirror_vram_addr(addr) as usize];
                 result
             }
-            0x3000..=0x3eff => panic!(
-                "address space 0x3000..0x3eff is not expected to be used, requested = {} ",
-                addr
-            ),
-            0x3f00..=0x3fff => self.palette_table[(addr - 0x3f00) as usize],
-            _ => panic!("unexpected access to mirrored space {}", addr),
+            _ => self.palette_table[Self::palette_index(addr)],
+        }
+    }
+
+    /// Data read_data would return, without updating internal read buffer.
+    pub fn peek_data(&self, addr: u16) -> u8 {
+        match addr & 0x3fff {
+            0x3f00..=0x3fff => self.palette_table[Self::palette_index(addr)],
+            _ => self.internal_data_buf,
         }
     }
 
     /// Writes data.
+    /// Address space is 14 bits, $3000-$3EFF mirrors $2000-$2EFF.
     pub fn write_to_data(&mut self, addr: u16, value: u8) {
+        let addr = addr & 0x3fff;
         match addr {
-            0..=0x1fff => panic!("attempt to write to CHR ROM space {}", addr),
-            0x2000..=0x2fff => {
-                self.vram[self.mirror_vram_addr(addr) as usize] = value;
+            0..=0x1fff => {
+                self.error
+                    .get_or_insert(BusError::ChrRomWrite { addr, data: value });
             }
-            0x3000..=0x3eff => panic!("address {} shouldn't be used", addr),
-
-            // Addresses $3F10/$3F14/$3F18/$3F1C are mirrors of $3F00/$3F04/$3F08/$3F0C
-            0x3f10 | 0x3f14 | 0x3f18 | 0x3f1c => {
-                let add_mirror = addr - 0x10;
-                self.palette_table[(add_mirror - 0x3f00) as usize] = value;
+            0x2000..=0x3eff => {
+                self.vram[self.mirror_vram_addr(addr) as usize] = value;
             }
-            0x3f00..=0x3fff => {
-                self.palette_table[(addr - 0x3f00) as usize] = value;
+            _ => {
+                self.palette_table[Self::palette_index(addr)] = value;
             }
-            _ => panic!("unexpected access to mirrored space {}", addr),
         }
     }
 }
@@ -2,7 +2,7 @@ use std::path::PathBuf;
 
 use crate::cartridge::Cartridge;
 
-use super::ppu_bus::PpuBus;
+use super::{ppu_bus::PpuBus, BusError};
 
 fn create_test_cartridge() -> Cartridge {
     let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -32,8 +32,49 @@ fn test_read_data_vram() {
 }
 
 #[test]
-#[should_panic]
-fn test_read_data_forbidden() {
+fn test_read_data_mirrors() {
     let mut bus = PpuBus::new();
-    bus.read_data(0x3000);
-}
\ No newline at end of file
+    bus.write_to_vram(0x01, 0x05);
+    // $3000-$3EFF mirrors $2000-$2EFF, address space is 14 bits
+    bus.read_data(0x3001);
+    assert_eq!(bus.read_data(0x3001), 0x05);
+    bus.read_data(0x7001);
+    assert_eq!(bus.read_data(0x7001), 0x05);
+}
+
+#[test]
+fn test_chr_rom_not_writable() {
+    let mut cartridge = create_test_cartridge();
+    cartridge.chr_rom = vec![0x06];
+
+    let mut bus = PpuBus::new();
+    bus.connect_cartridge(&cartridge);
+    bus.write_to_data(0x0, 0x07);
+    bus.read_data(0x0);
+    assert_eq!(bus.read_data(0x0), 0x06);
+    assert_eq!(
+        bus.take_error(),
+        Some(BusError::ChrRomWrite {
+            addr: 0x0,
+            data: 0x07
+        })
+    );
+    assert_eq!(bus.take_error(), None);
+}
+
+#[test]
+fn test_palette_mirrors() {
+    let mut bus = PpuBus::new();
+    // $3F10 mirrors $3F00
+    bus.write_to_data(0x3f10, 0x0f);
+    assert_eq!(bus.read_data(0x3f00), 0x0f);
+    assert_eq!(bus.palette_table()[0x10], 0x00);
+
+    // $3F20-$3FFF mirror $3F00-$3F1F
+    bus.write_to_data(0x3f05, 0x16);
+    assert_eq!(bus.read_data(0x3f25), 0x16);
+    assert_eq!(bus.peek_data(0x3fe5), 0x16);
+    bus.write_to_data(0x3fff, 0x2a);
+    assert_eq!(bus.peek_data(0x3f1f), 0x2a);
+    assert_eq!(bus.peek_data(0x3ff0), 0x0f);
+}
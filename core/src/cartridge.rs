@@ -70,6 +70,15 @@ impl Cartridge {
         })
     }
 
+    /// iNES mapper number.
+    pub fn mapper(&self) -> u8 {
+        self.mapper
+    }
+
+    pub fn prg_rom(&self) -> &Vec<u8> {
+        &self.prg_rom
+    }
+
     pub fn chr_rom(&self) -> &Vec<u8> {
         &self.chr_rom
     }
@@ -86,4 +95,19 @@ impl Cartridge {
         let bytes: Vec<u8> = std::fs::read(file).map_err(|e| CartridgeError::Io(e.to_string()))?;
         Self::new(&bytes)
     }
+
+    /// Creates an NROM cartridge running code at $8000 (test programs).
+    /// Code is followed by NOPs in 32KB PRG ROM, reset vector points to $8000 and CHR ROM is blank.
+    #[cfg(test)]
+    pub(crate) fn from_code(code: &[u8]) -> Self {
+        let mut prg_rom = vec![0xea; 2 * PRG_ROM_PAGE_SIZE];
+        prg_rom[..code.len()].copy_from_slice(code);
+        prg_rom[0x7ffc..0x7ffe].copy_from_slice(&0x8000u16.to_le_bytes());
+        Self {
+            prg_rom,
+            chr_rom: vec![0; CHR_ROM_PAGE_SIZE],
+            mapper: 0,
+            screen_mirroring: Mirroring::Vertical,
+        }
+    }
 }
@@ -39,16 +39,21 @@ impl Joypad {
     }
 
     pub fn read(&mut self) -> u8 {
-        if self.button_index > 7 {
-            return 1;
-        }
-        let response = (self.button_status.bits & (1 << self.button_index)) >> self.button_index;
+        let response = self.peek();
         if !self.strobe && self.button_index <= 7 {
             self.button_index += 1;
         }
         response
     }
 
+    /// Value read would return, without shifting to next button.
+    pub fn peek(&self) -> u8 {
+        if self.button_index > 7 {
+            return 1;
+        }
+        (self.button_status.bits & (1 << self.button_index)) >> self.button_index
+    }
+
     pub fn set_button_pressed_status(&mut self, button: JoypadButton, pressed: bool) {
         self.button_status.set(button, pressed);
     }
@@ -0,0 +1,659 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::memory::Ram;
+
+use super::{cycle::Access, AddressingMode, Cpu};
+
+/// Alternative names of unofficial mnemonics.
+const ALIASES: [(&str, &str); 8] = [
+    ("ISC", "ISB"),
+    ("SBX", "AXS"),
+    ("ASR", "ALR"),
+    ("KIL", "JAM"),
+    ("HLT", "JAM"),
+    ("SHA", "AHX"),
+    ("ANE", "XAA"),
+    ("SHS", "TAS"),
+];
+
+/// Assembly error, at a source line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblerError {
+    /// Line number, from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Assembled program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    /// Address of first byte.
+    pub origin: u16,
+    pub bytes: Vec<u8>,
+    /// Labels and constants.
+    pub symbols: BTreeMap<String, u16>,
+}
+
+/// Assembles 6502 source to bytes, placed at origin.
+///
+/// Source syntax (ca65 compatible subset):
+/// - instructions: official and unofficial mnemonics (e.g. `LAX ($10),Y`), case insensitive
+/// - labels (`loop:`), constants (`PPUCTRL = $2000`), comments (`; ...`)
+/// - directives: `.org` (moves forward only, gap is zero-filled), `.byte` (numbers and strings), `.word`
+/// - expressions: `$FF`, `%1010`, `255`, `'A'`, labels, `*` (current address), `+` and `-`,
+///   `<` (low byte) and `>` (high byte) prefixes
+/// - `a:` and `z:` operand prefixes force absolute or zero page addressing
+///
+/// Zero page addressing is used when the operand is known to be below $100
+/// when reached in source: forward references use absolute addressing.
+pub fn assemble(source: &str, origin: u16) -> Result<Program, AssemblerError> {
+    let statements = source
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            parse_line(line).map_err(|message| AssemblerError {
+                line: i + 1,
+                message,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut assembler = Assembler {
+        symbols: BTreeMap::new(),
+        origin,
+        bytes: vec![],
+        started: false,
+        sizes: vec![],
+    };
+    // First pass computes symbols, second pass emits bytes with the same instruction sizes
+    for pass in [Pass::Symbols, Pass::Emit] {
+        assembler.bytes.clear();
+        assembler.started = false;
+        assembler.origin = origin;
+        for (i, statement) in statements.iter().enumerate() {
+            assembler
+                .statement(statement, i, pass)
+                .map_err(|message| AssemblerError {
+                    line: i + 1,
+                    message,
+                })?;
+        }
+    }
+
+    Ok(Program {
+        origin: assembler.origin,
+        bytes: assembler.bytes,
+        symbols: assembler.symbols,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pass {
+    Symbols,
+    Emit,
+}
+
+/// Operand syntax, identifying an opcode with the mnemonic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Syntax {
+    Implied,
+    Accumulator,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndirectX,
+    IndirectY,
+    Relative,
+}
+
+impl Syntax {
+    fn of(code: u8) -> Syntax {
+        let opcode = &Cpu::<Ram>::OPCODES[code as usize];
+        match (opcode.len, opcode.mode) {
+            (1, _) if matches!(code, 0x0a | 0x4a | 0x2a | 0x6a) => Syntax::Accumulator,
+            (1, _) => Syntax::Implied,
+            (_, AddressingMode::Immediate) => Syntax::Immediate,
+            (_, AddressingMode::ZeroPage) => Syntax::ZeroPage,
+            (_, AddressingMode::ZeroPage_X) => Syntax::ZeroPageX,
+            (_, AddressingMode::ZeroPage_Y) => Syntax::ZeroPageY,
+            (_, AddressingMode::Absolute) => Syntax::Absolute,
+            (_, AddressingMode::Absolute_X) => Syntax::AbsoluteX,
+            (_, AddressingMode::Absolute_Y) => Syntax::AbsoluteY,
+            (_, AddressingMode::Indirect_X) => Syntax::IndirectX,
+            (_, AddressingMode::Indirect_Y) => Syntax::IndirectY,
+            _ if matches!(opcode.access, Access::Branch(_)) => Syntax::Relative,
+            _ if code == 0x6c => Syntax::Indirect,
+            // JMP and JSR
+            _ => Syntax::Absolute,
+        }
+    }
+
+    /// Instruction size.
+    fn len(self) -> u16 {
+        match self {
+            Syntax::Implied | Syntax::Accumulator => 1,
+            Syntax::Absolute | Syntax::AbsoluteX | Syntax::AbsoluteY | Syntax::Indirect => 3,
+            _ => 2,
+        }
+    }
+}
+
+/// Opcode of instruction, official opcodes first.
+fn find_opcode(mnemonic: &str, syntax: Syntax) -> Option<u8> {
+    let official = (0..=255u8).find(|code| {
+        Cpu::<Ram>::OPCODES[*code as usize].mnemonic == mnemonic && Syntax::of(*code) == syntax
+    });
+    official.or_else(|| {
+        (0..=255u8).find(|code| {
+            Cpu::<Ram>::OPCODES[*code as usize]
+                .mnemonic
+                .strip_prefix('*')
+                == Some(mnemonic)
+                && Syntax::of(*code) == syntax
+        })
+    })
+}
+
+fn is_mnemonic(mnemonic: &str) -> bool {
+    Cpu::<Ram>::OPCODES
+        .iter()
+        .any(|opcode| opcode.mnemonic.trim_start_matches('*') == mnemonic)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    Number(i32),
+    Symbol(String),
+    /// Current address.
+    Pc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Selector {
+    Low,
+    High,
+}
+
+/// Sum of terms, with an optional byte selector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Expr {
+    selector: Option<Selector>,
+    /// Terms, negated if true.
+    terms: Vec<(bool, Term)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Index {
+    None,
+    X,
+    Y,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Force {
+    None,
+    Absolute,
+    ZeroPage,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Operand {
+    None,
+    Accumulator,
+    Immediate(Expr),
+    /// Zero page, absolute or relative.
+    Direct(Expr, Index, Force),
+    Indirect(Expr),
+    IndirectX(Expr),
+    IndirectY(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ByteItem {
+    Expr(Expr),
+    String(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Directive {
+    None,
+    Constant(String, Expr),
+    Org(Expr),
+    Byte(Vec<ByteItem>),
+    Word(Vec<Expr>),
+    Instruction(String, Operand),
+}
+
+/// Source line: labels, followed by a directive or instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Statement {
+    labels: Vec<String>,
+    directive: Directive,
+}
+
+fn parse_line(line: &str) -> Result<Statement, String> {
+    let mut rest = strip_comment(line).trim();
+    let mut labels = vec![];
+    while let Some((label, after)) = rest.split_once(':') {
+        let label = label.trim();
+        // Not a label: a: and z: prefixes, or ':' in a string
+        if !is_identifier(label)
+            || label.eq_ignore_ascii_case("a")
+            || label.eq_ignore_ascii_case("z")
+        {
+            break;
+        }
+        labels.push(label.to_string());
+        rest = after.trim();
+    }
+
+    let directive = if rest.is_empty() {
+        Directive::None
+    } else if let Some((name, value)) = constant(rest) {
+        Directive::Constant(name.to_string(), parse_expr(value)?)
+    } else {
+        let (word, operand) = match rest.split_once(char::is_whitespace) {
+            Some((word, operand)) => (word, operand.trim()),
+            None => (rest, ""),
+        };
+        match word.to_ascii_lowercase().as_str() {
+            ".org" => Directive::Org(parse_expr(operand)?),
+            ".byte" | ".db" => Directive::Byte(
+                split_list(operand)?
+                    .into_iter()
+                    .map(|item| match item.strip_prefix('"') {
+                        Some(string) => match string.strip_suffix('"') {
+                            Some(string) => Ok(ByteItem::String(string.as_bytes().to_vec())),
+                            None => Err(String::from("unterminated string")),
+                        },
+                        None => parse_expr(item).map(ByteItem::Expr),
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            ".word" | ".dw" => Directive::Word(
+                split_list(operand)?
+                    .into_iter()
+                    .map(parse_expr)
+                    .collect::<Result<_, _>>()?,
+            ),
+            directive if directive.starts_with('.') => {
+                return Err(format!("unknown directive '{}'", word))
+            }
+            _ => {
+                let mnemonic = word.trim_start_matches('*').to_ascii_uppercase();
+                let mnemonic = ALIASES
+                    .iter()
+                    .find(|(alias, _)| *alias == mnemonic)
+                    .map(|(_, name)| name.to_string())
+                    .unwrap_or(mnemonic);
+                if !is_mnemonic(&mnemonic) {
+                    return Err(format!("unknown instruction '{}'", word));
+                }
+                Directive::Instruction(mnemonic, parse_operand(operand)?)
+            }
+        }
+    };
+    Ok(Statement { labels, directive })
+}
+
+/// Characters outside strings and character literals, with their byte offset.
+fn unquoted_chars(text: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut quote = None;
+    text.char_indices().filter(move |(_, c)| match quote {
+        Some(q) => {
+            if *c == q {
+                quote = None;
+            }
+            false
+        }
+        None if matches!(c, '"' | '\'') => {
+            quote = Some(*c);
+            false
+        }
+        None => true,
+    })
+}
+
+/// Removes comment, unless ';' is quoted.
+fn strip_comment(line: &str) -> &str {
+    match unquoted_chars(line).find(|(_, c)| *c == ';') {
+        Some((i, _)) => &line[..i],
+        None => line,
+    }
+}
+
+/// Name and value of a constant definition (NAME = value).
+fn constant(statement: &str) -> Option<(&str, &str)> {
+    let (i, _) = unquoted_chars(statement).find(|(_, c)| *c == '=')?;
+    let name = statement[..i].trim();
+    is_identifier(name).then(|| (name, &statement[i + 1..]))
+}
+
+/// Splits comma separated list, except in strings and character literals.
+fn split_list(list: &str) -> Result<Vec<&str>, String> {
+    let mut items = vec![];
+    let mut start = 0;
+    for (i, c) in unquoted_chars(list) {
+        if c == ',' {
+            items.push(list[start..i].trim());
+            start = i + 1;
+        }
+    }
+    items.push(list[start..].trim());
+    if items.iter().any(|item| item.is_empty()) {
+        return Err(String::from("missing value"));
+    }
+    Ok(items)
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '@')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_operand(operand: &str) -> Result<Operand, String> {
+    if operand.is_empty() {
+        return Ok(Operand::None);
+    }
+    if let Some(value) = operand.strip_prefix('#') {
+        return Ok(Operand::Immediate(parse_expr(value)?));
+    }
+
+    // Syntax is matched in uppercase, expressions are sliced from original case (same byte offsets)
+    let compact: String = operand.chars().filter(|c| !c.is_whitespace()).collect();
+    let upper = compact.to_ascii_uppercase();
+    let slice = |value: &str| {
+        let start = value.as_ptr() as usize - upper.as_ptr() as usize;
+        &compact[start..start + value.len()]
+    };
+    if upper == "A" {
+        return Ok(Operand::Accumulator);
+    }
+    if let Some(inner) = upper.strip_prefix('(') {
+        if let Some(value) = inner.strip_suffix(",X)") {
+            return Ok(Operand::IndirectX(parse_expr(slice(value))?));
+        }
+        if let Some(value) = inner.strip_suffix("),Y") {
+            return Ok(Operand::IndirectY(parse_expr(slice(value))?));
+        }
+        if let Some(value) = inner.strip_suffix(')') {
+            return Ok(Operand::Indirect(parse_expr(slice(value))?));
+        }
+        return Err(format!("invalid operand '{}'", operand));
+    }
+
+    let (value, index) = match (upper.strip_suffix(",X"), upper.strip_suffix(",Y")) {
+        (Some(value), _) => (value, Index::X),
+        (_, Some(value)) => (value, Index::Y),
+        _ => (upper.as_str(), Index::None),
+    };
+    let (value, force) = match (value.strip_prefix("A:"), value.strip_prefix("Z:")) {
+        (Some(value), _) => (value, Force::Absolute),
+        (_, Some(value)) => (value, Force::ZeroPage),
+        _ => (value, Force::None),
+    };
+    Ok(Operand::Direct(parse_expr(slice(value))?, index, force))
+}
+
+fn parse_expr(expr: &str) -> Result<Expr, String> {
+    let expr = expr.trim();
+    let (selector, expr) = match expr.chars().next() {
+        Some('<') => (Some(Selector::Low), &expr[1..]),
+        Some('>') => (Some(Selector::High), &expr[1..]),
+        _ => (None, expr),
+    };
+
+    let mut terms = vec![];
+    let mut negate = false;
+    let mut term = String::new();
+    let mut chars = expr.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                let value = chars.next().ok_or("invalid character")?;
+                if chars.next() != Some('\'') {
+                    return Err(String::from("invalid character"));
+                }
+                term.push_str(&(value as u32).to_string());
+            }
+            '+' | '-' if !term.is_empty() => {
+                terms.push((negate, parse_term(&term)?));
+                term.clear();
+                negate = c == '-';
+            }
+            '-' if terms.is_empty() => negate = true,
+            c if c.is_whitespace() => {}
+            c => term.push(c),
+        }
+    }
+    terms.push((negate, parse_term(&term)?));
+    Ok(Expr { selector, terms })
+}
+
+fn parse_term(term: &str) -> Result<Term, String> {
+    let number = |digits: &str, radix| {
+        i32::from_str_radix(digits, radix).map_err(|_| format!("invalid number '{}'", term))
+    };
+    match term.chars().next() {
+        None => Err(String::from("missing value")),
+        Some('$') => Ok(Term::Number(number(&term[1..], 16)?)),
+        Some('%') => Ok(Term::Number(number(&term[1..], 2)?)),
+        Some('*') if term == "*" => Ok(Term::Pc),
+        Some(c) if c.is_ascii_digit() => Ok(Term::Number(number(term, 10)?)),
+        _ if is_identifier(term) => Ok(Term::Symbol(term.to_string())),
+        _ => Err(format!("invalid expression '{}'", term)),
+    }
+}
+
+struct Assembler {
+    symbols: BTreeMap<String, u16>,
+    origin: u16,
+    bytes: Vec<u8>,
+    /// Indicates if origin can no longer be changed without padding.
+    started: bool,
+    /// Instruction syntax chosen by first pass, by statement.
+    sizes: Vec<Option<Syntax>>,
+}
+
+impl Assembler {
+    fn pc(&self) -> u16 {
+        self.origin.wrapping_add(self.bytes.len() as u16)
+    }
+
+    /// Evaluates expression, None if a symbol is not defined yet.
+    fn eval(&self, expr: &Expr, pass: Pass) -> Result<Option<i32>, String> {
+        let mut value: i32 = 0;
+        for (negate, term) in &expr.terms {
+            let term = match term {
+                Term::Number(number) => *number,
+                Term::Pc => self.pc() as i32,
+                Term::Symbol(symbol) => match (self.symbols.get(symbol), pass) {
+                    (Some(symbol), _) => *symbol as i32,
+                    (None, Pass::Symbols) => return Ok(None),
+                    (None, Pass::Emit) => return Err(format!("undefined symbol '{}'", symbol)),
+                },
+            };
+            value = if *negate {
+                value.checked_sub(term)
+            } else {
+                value.checked_add(term)
+            }
+            .ok_or("expression overflow")?;
+        }
+        Ok(Some(match expr.selector {
+            Some(Selector::Low) => value & 0xff,
+            Some(Selector::High) => (value >> 8) & 0xff,
+            None => value,
+        }))
+    }
+
+    /// Evaluates expression on second pass, 0 on first pass if not defined yet.
+    fn value(&self, expr: &Expr, pass: Pass) -> Result<i32, String> {
+        Ok(self.eval(expr, pass)?.unwrap_or(0))
+    }
+
+    fn define(&mut self, name: &str, value: u16, pass: Pass) -> Result<(), String> {
+        if pass == Pass::Symbols && self.symbols.insert(name.to_string(), value).is_some() {
+            return Err(format!("'{}' is already defined", name));
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Statement, index: usize, pass: Pass) -> Result<(), String> {
+        for label in &statement.labels {
+            self.define(label, self.pc(), pass)?;
+        }
+
+        match &statement.directive {
+            Directive::None => {}
+            Directive::Constant(name, expr) => {
+                let value = self
+                    .eval(expr, pass)?
+                    .ok_or_else(|| format!("'{}' must be defined from previous symbols", name))?;
+                self.define(name, word(value)?, pass)?;
+            }
+            Directive::Org(expr) => {
+                // Symbols must be defined before
+                let address = self
+                    .value(expr, Pass::Emit)
+                    .and_then(word)
+                    .map_err(|error| format!(".org: {}", error))?;
+                if !self.started {
+                    self.origin = address;
+                } else if address >= self.pc() {
+                    self.bytes
+                        .resize(address.wrapping_sub(self.origin) as usize, 0);
+                } else {
+                    return Err(format!(".org ${:04X} is before current address", address));
+                }
+            }
+            Directive::Byte(items) => {
+                for item in items {
+                    match item {
+                        ByteItem::Expr(expr) => {
+                            let value = byte(self.value(expr, pass)?)?;
+                            self.emit(&[value]);
+                        }
+                        ByteItem::String(string) => self.emit(string),
+                    }
+                }
+            }
+            Directive::Word(exprs) => {
+                for expr in exprs {
+                    let value = word(self.value(expr, pass)?)?;
+                    self.emit(&value.to_le_bytes());
+                }
+            }
+            Directive::Instruction(mnemonic, operand) => {
+                self.instruction(mnemonic, operand, index, pass)?
+            }
+        }
+        Ok(())
+    }
+
+    fn instruction(
+        &mut self,
+        mnemonic: &str,
+        operand: &Operand,
+        index: usize,
+        pass: Pass,
+    ) -> Result<(), String> {
+        let supported = |syntax| find_opcode(mnemonic, syntax).is_some();
+        let syntax = match (operand, pass) {
+            (Operand::None, _) if supported(Syntax::Implied) => Syntax::Implied,
+            (Operand::None | Operand::Accumulator, _) => Syntax::Accumulator,
+            (Operand::Immediate(_), _) => Syntax::Immediate,
+            (Operand::Indirect(_), _) => Syntax::Indirect,
+            (Operand::IndirectX(_), _) => Syntax::IndirectX,
+            (Operand::IndirectY(_), _) => Syntax::IndirectY,
+            (Operand::Direct(..), _) if supported(Syntax::Relative) => Syntax::Relative,
+            // Instruction size must not change on second pass
+            (Operand::Direct(..), Pass::Emit) => self.sizes[index].unwrap(),
+            (Operand::Direct(expr, index, force), Pass::Symbols) => {
+                let (zero_page, absolute) = match index {
+                    Index::None => (Syntax::ZeroPage, Syntax::Absolute),
+                    Index::X => (Syntax::ZeroPageX, Syntax::AbsoluteX),
+                    Index::Y => (Syntax::ZeroPageY, Syntax::AbsoluteY),
+                };
+                let fits_zero_page = matches!(self.eval(expr, pass)?, Some(0..=0xff));
+                match force {
+                    Force::Absolute => absolute,
+                    Force::ZeroPage => zero_page,
+                    Force::None if fits_zero_page && supported(zero_page) => zero_page,
+                    Force::None if supported(absolute) => absolute,
+                    Force::None => zero_page,
+                }
+            }
+        };
+        if pass == Pass::Symbols {
+            self.sizes.resize(index + 1, None);
+            self.sizes[index] = Some(syntax);
+        }
+        let code = find_opcode(mnemonic, syntax)
+            .ok_or_else(|| format!("invalid addressing mode for {}", mnemonic))?;
+
+        let operand_value = match operand {
+            Operand::None | Operand::Accumulator => 0,
+            Operand::Immediate(expr)
+            | Operand::Indirect(expr)
+            | Operand::IndirectX(expr)
+            | Operand::IndirectY(expr)
+            | Operand::Direct(expr, _, _) => self.value(expr, pass)?,
+        };
+        match syntax.len() {
+            1 => self.emit(&[code]),
+            2 if syntax == Syntax::Relative => {
+                let offset = operand_value - (self.pc() as i32 + 2);
+                if pass == Pass::Emit && !(-128..=127).contains(&offset) {
+                    return Err(format!("branch out of range ({} bytes)", offset));
+                }
+                self.emit(&[code, offset as u8]);
+            }
+            2 => {
+                let value = byte(operand_value)?;
+                if syntax != Syntax::Immediate && pass == Pass::Emit && operand_value < 0 {
+                    return Err(format!("invalid zero page address {}", operand_value));
+                }
+                self.emit(&[code, value]);
+            }
+            _ => {
+                let [lo, hi] = word(operand_value)?.to_le_bytes();
+                self.emit(&[code, lo, hi]);
+            }
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        self.started = true;
+        self.bytes.extend_from_slice(bytes);
+    }
+}
+
+/// Byte value, signed or not.
+fn byte(value: i32) -> Result<u8, String> {
+    match value {
+        -128..=255 => Ok(value as u8),
+        _ => Err(format!("value {} does not fit in a byte", value)),
+    }
+}
+
+/// Word value, signed or not.
+fn word(value: i32) -> Result<u16, String> {
+    match value {
+        -32768..=65535 => Ok(value as u16),
+        _ => Err(format!("value {} does not fit in a word", value)),
+    }
+}
@@ -0,0 +1,240 @@
+use std::path::PathBuf;
+
+use crate::{
+    cartridge::Cartridge,
+    cpu::{
+        assembler::{assemble, AssemblerError},
+        disassembler::Disassembler,
+        Cpu,
+    },
+    memory::Ram,
+};
+
+fn bytes(source: &str) -> Vec<u8> {
+    assemble(source, 0x8000).unwrap().bytes
+}
+
+fn error(source: &str) -> AssemblerError {
+    assemble(source, 0x8000).unwrap_err()
+}
+
+#[test]
+fn test_instructions() {
+    assert_eq!(
+        bytes("LDA #$05\nlda #2\nBRK"),
+        [0xa9, 0x05, 0xa9, 0x02, 0x00]
+    );
+    assert_eq!(
+        bytes(
+            "
+            LDA $10
+            LDA $10,X
+            LDX $10,Y
+            LDA $1234
+            LDA $1234,X
+            LDA $1234,Y
+            LDA ($10,X)
+            LDA ($10),Y
+            JMP ($1234)
+            JSR $1234
+            "
+        ),
+        [
+            0xa5, 0x10, 0xb5, 0x10, 0xb6, 0x10, 0xad, 0x34, 0x12, 0xbd, 0x34, 0x12, 0xb9, 0x34,
+            0x12, 0xa1, 0x10, 0xb1, 0x10, 0x6c, 0x34, 0x12, 0x20, 0x34, 0x12
+        ]
+    );
+    // Implied and accumulator
+    assert_eq!(bytes("ASL\nASL A\nNOP\nINX"), [0x0a, 0x0a, 0xea, 0xe8]);
+    // Forced absolute and zero page addressing
+    assert_eq!(
+        bytes("STA a:$10\nLDA a:$0010,X\nLDA z:$10"),
+        [0x8d, 0x10, 0x00, 0xbd, 0x10, 0x00, 0xa5, 0x10]
+    );
+    // No zero page addressing for LDA abs,Y
+    assert_eq!(bytes("LDA $10,Y"), [0xb9, 0x10, 0x00]);
+}
+
+#[test]
+fn test_unofficial_instructions() {
+    assert_eq!(
+        bytes("LAX ($10),Y\n*NOP $10\nDCP $1234,X\nISC $10\nKIL"),
+        [0xb3, 0x10, 0x04, 0x10, 0xdf, 0x34, 0x12, 0xe7, 0x10, 0x02]
+    );
+    // Official encoding is preferred
+    assert_eq!(bytes("SBC #$10\nNOP"), [0xe9, 0x10, 0xea]);
+}
+
+#[test]
+fn test_labels() {
+    let program = assemble(
+        "
+        PPUCTRL = $2000
+        start:  LDX #0
+        loop:   INX
+                BNE loop
+                BEQ end
+                JMP start
+                LDA #<data
+                LDA #>data
+                STA PPUCTRL
+        end:    JMP *
+        data:   .byte 1, 2
+        ",
+        0xc000,
+    )
+    .unwrap();
+    assert_eq!(
+        program.bytes,
+        [
+            0xa2, 0x00, // LDX #0
+            0xe8, // INX
+            0xd0, 0xfd, // BNE loop
+            0xf0, 0x0a, // BEQ end
+            0x4c, 0x00, 0xc0, // JMP start
+            0xa9, 0x14, // LDA #<data
+            0xa9, 0xc0, // LDA #>data
+            0x8d, 0x00, 0x20, // STA PPUCTRL
+            0x4c, 0x11, 0xc0, // JMP *
+            0x01, 0x02,
+        ]
+    );
+    assert_eq!(program.symbols["loop"], 0xc002);
+    assert_eq!(program.symbols["data"], 0xc014);
+    assert_eq!(program.symbols["PPUCTRL"], 0x2000);
+}
+
+#[test]
+fn test_forward_reference() {
+    // Forward references use absolute addressing, even if in zero page
+    let program = assemble("LDA value\nvalue = $10\nLDA value+1", 0).unwrap();
+    assert_eq!(program.bytes, [0xad, 0x10, 0x00, 0xa5, 0x11]);
+
+    let program = assemble("LDA data-1,X\n.org $10\ndata: .byte 0", 0).unwrap();
+    assert_eq!(
+        program.bytes,
+        [0xbd, 0x0f, 0x00, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+    );
+}
+
+#[test]
+fn test_directives() {
+    let program = assemble(
+        "
+        .org $FFF4
+        nmi:
+        .byte \"AB;\", 'C', -1, %101 ; comment
+        .word nmi, $C000, -1
+        ",
+        0x8000,
+    )
+    .unwrap();
+    assert_eq!(program.origin, 0xfff4);
+    assert_eq!(
+        program.bytes,
+        [0x41, 0x42, 0x3b, 0x43, 0xff, 0x05, 0xf4, 0xff, 0x00, 0xc0, 0xff, 0xff]
+    );
+    assert_eq!(program.symbols["nmi"], 0xfff4);
+
+    // Gap is zero-filled
+    assert_eq!(bytes("NOP\n.org $8003\nNOP"), [0xea, 0x00, 0x00, 0xea]);
+}
+
+#[test]
+fn test_quoted_separators() {
+    // '=', ',' and ';' in strings and character literals
+    assert_eq!(
+        bytes(".byte \"a=b\", ',', '=', \"c,d;\" ; comment"),
+        [0x61, 0x3d, 0x62, 0x2c, 0x3d, 0x63, 0x2c, 0x64, 0x3b]
+    );
+    assert_eq!(bytes("CMP #'='\nCMP #','"), [0xc9, 0x3d, 0xc9, 0x2c]);
+    assert_eq!(
+        bytes("SEP = ','\nLDA #SEP\n.byte \"it's\""),
+        [0xa9, 0x2c, 0x69, 0x74, 0x27, 0x73]
+    );
+}
+
+#[test]
+fn test_errors() {
+    assert_eq!(
+        error("NOP\nFOO #1"),
+        AssemblerError {
+            line: 2,
+            message: String::from("unknown instruction 'FOO'"),
+        }
+    );
+    assert_eq!(
+        error("STX $1234,X").message,
+        "invalid addressing mode for STX"
+    );
+    assert_eq!(error("LDA missing").message, "undefined symbol 'missing'");
+    assert_eq!(
+        error("LDA #$100").message,
+        "value 256 does not fit in a byte"
+    );
+    assert_eq!(
+        error("loop: NOP\nloop: NOP").to_string(),
+        "line 2: 'loop' is already defined"
+    );
+    assert_eq!(
+        error("loop: .byte 0\n.org $8100\nBNE loop").message,
+        "branch out of range (-258 bytes)"
+    );
+    assert_eq!(
+        error(".org $8010\nNOP\n.org $8000").message,
+        ".org $8000 is before current address"
+    );
+    assert_eq!(error(".fill 10").message, "unknown directive '.fill'");
+    assert_eq!(error(".word $7FFFFFFF+1").message, "expression overflow");
+    assert_eq!(error("LDA -$7FFFFFFF-2").message, "expression overflow");
+}
+
+#[test]
+fn test_all_opcodes() {
+    // Disassembled opcodes assemble to an opcode with the same instruction and addressing mode
+    for code in 0..=255u8 {
+        let data = [code, 0x34, 0x12];
+        let mut disassembler = Disassembler::new(&data, 0x8000);
+        disassembler.add_code_range(0x8000..=0x8000);
+        let line = &disassembler.lines()[0];
+        // Unofficial opcodes are disassembled as comment
+        let text = match line.text.split_once(" ; ") {
+            Some((_, instruction)) => instruction,
+            None => &line.text,
+        };
+
+        let assembled = bytes(text);
+        let expected = &Cpu::<Ram>::OPCODES[code as usize];
+        let actual = &Cpu::<Ram>::OPCODES[assembled[0] as usize];
+        assert_eq!(
+            (
+                actual.mnemonic.trim_start_matches('*'),
+                actual.mode,
+                actual.len
+            ),
+            (
+                expected.mnemonic.trim_start_matches('*'),
+                expected.mode,
+                expected.len
+            ),
+            "{:02X}: {}",
+            code,
+            text
+        );
+        assert_eq!(assembled[1..], line.bytes[1..], "{:02X}: {}", code, text);
+    }
+}
+
+#[test]
+fn test_disassembly_round_trip() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("res/nestest.nes");
+    let cartridge = Cartridge::from_file(path).unwrap();
+    let mut disassembler = Disassembler::for_cartridge(&cartridge).unwrap();
+    disassembler.add_vectors();
+    disassembler.add_entry_point(0xc000, Some("automation"));
+
+    let program = assemble(&disassembler.to_ca65(), 0).unwrap();
+    assert_eq!(program.origin, 0xc000);
+    assert_eq!(&program.bytes, cartridge.prg_rom());
+    assert_eq!(program.symbols["automation"], 0xc000);
+}
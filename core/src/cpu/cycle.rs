@@ -0,0 +1,478 @@
+use std::fmt;
+
+use crate::{
+    bus::Bus,
+    memory::{page_cross, Memory},
+};
+
+use super::{
+    instruction::{AddressingMode, Instructions},
+    interrupt::{self, InterruptType},
+    Cpu, CpuFlags, RunMode, STACK,
+};
+
+/// Bus access pattern of an instruction, with the operation it performs.
+/// Each pattern is a sequence of cycles, each cycle performing exactly one bus read or write.
+/// Reference: https://www.nesdev.org/6502_cpu.txt.
+pub(super) enum Access<B> {
+    /// Operates on registers, reads next byte (dummy read).
+    Implied(fn(&mut Cpu<B>)),
+    /// Reads operand.
+    Read(fn(&mut Cpu<B>, u8)),
+    /// Writes operand.
+    Write(fn(&mut Cpu<B>) -> u8),
+    /// Reads operand, writes it back unmodified (dummy write), then writes modified value.
+    ReadModifyWrite(fn(&mut Cpu<B>, u8) -> u8),
+    /// Relative branch, taken if condition is true.
+    Branch(fn(&Cpu<B>) -> bool),
+    /// Stack and jump instructions, each one with its own sequence.
+    ControlFlow,
+    /// BRK, hardware interrupt or reset sequence.
+    Interrupt,
+    /// JAM: never completes, the CPU is halted until reset.
+    Jam,
+}
+
+// Not derived: B does not need to be Copy
+impl<B> Clone for Access<B> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<B> Copy for Access<B> {}
+
+impl<B> fmt::Debug for Access<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Access::Implied(_) => "Implied",
+            Access::Read(_) => "Read",
+            Access::Write(_) => "Write",
+            Access::ReadModifyWrite(_) => "ReadModifyWrite",
+            Access::Branch(_) => "Branch",
+            Access::ControlFlow => "ControlFlow",
+            Access::Interrupt => "Interrupt",
+            Access::Jam => "Jam",
+        };
+        f.write_str(name)
+    }
+}
+
+impl<B: Bus> Cpu<B> {
+    /// Performs first cycle of an instruction: fetches opcode.
+    /// Returns false if BRK is fetched in StopOnBrk run mode.
+    pub(super) fn fetch_opcode(&mut self) -> bool {
+        let code = self.fetch_operand();
+        let opcode = &Self::OPCODES[code as usize];
+
+        if code == 0x00 {
+            if self.run_mode == RunMode::StopOnBrk {
+                return false;
+            }
+            self.interrupt = interrupt::BRK;
+        }
+
+        self.opcode = code;
+        self.mode = opcode.mode;
+        self.access = opcode.access;
+        self.page_crossed = false;
+        self.irq_inhibit = self.status.contains(CpuFlags::INTERRUPT_DISABLE);
+        self.cycle = 1;
+        true
+    }
+
+    /// Performs first cycle of a hardware interrupt sequence: opcode fetch is discarded.
+    pub(super) fn start_interrupt(&mut self, interrupt: interrupt::Interrupt) {
+        self.mem_read(self.program_counter);
+        self.interrupt = interrupt;
+        self.access = Access::Interrupt;
+        self.cycle = 1;
+    }
+
+    /// Performs next cycle of current instruction.
+    pub(super) fn step(&mut self) {
+        self.cycle += 1;
+        let done = match self.access {
+            Access::Implied(operation) => {
+                self.mem_read(self.program_counter);
+                operation(self);
+                true
+            }
+            Access::Read(_) | Access::Write(_) | Access::ReadModifyWrite(_) => {
+                self.addressed_cycle()
+            }
+            Access::Branch(condition) => self.branch_cycle(condition),
+            Access::ControlFlow => self.control_flow_cycle(),
+            Access::Interrupt => self.interrupt_cycle(),
+            Access::Jam => self.jam_cycle(),
+        };
+
+        if done {
+            self.complete();
+        }
+    }
+
+    /// Performs a JAM cycle: reads next byte, then address bus is stuck at $FFFF.
+    /// Never completes, so interrupts are not polled anymore.
+    fn jam_cycle(&mut self) -> bool {
+        if self.cycle == 2 {
+            self.mem_read(self.program_counter);
+        } else {
+            self.mem_read(0xffff);
+            self.cycle = 3;
+        }
+        false
+    }
+
+    /// Ends current instruction and polls interrupts.
+    fn complete(&mut self) {
+        self.cycle = 0;
+
+        // The first instruction of an interrupt handler always runs
+        if matches!(self.access, Access::Interrupt) {
+            return;
+        }
+
+        // CLI, SEI and PLP change interrupt disable flag after interrupt polling
+        if !matches!(self.opcode, 0x58 | 0x78 | 0x28) {
+            self.irq_inhibit = self.status.contains(CpuFlags::INTERRUPT_DISABLE);
+        }
+        self.poll_interrupts();
+    }
+
+    /// Reads byte at program counter and moves to next one.
+    fn fetch_operand(&mut self) -> u8 {
+        let data = self.mem_read(self.program_counter);
+        self.program_counter = self.program_counter.wrapping_add(1);
+        data
+    }
+
+    /// Reads top of stack, without popping it.
+    fn dummy_stack_read(&mut self) {
+        self.mem_read(STACK + self.stack_pointer as u16);
+    }
+
+    /// Adds index to low byte of effective address.
+    /// High byte is fixed on next cycle if page is crossed.
+    fn index_address(&mut self, high: u8, index: u8) {
+        let (low, crossed) = (self.address as u8).overflowing_add(index);
+        self.page_crossed = crossed;
+        self.address = (high as u16) << 8 | low as u16;
+    }
+
+    /// Reads (possibly wrong page) indexed address, then fixes address high byte.
+    /// Read instructions complete here when no page is crossed.
+    fn fix_indexed_address(&mut self) -> bool {
+        let data = self.mem_read(self.address);
+        if let Access::Read(operation) = self.access {
+            if !self.page_crossed {
+                operation(self, data);
+                return true;
+            }
+        }
+        if self.page_crossed {
+            self.address = self.address.wrapping_add(0x100);
+        }
+        false
+    }
+
+    /// Cycles of instructions addressing memory.
+    fn addressed_cycle(&mut self) -> bool {
+        let addressing_cycles = match self.mode {
+            AddressingMode::Immediate => 0,
+            AddressingMode::ZeroPage => 1,
+            AddressingMode::ZeroPage_X | AddressingMode::ZeroPage_Y | AddressingMode::Absolute => 2,
+            AddressingMode::Absolute_X | AddressingMode::Absolute_Y => 3,
+            AddressingMode::Indirect_X | AddressingMode::Indirect_Y => 4,
+            AddressingMode::NoneAddressing => unreachable!("implied instruction"),
+        };
+
+        // First cycle was opcode fetch
+        let cycle = self.cycle - 1;
+        if cycle <= addressing_cycles {
+            return self.addressing_cycle(cycle);
+        }
+
+        if self.mode == AddressingMode::Immediate {
+            self.address = self.program_counter;
+            self.program_counter = self.program_counter.wrapping_add(1);
+        }
+        self.operand_cycle(cycle - addressing_cycles - 1)
+    }
+
+    /// Computes effective address.
+    /// Returns true if instruction completes (read without page crossing).
+    fn addressing_cycle(&mut self, cycle: u8) -> bool {
+        match (self.mode, cycle) {
+            (AddressingMode::ZeroPage | AddressingMode::ZeroPage_X | AddressingMode::ZeroPage_Y, 1)
+            | (AddressingMode::Absolute | AddressingMode::Absolute_X | AddressingMode::Absolute_Y, 1) => {
+                self.address = self.fetch_operand() as u16;
+            }
+            (AddressingMode::ZeroPage_X, 2) => {
+                self.mem_read(self.address);
+                self.address = (self.address as u8).wrapping_add(self.register_x) as u16;
+            }
+            (AddressingMode::ZeroPage_Y, 2) => {
+                self.mem_read(self.address);
+                self.address = (self.address as u8).wrapping_add(self.register_y) as u16;
+            }
+            (AddressingMode::Absolute, 2) => {
+                self.address |= (self.fetch_operand() as u16) << 8;
+            }
+            (AddressingMode::Absolute_X, 2) => {
+                let high = self.fetch_operand();
+                self.index_address(high, self.register_x);
+            }
+            (AddressingMode::Absolute_Y, 2) => {
+                let high = self.fetch_operand();
+                self.index_address(high, self.register_y);
+            }
+            (AddressingMode::Indirect_X | AddressingMode::Indirect_Y, 1) => {
+                self.pointer = self.fetch_operand();
+            }
+            (AddressingMode::Indirect_X, 2) => {
+                self.mem_read(self.pointer as u16);
+                self.pointer = self.pointer.wrapping_add(self.register_x);
+            }
+            (AddressingMode::Indirect_X, 3) | (AddressingMode::Indirect_Y, 2) => {
+                self.address = self.mem_read(self.pointer as u16) as u16;
+            }
+            (AddressingMode::Indirect_X, 4) => {
+                self.address |= (self.mem_read(self.pointer.wrapping_add(1) as u16) as u16) << 8;
+            }
+            (AddressingMode::Indirect_Y, 3) => {
+                let high = self.mem_read(self.pointer.wrapping_add(1) as u16);
+                self.index_address(high, self.register_y);
+            }
+            (AddressingMode::Absolute_X | AddressingMode::Absolute_Y, 3)
+            | (AddressingMode::Indirect_Y, 4) => return self.fix_indexed_address(),
+            _ => unreachable!("{:?} has no addressing cycle {}", self.mode, cycle),
+        }
+        false
+    }
+
+    /// Reads and/or writes effective address.
+    fn operand_cycle(&mut self, cycle: u8) -> bool {
+        match (self.access, cycle) {
+            (Access::Read(operation), 0) => {
+                let data = self.mem_read(self.address);
+                operation(self, data);
+                true
+            }
+            (Access::Write(operation), 0) => {
+                // Unstable stores may change address
+                let data = operation(self);
+                self.mem_write(self.address, data);
+                true
+            }
+            (Access::ReadModifyWrite(_), 0) => {
+                self.data = self.mem_read(self.address);
+                false
+            }
+            (Access::ReadModifyWrite(operation), 1) => {
+                // Unmodified value is written back while it is modified
+                self.mem_write(self.address, self.data);
+                self.data = operation(self, self.data);
+                false
+            }
+            (Access::ReadModifyWrite(_), 2) => {
+                self.mem_write(self.address, self.data);
+                true
+            }
+            _ => unreachable!("{:?} has no operand cycle {}", self.access, cycle),
+        }
+    }
+
+    /// Cycles of relative branches: 2 if not taken, 3 if taken, 4 if taken to another page.
+    fn branch_cycle(&mut self, condition: fn(&Self) -> bool) -> bool {
+        match self.cycle {
+            2 => {
+                self.data = self.fetch_operand();
+                !condition(self)
+            }
+            3 => {
+                self.mem_read(self.program_counter);
+                self.address = self
+                    .program_counter
+                    .wrapping_add(self.data as i8 as u16);
+                self.page_crossed = page_cross(self.program_counter, self.address);
+                // Only low byte is updated, high byte is fixed on next cycle
+                self.program_counter = (self.program_counter & 0xFF00) | (self.address & 0x00FF);
+                !self.page_crossed
+            }
+            _ => {
+                self.mem_read(self.program_counter);
+                self.program_counter = self.address;
+                true
+            }
+        }
+    }
+
+    /// Cycles of stack and jump instructions.
+    fn control_flow_cycle(&mut self) -> bool {
+        match (self.opcode, self.cycle) {
+            /* PHA, PHP, PLA, PLP, RTS, RTI */
+            (0x48 | 0x08 | 0x68 | 0x28 | 0x60 | 0x40, 2) => {
+                self.mem_read(self.program_counter);
+            }
+
+            /* PHA */
+            (0x48, 3) => {
+                let data = self.pha();
+                self.stack_push(data);
+                return true;
+            }
+
+            /* PHP */
+            (0x08, 3) => {
+                let data = self.php();
+                self.stack_push(data);
+                return true;
+            }
+
+            /* PLA, PLP, RTS, RTI, JSR */
+            (0x68 | 0x28 | 0x60 | 0x40 | 0x20, 3) => self.dummy_stack_read(),
+
+            /* PLA */
+            (0x68, 4) => {
+                let data = self.stack_pop();
+                self.pla(data);
+                return true;
+            }
+
+            /* PLP */
+            (0x28, 4) => {
+                let data = self.stack_pop();
+                self.plp(data);
+                return true;
+            }
+
+            /* JSR, JMP Absolute, JMP Indirect */
+            (0x20 | 0x4c | 0x6c, 2) => {
+                self.address = self.fetch_operand() as u16;
+            }
+
+            /* JSR: return address is last byte of JSR */
+            (0x20, 4) => self.stack_push((self.program_counter >> 8) as u8),
+            (0x20, 5) => self.stack_push(self.program_counter as u8),
+
+            /* JSR, JMP Absolute */
+            (0x20 | 0x4c, _) => {
+                let high = self.mem_read(self.program_counter);
+                self.program_counter = (high as u16) << 8 | self.address;
+                return true;
+            }
+
+            /* RTS */
+            (0x60, 4) => self.address = self.stack_pop() as u16,
+            (0x60, 5) => {
+                self.address |= (self.stack_pop() as u16) << 8;
+                self.program_counter = self.address;
+            }
+            (0x60, _) => {
+                self.mem_read(self.program_counter);
+                self.program_counter = self.program_counter.wrapping_add(1);
+                return true;
+            }
+
+            /* RTI */
+            (0x40, 4) => {
+                let data = self.stack_pop();
+                self.rti(data);
+            }
+            (0x40, 5) => self.address = self.stack_pop() as u16,
+            (0x40, _) => {
+                self.program_counter = (self.stack_pop() as u16) << 8 | self.address;
+                return true;
+            }
+
+            /* JMP Indirect */
+            (0x6c, 3) => {
+                self.address |= (self.fetch_operand() as u16) << 8;
+            }
+            (0x6c, 4) => self.data = self.mem_read(self.address),
+            (0x6c, _) => {
+                // 6502 bug mode with with page boundary:
+                // If address $3000 contains $40, $30FF contains $80, and $3100 contains $50,
+                // the result of JMP ($30FF) will be a transfer of control to $4080 rather than $5080 as you intended
+                // i.e. the 6502 took the low byte of the address from $30FF and the high byte from $3000
+                let high_address = (self.address & 0xFF00) | (self.address as u8).wrapping_add(1) as u16;
+                let high = self.mem_read(high_address);
+                self.program_counter = (high as u16) << 8 | self.data as u16;
+                return true;
+            }
+
+            _ => unreachable!("{:#04x} is not a control flow instruction", self.opcode),
+        }
+        false
+    }
+
+    /// Cycles of BRK and hardware interrupts: push PC and status, then fetch vector.
+    fn interrupt_cycle(&mut self) -> bool {
+        match self.cycle {
+            2 => {
+                self.mem_read(self.program_counter);
+                // Return address skips padding byte after BRK
+                if self.interrupt.itype == InterruptType::Brk {
+                    self.program_counter = self.program_counter.wrapping_add(1);
+                }
+            }
+            // Reset does not write to the stack, but still moves the stack pointer
+            // Source: https://www.nesdev.org/wiki/CPU_power_up_state
+            3..=5 if self.interrupt.itype == InterruptType::Reset => {
+                self.dummy_stack_read();
+                self.stack_pointer = self.stack_pointer.wrapping_sub(1);
+                if self.cycle == 5 {
+                    self.address = self.interrupt.vector_addr;
+                    self.status.insert(CpuFlags::INTERRUPT_DISABLE);
+                    self.irq_inhibit = true;
+                }
+            }
+            3 => self.stack_push((self.program_counter >> 8) as u8),
+            4 => self.stack_push(self.program_counter as u8),
+            5 => {
+                // NMI occurring during the first cycles of IRQ/BRK hijacks the vector fetch
+                // Source: https://www.nesdev.org/wiki/CPU_interrupts#Interrupt_hijacking
+                self.address = self.interrupt.vector_addr;
+                if matches!(self.interrupt.itype, InterruptType::Irq | InterruptType::Brk)
+                    && self.poll_nmi()
+                {
+                    self.address = interrupt::NMI.vector_addr;
+                }
+
+                // Bits 4 and 5 only exist on the stack: B is set by BRK (and PHP), bit 5 is always set
+                // Source: https://www.nesdev.org/wiki/Status_flags#The_B_flag
+                let mut flag = self.status;
+                flag.set(
+                    CpuFlags::BREAK,
+                    self.interrupt.b_flag_mask & CpuFlags::BREAK.bits() != 0,
+                );
+                flag.set(
+                    CpuFlags::BREAK2,
+                    self.interrupt.b_flag_mask & CpuFlags::BREAK2.bits() != 0,
+                );
+                self.stack_push(flag.bits);
+                self.status.insert(CpuFlags::INTERRUPT_DISABLE);
+                self.irq_inhibit = true;
+            }
+            6 => self.data = self.mem_read(self.address),
+            _ => {
+                let high = self.mem_read(self.address.wrapping_add(1));
+                self.program_counter = (high as u16) << 8 | self.data as u16;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Unstable stores (SHX, SHY, AHX, TAS) AND data with address high byte + 1.
+    /// When page is crossed, stored value also replaces address high byte.
+    pub(super) fn unstable_store(&mut self, data: u8) -> u8 {
+        let high = ((self.address >> 8) as u8).wrapping_sub(self.page_crossed as u8);
+        let data = data & high.wrapping_add(1);
+        if self.page_crossed {
+            self.address = (data as u16) << 8 | (self.address & 0x00FF);
+        }
+        data
+    }
+}
@@ -0,0 +1,369 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    bus::cpu_bus::CpuBus,
+    cartridge::Cartridge,
+    cpu::{memory::BusAccess, Cpu},
+    memory::{Memory, Ram},
+    ppu::Ppu,
+};
+
+use BusAccess::{Read, Write};
+
+/// Runs one instruction at $8600.
+/// Returns bus accesses, checking there is exactly one per cycle.
+fn run_instruction(cpu: &mut Cpu<Ram>, code: &[u8]) -> Vec<BusAccess> {
+    cpu.load(code.to_vec());
+    cpu.program_counter = 0x8600;
+    cpu.bus_log = Some(vec![]);
+
+    let mut cycles = 0;
+    loop {
+        cpu.tick().unwrap();
+        cycles += 1;
+        assert_eq!(cpu.bus_log.as_ref().unwrap().len(), cycles);
+        if cpu.instruction_changed() {
+            break;
+        }
+    }
+    cpu.bus_log.take().unwrap()
+}
+
+#[test]
+fn test_implied_dummy_read() {
+    let mut cpu = Cpu::new(Ram::new());
+    // NOP
+    let accesses = run_instruction(&mut cpu, &[0xea, 0x42]);
+    assert_eq!(accesses, vec![Read(0x8600, 0xea), Read(0x8601, 0x42)]);
+}
+
+#[test]
+fn test_zero_page_x_dummy_read() {
+    let mut cpu = Cpu::new(Ram::new());
+    cpu.register_x = 0x20;
+    cpu.mem_write(0x10, 0x55);
+    // LDA $F0,X (wraps in zero page)
+    let accesses = run_instruction(&mut cpu, &[0xb5, 0xf0]);
+    assert_eq!(
+        accesses,
+        vec![
+            Read(0x8600, 0xb5),
+            Read(0x8601, 0xf0),
+            Read(0x00f0, 0x00),
+            Read(0x0010, 0x55),
+        ]
+    );
+    assert_eq!(cpu.register_a, 0x55);
+}
+
+#[test]
+fn test_absolute_x_read() {
+    let mut cpu = Cpu::new(Ram::new());
+    cpu.register_x = 0x01;
+    cpu.mem_write(0x1001, 0x55);
+    // LDA $1000,X
+    let accesses = run_instruction(&mut cpu, &[0xbd, 0x00, 0x10]);
+    assert_eq!(
+        accesses,
+        vec![
+            Read(0x8600, 0xbd),
+            Read(0x8601, 0x00),
+            Read(0x8602, 0x10),
+            Read(0x1001, 0x55),
+        ]
+    );
+}
+
+#[test]
+fn test_absolute_x_read_page_cross() {
+    let mut cpu = Cpu::new(Ram::new());
+    cpu.register_x = 0x20;
+    cpu.mem_write(0x1010, 0x11);
+    cpu.mem_write(0x1110, 0x55);
+    // LDA $10F0,X: first read is done before high byte is fixed
+    let accesses = run_instruction(&mut cpu, &[0xbd, 0xf0, 0x10]);
+    assert_eq!(
+        accesses,
+        vec![
+            Read(0x8600, 0xbd),
+            Read(0x8601, 0xf0),
+            Read(0x8602, 0x10),
+            Read(0x1010, 0x11),
+            Read(0x1110, 0x55),
+        ]
+    );
+    assert_eq!(cpu.register_a, 0x55);
+}
+
+#[test]
+fn test_absolute_x_write_dummy_read() {
+    let mut cpu = Cpu::new(Ram::new());
+    cpu.register_a = 0x55;
+    cpu.register_x = 0x01;
+    // STA $1000,X: always reads before writing
+    let accesses = run_instruction(&mut cpu, &[0x9d, 0x00, 0x10]);
+    assert_eq!(
+        accesses,
+        vec![
+            Read(0x8600, 0x9d),
+            Read(0x8601, 0x00),
+            Read(0x8602, 0x10),
+            Read(0x1001, 0x00),
+            Write(0x1001, 0x55),
+        ]
+    );
+}
+
+#[test]
+fn test_indirect_y_read_page_cross() {
+    let mut cpu = Cpu::new(Ram::new());
+    cpu.register_y = 0x20;
+    cpu.mem_write_u16(0x10, 0x10f0);
+    cpu.mem_write(0x1110, 0x55);
+    // LDA ($10),Y
+    let accesses = run_instruction(&mut cpu, &[0xb1, 0x10]);
+    assert_eq!(
+        accesses,
+        vec![
+            Read(0x8600, 0xb1),
+            Read(0x8601, 0x10),
+            Read(0x0010, 0xf0),
+            Read(0x0011, 0x10),
+            Read(0x1010, 0x00),
+            Read(0x1110, 0x55),
+        ]
+    );
+}
+
+#[test]
+fn test_read_modify_write_double_write() {
+    let mut cpu = Cpu::new(Ram::new());
+    cpu.mem_write(0x10, 0x05);
+    // INC $10: unmodified value is written back first
+    let accesses = run_instruction(&mut cpu, &[0xe6, 0x10]);
+    assert_eq!(
+        accesses,
+        vec![
+            Read(0x8600, 0xe6),
+            Read(0x8601, 0x10),
+            Read(0x0010, 0x05),
+            Write(0x0010, 0x05),
+            Write(0x0010, 0x06),
+        ]
+    );
+}
+
+#[test]
+fn test_read_modify_write_absolute_x() {
+    let mut cpu = Cpu::new(Ram::new());
+    cpu.register_x = 0x01;
+    cpu.mem_write(0x1001, 0x01);
+    // ASL $1000,X
+    let accesses = run_instruction(&mut cpu, &[0x1e, 0x00, 0x10]);
+    assert_eq!(
+        accesses,
+        vec![
+            Read(0x8600, 0x1e),
+            Read(0x8601, 0x00),
+            Read(0x8602, 0x10),
+            Read(0x1001, 0x01),
+            Read(0x1001, 0x01),
+            Write(0x1001, 0x01),
+            Write(0x1001, 0x02),
+        ]
+    );
+}
+
+#[test]
+fn test_branch_not_taken() {
+    let mut cpu = Cpu::new(Ram::new());
+    // BEQ +$10
+    let accesses = run_instruction(&mut cpu, &[0xf0, 0x10]);
+    assert_eq!(accesses, vec![Read(0x8600, 0xf0), Read(0x8601, 0x10)]);
+    assert_eq!(cpu.program_counter, 0x8602);
+}
+
+#[test]
+fn test_branch_taken() {
+    let mut cpu = Cpu::new(Ram::new());
+    // BNE +$10
+    let accesses = run_instruction(&mut cpu, &[0xd0, 0x10]);
+    assert_eq!(
+        accesses,
+        vec![Read(0x8600, 0xd0), Read(0x8601, 0x10), Read(0x8602, 0x00)]
+    );
+    assert_eq!(cpu.program_counter, 0x8612);
+}
+
+#[test]
+fn test_branch_taken_page_cross() {
+    let mut cpu = Cpu::new(Ram::new());
+    // BNE -3: reads next opcode, then same offset in wrong page
+    let accesses = run_instruction(&mut cpu, &[0xd0, 0xfd]);
+    assert_eq!(
+        accesses,
+        vec![
+            Read(0x8600, 0xd0),
+            Read(0x8601, 0xfd),
+            Read(0x8602, 0x00),
+            Read(0x86ff, 0x00),
+        ]
+    );
+    assert_eq!(cpu.program_counter, 0x85ff);
+}
+
+#[test]
+fn test_jsr_rts() {
+    let mut cpu = Cpu::new(Ram::new());
+    // JSR $1234
+    let accesses = run_instruction(&mut cpu, &[0x20, 0x34, 0x12]);
+    assert_eq!(
+        accesses,
+        vec![
+            Read(0x8600, 0x20),
+            Read(0x8601, 0x34),
+            Read(0x01fd, 0x00),
+            Write(0x01fd, 0x86),
+            Write(0x01fc, 0x02),
+            Read(0x8602, 0x12),
+        ]
+    );
+    assert_eq!(cpu.program_counter, 0x1234);
+
+    // RTS
+    let accesses = run_instruction(&mut cpu, &[0x60, 0x42]);
+    assert_eq!(
+        accesses,
+        vec![
+            Read(0x8600, 0x60),
+            Read(0x8601, 0x42),
+            Read(0x01fb, 0x00),
+            Read(0x01fc, 0x02),
+            Read(0x01fd, 0x86),
+            Read(0x8602, 0x12),
+        ]
+    );
+    assert_eq!(cpu.program_counter, 0x8603);
+}
+
+#[test]
+fn test_instruction_cycles() {
+    // Without page crossing, each instruction takes its base cycle count
+    // JAM is not tested: it never completes
+    for opcode in Cpu::<Ram>::OPCODES.iter().filter(|op| op.mnemonic != "*JAM") {
+        let mut cpu = Cpu::new(Ram::new());
+        // N, V, Z and C set
+        cpu.status = super::CpuFlags::from_bits_truncate(0b1100_0011);
+        let accesses = run_instruction(&mut cpu, &[opcode.code]);
+        let expected = match opcode.code {
+            // BCS, BEQ, BMI, BVS are taken
+            0xb0 | 0xf0 | 0x30 | 0x70 => opcode.cycles as usize + 1,
+            _ => opcode.cycles as usize,
+        };
+        assert_eq!(
+            accesses.len(),
+            expected,
+            "{} ({:#04x})",
+            opcode.mnemonic,
+            opcode.code
+        );
+    }
+}
+
+#[test]
+fn test_reset() {
+    let mut cpu = Cpu::new(Ram::new());
+    cpu.mem_write_u16(0xfffc, 0x8000);
+    cpu.reset();
+    cpu.bus_log = Some(vec![]);
+    for _ in 0..7 {
+        assert!(!cpu.instruction_changed());
+        cpu.tick().unwrap();
+    }
+    assert!(cpu.instruction_changed());
+
+    // Stack is read instead of written
+    assert_eq!(
+        cpu.bus_log.take().unwrap(),
+        vec![
+            Read(0x0000, 0x00),
+            Read(0x0000, 0x00),
+            Read(0x0100, 0x00),
+            Read(0x01ff, 0x00),
+            Read(0x01fe, 0x00),
+            Read(0xfffc, 0x00),
+            Read(0xfffd, 0x80),
+        ]
+    );
+    assert_eq!(cpu.program_counter, 0x8000);
+    assert_eq!(cpu.stack_pointer, 0xfd);
+    assert!(cpu.status.contains(super::CpuFlags::INTERRUPT_DISABLE));
+}
+
+/// Creates a CPU connected to a bus, running code at $8000.
+fn cpu_with_bus(code: &[u8]) -> Cpu {
+    let cartridge = Cartridge::from_code(code);
+    let bus = Rc::new(RefCell::new(CpuBus::new()));
+    bus.borrow_mut().connect_cartridge(&cartridge);
+    bus.borrow_mut().connect_ppu(&Rc::new(RefCell::new(Ppu::new())));
+    let mut cpu = Cpu::new(bus);
+    cpu.reset();
+    // Reset sequence
+    cpu.tick().unwrap();
+    while !cpu.instruction_changed() {
+        cpu.tick().unwrap();
+    }
+    cpu
+}
+
+/// Runs instructions until program counter reaches address.
+/// Returns the number of cycles.
+fn run_until(cpu: &mut Cpu, addr: u16) -> u64 {
+    let start = cpu.cycles();
+    cpu.tick().unwrap();
+    while !(cpu.instruction_changed() && cpu.program_counter == addr) {
+        cpu.tick().unwrap();
+    }
+    cpu.cycles() - start
+}
+
+#[test]
+fn test_oam_dma_stall() {
+    // Reset sequence takes 7 cycles
+    // LDA #$02, STA $4014: DMA starts on an odd cycle
+    let mut cpu = cpu_with_bus(&[0xa9, 0x02, 0x8d, 0x14, 0x40]);
+    assert_eq!(cpu.cycles(), 7);
+    assert_eq!(run_until(&mut cpu, 0x8002), 2);
+    assert_eq!(run_until(&mut cpu, 0x8005), 4 + 513);
+
+    // LDA #$02, BIT $00, STA $4014: DMA starts on an even cycle
+    let mut cpu = cpu_with_bus(&[0xa9, 0x02, 0x24, 0x00, 0x8d, 0x14, 0x40]);
+    assert_eq!(run_until(&mut cpu, 0x8004), 5);
+    assert_eq!(run_until(&mut cpu, 0x8007), 4 + 514);
+}
+
+#[test]
+fn test_jam() {
+    // JAM
+    let mut cpu = cpu_with_bus(&[0x02, 0x42]);
+    cpu.bus_log = Some(vec![]);
+    for _ in 0..5 {
+        cpu.tick().unwrap();
+        assert!(!cpu.instruction_changed());
+    }
+    assert!(cpu.jammed());
+    assert_eq!(
+        cpu.bus_log.take().unwrap(),
+        vec![
+            Read(0x8000, 0x02),
+            Read(0x8001, 0x42),
+            Read(0xffff, 0xea),
+            Read(0xffff, 0xea),
+            Read(0xffff, 0xea),
+        ]
+    );
+
+    // Only reset restarts the CPU
+    cpu.reset();
+    assert!(!cpu.jammed());
+}
@@ -0,0 +1,324 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    ops::RangeInclusive,
+};
+
+use crate::{cartridge::Cartridge, memory::Ram};
+
+use super::{cycle::Access, AddressingMode, Cpu};
+
+/// Interrupt vectors, with their entry point label.
+const VECTORS: [(u16, &str); 3] = [(0xfffa, "nmi"), (0xfffc, "reset"), (0xfffe, "irq")];
+
+/// Data bytes per .byte line, lines are aligned on this size.
+const BYTES_PER_LINE: usize = 8;
+
+/// PRG ROM bank size.
+const BANK_SIZE: usize = 0x4000;
+
+/// Role of a byte, found by code flow analysis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ByteKind {
+    Data,
+    Opcode,
+    Operand,
+}
+
+/// Disassembled line: instruction or data bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisassemblyLine {
+    pub address: u16,
+    /// Label of address, if any.
+    pub label: Option<String>,
+    pub bytes: Vec<u8>,
+    /// Instruction (e.g. "LDA #$10") or .byte directive.
+    pub text: String,
+}
+
+/// 6502 disassembler of a memory block (PRG ROM bank...).
+///
+/// Bytes are data until they are found to be code: by following code flow from entry points
+/// (see add_entry_point and add_vectors), or by linear disassembly of a range (see add_code_range).
+/// Branch, JMP and JSR targets get labels, and output can be reassembled by ca65 (see to_ca65).
+pub struct Disassembler<'a> {
+    data: &'a [u8],
+    /// Address of first byte.
+    origin: u16,
+    kinds: Vec<ByteKind>,
+    labels: BTreeMap<u16, String>,
+}
+
+impl<'a> Disassembler<'a> {
+    /// Creates a disassembler of data, mapped at origin.
+    /// Panics if data does not fit in address space, from origin.
+    pub fn new(data: &'a [u8], origin: u16) -> Self {
+        assert!(
+            origin as usize + data.len() <= 0x10000,
+            "data does not fit in address space"
+        );
+        Self {
+            data,
+            origin,
+            kinds: vec![ByteKind::Data; data.len()],
+            labels: BTreeMap::new(),
+        }
+    }
+
+    /// Creates a disassembler of a mapper 0 cartridge PRG ROM, at its CPU address:
+    /// $8000 for 32KB, $C000 for 16KB (mirrored at $8000).
+    /// Returns None for other mappers and PRG ROM sizes (see for_bank).
+    pub fn for_cartridge(cartridge: &'a Cartridge) -> Option<Self> {
+        let prg_rom = cartridge.prg_rom();
+        if cartridge.mapper != 0 || !matches!(prg_rom.len(), 0x4000 | 0x8000) {
+            return None;
+        }
+        Some(Self::new(prg_rom, (0x10000 - prg_rom.len()) as u16))
+    }
+
+    /// Creates a disassembler of a 16KB PRG ROM bank, mapped at origin (e.g. $8000 or $C000),
+    /// for banks switched by the cartridge mapper.
+    /// Returns None if the bank does not exist, or does not fit in address space from origin.
+    pub fn for_bank(cartridge: &'a Cartridge, bank: usize, origin: u16) -> Option<Self> {
+        let start = bank.checked_mul(BANK_SIZE)?;
+        let data = cartridge
+            .prg_rom()
+            .get(start..start.checked_add(BANK_SIZE)?)?;
+        if origin as usize + BANK_SIZE > 0x10000 {
+            return None;
+        }
+        Some(Self::new(data, origin))
+    }
+
+    /// Follows code flow from NMI, reset and IRQ vectors, if they are in data.
+    pub fn add_vectors(&mut self) {
+        for (vector, label) in VECTORS {
+            if let (Some(lo), Some(hi)) = (self.byte(vector), self.byte(vector + 1)) {
+                self.add_entry_point(u16::from_le_bytes([lo, hi]), Some(label));
+            }
+        }
+    }
+
+    /// Follows code flow from address: instructions are decoded until an unconditional jump,
+    /// a return or an interrupt, and branch and JSR targets are followed.
+    /// Flow stops when leaving data, or on bytes already decoded differently.
+    pub fn add_entry_point(&mut self, address: u16, label: Option<&str>) {
+        if let Some(label) = label {
+            self.labels.insert(address, label.to_string());
+        }
+
+        let mut pending = vec![address];
+        while let Some(mut address) = pending.pop() {
+            while let Some(len) = self.mark_instruction(address) {
+                let code = self.data[self.offset(address).unwrap()];
+                let opcode = &Cpu::<Ram>::OPCODES[code as usize];
+                if let Some(target) = self.target(address) {
+                    pending.push(target);
+                }
+                let ends_flow = matches!(opcode.access, Access::Interrupt | Access::Jam)
+                    || matches!(code, 0x4c | 0x6c | 0x40 | 0x60);
+                if ends_flow {
+                    break;
+                }
+                address = address.wrapping_add(len as u16);
+            }
+        }
+    }
+
+    /// Disassembles range linearly, as code.
+    /// Bytes that cannot start an instruction (already decoded, or truncated instruction) are left as is.
+    pub fn add_code_range(&mut self, range: RangeInclusive<u16>) {
+        let mut address = *range.start() as u32;
+        while address <= *range.end() as u32 {
+            match self.mark_instruction(address as u16) {
+                Some(len) => {
+                    self.target(address as u16);
+                    address += len as u32;
+                }
+                None => address += 1,
+            }
+        }
+    }
+
+    /// Labels, by address.
+    pub fn labels(&self) -> &BTreeMap<u16, String> {
+        &self.labels
+    }
+
+    /// Indicates if address holds an instruction opcode.
+    pub fn is_code(&self, address: u16) -> bool {
+        self.kind(address) == Some(ByteKind::Opcode)
+    }
+
+    /// Disassembles data: instructions, and data bytes as .byte directives.
+    /// Unofficial opcodes are output as .byte, with their disassembly as comment.
+    pub fn lines(&self) -> Vec<DisassemblyLine> {
+        let mut lines = vec![];
+        let mut offset = 0;
+        while offset < self.data.len() {
+            let address = self.address(offset);
+            let label = self.labels.get(&address).cloned();
+            let len = if self.kinds[offset] == ByteKind::Opcode {
+                Cpu::<Ram>::OPCODES[self.data[offset] as usize].len as usize
+            } else {
+                // Data bytes up to next instruction, label or line boundary
+                (offset..self.data.len())
+                    .enumerate()
+                    .take_while(|(i, o)| {
+                        let address = self.address(*o);
+                        self.kinds[*o] != ByteKind::Opcode
+                            && (*i == 0
+                                || (!self.labels.contains_key(&address)
+                                    && !(address as usize).is_multiple_of(BYTES_PER_LINE)))
+                    })
+                    .count()
+            };
+            let bytes = self.data[offset..offset + len].to_vec();
+            let text = if self.kinds[offset] == ByteKind::Opcode {
+                self.instruction_text(address, &bytes)
+            } else {
+                byte_directive(&bytes)
+            };
+            lines.push(DisassemblyLine {
+                address,
+                label,
+                bytes,
+                text,
+            });
+            offset += len;
+        }
+        lines
+    }
+
+    /// ca65 source, assembling to the same bytes at the same address.
+    /// Labels inside instructions, or outside data, are defined as constants.
+    pub fn to_ca65(&self) -> String {
+        let lines = self.lines();
+        let line_addresses: HashSet<u16> = lines.iter().map(|line| line.address).collect();
+        let mut source = String::new();
+        for (address, label) in &self.labels {
+            if !line_addresses.contains(address) {
+                source.push_str(&format!("{} = ${:04X}\n", label, address));
+            }
+        }
+        source.push_str(&format!(".org ${:04X}\n", self.origin));
+        for line in lines {
+            if let Some(label) = line.label {
+                source.push_str(&format!("{}:\n", label));
+            }
+            source.push_str(&format!("    {}\n", line.text));
+        }
+        source
+    }
+
+    /// Marks instruction at address as code, and returns its length.
+    /// Returns None if it does not fit in data, or overlaps bytes already decoded.
+    fn mark_instruction(&mut self, address: u16) -> Option<u8> {
+        let offset = self.offset(address)?;
+        let len = Cpu::<Ram>::OPCODES[self.data[offset] as usize].len;
+        let bytes = self.kinds.get(offset..offset + len as usize)?;
+        if bytes.iter().any(|kind| *kind != ByteKind::Data) {
+            return None;
+        }
+        self.kinds[offset] = ByteKind::Opcode;
+        for kind in &mut self.kinds[offset + 1..offset + len as usize] {
+            *kind = ByteKind::Operand;
+        }
+        Some(len)
+    }
+
+    /// Branch, JMP or JSR target of instruction at address.
+    /// Targets in data get a label.
+    fn target(&mut self, address: u16) -> Option<u16> {
+        let offset = self.offset(address)?;
+        let code = self.data[offset];
+        let (target, prefix) = match (&Cpu::<Ram>::OPCODES[code as usize].access, code) {
+            (Access::Branch(_), _) => (branch_target(address, self.data[offset + 1]), "loc"),
+            (_, 0x4c) => (self.word(offset + 1), "loc"),
+            (_, 0x20) => (self.word(offset + 1), "sub"),
+            _ => return None,
+        };
+        self.offset(target)?;
+        let label = self
+            .labels
+            .entry(target)
+            .or_insert_with(|| format!("{}_{:04X}", prefix, target));
+        // Subroutine naming prevails
+        if prefix == "sub" && label.starts_with("loc_") {
+            *label = format!("{}_{:04X}", prefix, target);
+        }
+        Some(target)
+    }
+
+    /// Instruction in ca65 syntax, with labels for control flow targets.
+    fn instruction_text(&self, address: u16, bytes: &[u8]) -> String {
+        let opcode = &Cpu::<Ram>::OPCODES[bytes[0] as usize];
+        let mnemonic = opcode.mnemonic.trim_start_matches('*');
+        let label_or = |target: u16| match self.labels.get(&target) {
+            Some(label) => label.clone(),
+            None => format!("${:04X}", target),
+        };
+        let operand_u16 = || u16::from_le_bytes([bytes[1], bytes[2]]);
+        // Absolute addressing of zero page is forced, ca65 would use zero page addressing
+        let absolute = || match operand_u16() {
+            operand if operand < 0x100 => format!("a:${:04X}", operand),
+            operand => format!("${:04X}", operand),
+        };
+
+        let operand = match (opcode.len, opcode.mode) {
+            (1, _) if matches!(bytes[0], 0x0a | 0x4a | 0x2a | 0x6a) => String::from("A"),
+            (1, _) => String::from(""),
+            (2, AddressingMode::Immediate) => format!("#${:02X}", bytes[1]),
+            (2, AddressingMode::ZeroPage) => format!("${:02X}", bytes[1]),
+            (2, AddressingMode::ZeroPage_X) => format!("${:02X},X", bytes[1]),
+            (2, AddressingMode::ZeroPage_Y) => format!("${:02X},Y", bytes[1]),
+            (2, AddressingMode::Indirect_X) => format!("(${:02X},X)", bytes[1]),
+            (2, AddressingMode::Indirect_Y) => format!("(${:02X}),Y", bytes[1]),
+            (2, _) => label_or(branch_target(address, bytes[1])),
+            (3, AddressingMode::Absolute) => absolute(),
+            (3, AddressingMode::Absolute_X) => format!("{},X", absolute()),
+            (3, AddressingMode::Absolute_Y) => format!("{},Y", absolute()),
+            (3, _) if bytes[0] == 0x6c => format!("(${:04X})", operand_u16()),
+            (3, _) => label_or(operand_u16()),
+            _ => String::from(""),
+        };
+        let instruction = format!("{} {}", mnemonic, operand).trim_end().to_string();
+
+        // ca65 may pick another encoding of unofficial opcodes
+        if opcode.mnemonic.starts_with('*') {
+            format!("{} ; {}", byte_directive(bytes), instruction)
+        } else {
+            instruction
+        }
+    }
+
+    fn offset(&self, address: u16) -> Option<usize> {
+        (address as usize)
+            .checked_sub(self.origin as usize)
+            .filter(|offset| *offset < self.data.len())
+    }
+
+    fn address(&self, offset: usize) -> u16 {
+        self.origin + offset as u16
+    }
+
+    fn byte(&self, address: u16) -> Option<u8> {
+        self.offset(address).map(|offset| self.data[offset])
+    }
+
+    fn word(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.data[offset], self.data[offset + 1]])
+    }
+
+    fn kind(&self, address: u16) -> Option<ByteKind> {
+        self.offset(address).map(|offset| self.kinds[offset])
+    }
+}
+
+fn branch_target(address: u16, offset: u8) -> u16 {
+    address.wrapping_add(2).wrapping_add(offset as i8 as u16)
+}
+
+fn byte_directive(bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(|b| format!("${:02X}", b)).collect();
+    format!(".byte {}", bytes.join(","))
+}
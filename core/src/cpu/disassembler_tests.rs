@@ -0,0 +1,224 @@
+use std::path::PathBuf;
+
+use crate::{
+    cartridge::Cartridge,
+    cpu::disassembler::{Disassembler, DisassemblyLine},
+};
+
+/// 16KB bank mapped at $C000: code, data and interrupt vectors.
+fn bank() -> Vec<u8> {
+    let code = [
+        0x78, // C000: SEI
+        0xa2, 0x00, // C001: LDX #$00
+        0xbd, 0x20, 0xc0, // C003: LDA $C020,X
+        0x20, 0x10, 0xc0, // C006: JSR $C010
+        0xe8, // C009: INX
+        0xd0, 0xf7, // C00A: BNE $C003
+        0x4c, 0x0c, 0xc0, // C00C: JMP $C00C
+        0x00, // C00F: unreachable
+        0x8d, 0x10, 0x00, // C010: STA $0010 (absolute)
+        0x04, 0x10, // C013: *NOP $10
+        0x60, // C015: RTS
+        0x40, // C016: RTI (NMI)
+        0x40, // C017: RTI (IRQ)
+    ];
+    let mut bank = vec![0; 0x4000];
+    bank[..code.len()].copy_from_slice(&code);
+    bank[0x20..0x23].copy_from_slice(&[1, 2, 3]);
+    bank[0x3ffa..].copy_from_slice(&[0x16, 0xc0, 0x00, 0xc0, 0x17, 0xc0]);
+    bank
+}
+
+#[test]
+fn test_code_flow() {
+    let bank = bank();
+    let mut disassembler = Disassembler::new(&bank, 0xc000);
+    disassembler.add_vectors();
+
+    let labels: Vec<(u16, &str)> = disassembler
+        .labels()
+        .iter()
+        .map(|(address, label)| (*address, label.as_str()))
+        .collect();
+    assert_eq!(
+        labels,
+        vec![
+            (0xc000, "reset"),
+            (0xc003, "loc_C003"),
+            (0xc00c, "loc_C00C"),
+            (0xc010, "sub_C010"),
+            (0xc016, "nmi"),
+            (0xc017, "irq"),
+        ]
+    );
+    assert!(disassembler.is_code(0xc00c));
+    assert!(!disassembler.is_code(0xc00f));
+    assert!(disassembler.is_code(0xc015));
+    assert!(!disassembler.is_code(0xc018));
+    assert!(!disassembler.is_code(0xc020));
+}
+
+#[test]
+fn test_lines() {
+    let bank = bank();
+    let mut disassembler = Disassembler::new(&bank, 0xc000);
+    disassembler.add_vectors();
+    let lines = disassembler.lines();
+
+    assert_eq!(
+        lines[5],
+        DisassemblyLine {
+            address: 0xc00a,
+            label: None,
+            bytes: vec![0xd0, 0xf7],
+            text: String::from("BNE loc_C003"),
+        }
+    );
+    let texts: Vec<&str> = lines.iter().map(|line| line.text.as_str()).collect();
+    assert_eq!(
+        texts[..14],
+        [
+            "SEI",
+            "LDX #$00",
+            "LDA $C020,X",
+            "JSR sub_C010",
+            "INX",
+            "BNE loc_C003",
+            "JMP loc_C00C",
+            ".byte $00",
+            "STA a:$0010",
+            ".byte $04,$10 ; NOP $10",
+            "RTS",
+            "RTI",
+            "RTI",
+            ".byte $00,$00,$00,$00,$00,$00,$00,$00",
+        ]
+    );
+    assert_eq!(lines[14].address, 0xc020);
+    assert!(lines[14].text.starts_with(".byte $01,$02,$03,$00"));
+
+    // Lines hold all bytes
+    let bytes: Vec<u8> = lines.into_iter().flat_map(|line| line.bytes).collect();
+    assert_eq!(bytes, bank);
+}
+
+#[test]
+fn test_to_ca65() {
+    let bank = bank();
+    let mut disassembler = Disassembler::new(&bank, 0xc000);
+    disassembler.add_vectors();
+    // Label inside an instruction
+    disassembler.add_entry_point(0xc011, Some("operand"));
+
+    let source = disassembler.to_ca65();
+    assert!(source.starts_with(
+        "operand = $C011
+.org $C000
+reset:
+    SEI
+    LDX #$00
+loc_C003:
+    LDA $C020,X
+    JSR sub_C010
+    INX
+    BNE loc_C003
+loc_C00C:
+    JMP loc_C00C
+    .byte $00
+sub_C010:
+    STA a:$0010
+    .byte $04,$10 ; NOP $10
+    RTS
+nmi:
+    RTI
+irq:
+    RTI
+"
+    ));
+    assert!(source.ends_with("    .byte $00,$00,$16,$C0,$00,$C0,$17,$C0\n"));
+}
+
+#[test]
+fn test_code_range() {
+    let bank = bank();
+    let mut disassembler = Disassembler::new(&bank[..0x18], 0xc000);
+    disassembler.add_code_range(0xc00c..=0xc017);
+
+    // $C00F is decoded as BRK, JSR and branch targets are not followed
+    let texts: Vec<String> = disassembler
+        .lines()
+        .into_iter()
+        .map(|line| line.text)
+        .collect();
+    assert_eq!(
+        texts,
+        [
+            ".byte $78,$A2,$00,$BD,$20,$C0,$20,$10",
+            ".byte $C0,$E8,$D0,$F7",
+            "JMP loc_C00C",
+            "BRK",
+            "STA a:$0010",
+            ".byte $04,$10 ; NOP $10",
+            "RTS",
+            "RTI",
+            "RTI",
+        ]
+    );
+    assert!(!disassembler.is_code(0xc000));
+}
+
+#[test]
+fn test_truncated_instruction() {
+    // JMP operand is out of data
+    let data = [0xea, 0x4c, 0x00];
+    let mut disassembler = Disassembler::new(&data, 0x8000);
+    disassembler.add_entry_point(0x8000, None);
+    let texts: Vec<String> = disassembler
+        .lines()
+        .into_iter()
+        .map(|line| line.text)
+        .collect();
+    assert_eq!(texts, ["NOP", ".byte $4C,$00"]);
+}
+
+#[test]
+fn test_nestest() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("res/nestest.nes");
+    let cartridge = Cartridge::from_file(path).unwrap();
+    let mut disassembler = Disassembler::for_cartridge(&cartridge).unwrap();
+    disassembler.add_vectors();
+    disassembler.add_entry_point(0xc000, Some("automation"));
+
+    // C000  4C F5 C5  JMP $C5F5
+    let lines = disassembler.lines();
+    let automation = lines.iter().find(|line| line.address == 0xc000).unwrap();
+    assert_eq!(automation.label.as_deref(), Some("automation"));
+    assert_eq!(automation.text, "JMP loc_C5F5");
+    assert!(disassembler.is_code(0xc5f5));
+    // C72A  D0 E0     BNE $C70C
+    assert!(lines
+        .iter()
+        .any(|line| line.address == 0xc72a && line.text == "BNE loc_C70C"));
+}
+
+#[test]
+fn test_banks() {
+    // 128KB MMC1 cartridge: last bank is fixed at $C000
+    let mut cartridge = Cartridge::from_code(&[]);
+    cartridge.prg_rom = vec![0; 0x20000];
+    cartridge.prg_rom[0x1c000..].copy_from_slice(&bank());
+    cartridge.mapper = 1;
+    assert!(Disassembler::for_cartridge(&cartridge).is_none());
+
+    let mut disassembler = Disassembler::for_bank(&cartridge, 7, 0xc000).unwrap();
+    disassembler.add_vectors();
+    assert!(disassembler.is_code(0xc010));
+    assert_eq!(disassembler.labels()[&0xc000], "reset");
+
+    // Switchable bank at $8000
+    let disassembler = Disassembler::for_bank(&cartridge, 0, 0x8000).unwrap();
+    assert_eq!(disassembler.lines()[0].address, 0x8000);
+
+    assert!(Disassembler::for_bank(&cartridge, 8, 0x8000).is_none());
+    assert!(Disassembler::for_bank(&cartridge, 0, 0xd000).is_none());
+}
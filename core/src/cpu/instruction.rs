@@ -1,9 +1,9 @@
-use crate::memory::Memory;
+use crate::bus::Bus;
 
 use super::{CpuFlags, Cpu};
 
 /// Instructions addressing mode
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(non_camel_case_types)]
 pub enum AddressingMode {
     Immediate,
@@ -19,10 +19,13 @@ pub enum AddressingMode {
 }
 
 /// NES CPU instructions.
+/// Bus accesses are performed cycle by cycle by the CPU (see cycle module):
+/// read instructions get the operand value, write instructions return the value to write,
+/// read-modify-write instructions get the operand value and return the modified one.
 /// Reference: https://www.nesdev.org/obelisk-6502-guide/reference.htm.
 pub(crate) trait Instructions {
-    fn lda(&mut self, mode: &AddressingMode);
-    fn sta(&mut self, mode: &AddressingMode);
+    fn lda(&mut self, data: u8);
+    fn sta(&mut self) -> u8;
     fn tax(&mut self);
     fn inx(&mut self);
     fn cld(&mut self);
@@ -32,71 +35,61 @@ pub(crate) trait Instructions {
     fn sec(&mut self);
     fn sei(&mut self);
     fn sed(&mut self);
-    fn pha(&mut self);
-    fn pla(&mut self);
-    fn php(&mut self);
-    fn plp(&mut self);
-    fn adc(&mut self, mode: &AddressingMode);
-    fn sbc(&mut self, mode: &AddressingMode);
-    fn and(&mut self, mode: &AddressingMode);
-    fn eor(&mut self, mode: &AddressingMode);
-    fn ora(&mut self, mode: &AddressingMode);
+    fn pha(&mut self) -> u8;
+    fn pla(&mut self, data: u8);
+    fn php(&mut self) -> u8;
+    fn plp(&mut self, data: u8);
+    fn adc(&mut self, data: u8);
+    fn sbc(&mut self, data: u8);
+    fn and(&mut self, data: u8);
+    fn eor(&mut self, data: u8);
+    fn ora(&mut self, data: u8);
     fn lsr_accumulator(&mut self);
-    fn lsr(&mut self, mode: &AddressingMode) -> u8;
+    fn lsr(&mut self, data: u8) -> u8;
     fn asl_accumulator(&mut self);
-    fn asl(&mut self, mode: &AddressingMode) -> u8;
+    fn asl(&mut self, data: u8) -> u8;
     fn rol_accumulator(&mut self);
-    fn rol(&mut self, mode: &AddressingMode) -> u8;
+    fn rol(&mut self, data: u8) -> u8;
     fn ror_accumulator(&mut self);
-    fn ror(&mut self, mode: &AddressingMode) -> u8;
-    fn inc(&mut self, mode: &AddressingMode) -> u8;
+    fn ror(&mut self, data: u8) -> u8;
+    fn inc(&mut self, data: u8) -> u8;
     fn iny(&mut self);
-    fn dec(&mut self, mode: &AddressingMode) -> u8;
+    fn dec(&mut self, data: u8) -> u8;
     fn dex(&mut self);
     fn dey(&mut self);
-    fn cmp(&mut self, mode: &AddressingMode);
-    fn cpy(&mut self, mode: &AddressingMode);
-    fn cpx(&mut self, mode: &AddressingMode);
-    fn jmp_indirect(&mut self);
-    fn jmp_absolute(&mut self);
-    fn jsr(&mut self);
-    fn rts(&mut self);
-    fn rti(&mut self);
-    fn bne(&mut self);
-    fn bvs(&mut self);
-    fn bvc(&mut self);
-    fn bpl(&mut self);
-    fn bmi(&mut self);
-    fn beq(&mut self);
-    fn bcs(&mut self);
-    fn bcc(&mut self);
-    fn bit(&mut self, mode: &AddressingMode);
-    fn stx(&mut self, mode: &AddressingMode);
-    fn sty(&mut self, mode: &AddressingMode);
-    fn ldx(&mut self, mode: &AddressingMode);
-    fn ldy(&mut self, mode: &AddressingMode);
+    fn cmp(&mut self, data: u8);
+    fn cpy(&mut self, data: u8);
+    fn cpx(&mut self, data: u8);
+    fn rti(&mut self, data: u8);
+    fn bne(&self) -> bool;
+    fn bvs(&self) -> bool;
+    fn bvc(&self) -> bool;
+    fn bpl(&self) -> bool;
+    fn bmi(&self) -> bool;
+    fn beq(&self) -> bool;
+    fn bcs(&self) -> bool;
+    fn bcc(&self) -> bool;
+    fn bit(&mut self, data: u8);
+    fn stx(&mut self) -> u8;
+    fn sty(&mut self) -> u8;
+    fn ldx(&mut self, data: u8);
+    fn ldy(&mut self, data: u8);
     fn tay(&mut self);
     fn tsx(&mut self);
     fn txa(&mut self);
     fn txs(&mut self);
     fn tya(&mut self);
+    fn nop(&mut self);
 }
 
-impl Instructions for Cpu {
-    fn lda(&mut self, mode: &AddressingMode) {
-        let (addr, page_cross) = self.get_operand_address(&mode);
-        let value = self.mem_read(addr);
-
-        self.register_a = value;
+impl<B: Bus> Instructions for Cpu<B> {
+    fn lda(&mut self, data: u8) {
+        self.register_a = data;
         self.update_zero_and_negative_flags(self.register_a);
-        if page_cross {
-            self.remaining_cycles += 1;
-        }
     }
 
-    fn sta(&mut self, mode: &AddressingMode) {
-        let (addr, _) = self.get_operand_address(mode);
-        self.mem_write(addr, self.register_a);
+    fn sta(&mut self) -> u8 {
+        self.register_a
     }
 
     fn tax(&mut self) {
@@ -137,204 +130,105 @@ impl Instructions for Cpu {
         self.status.insert(CpuFlags::DECIMAL_MODE);
     }
 
-    fn pha(&mut self) {
-        self.stack_push(self.register_a);
+    fn pha(&mut self) -> u8 {
+        self.register_a
     }
 
-    fn pla(&mut self) {
-        self.register_a = self.stack_pop();
+    fn pla(&mut self, data: u8) {
+        self.register_a = data;
         self.update_zero_and_negative_flags(self.register_a);
     }
 
-    fn php(&mut self) {
-        let mut flags = self.status.clone();
+    fn php(&mut self) -> u8 {
+        let mut flags = self.status;
         flags.insert(CpuFlags::BREAK);
         flags.insert(CpuFlags::BREAK2);
-        self.stack_push(flags.bits());
+        flags.bits()
     }
 
-    fn plp(&mut self) {
-        self.status.bits = self.stack_pop();
+    fn plp(&mut self, data: u8) {
+        self.status.bits = data;
         self.status.remove(CpuFlags::BREAK);
         self.status.insert(CpuFlags::BREAK2);
     }
 
-    fn adc(&mut self, mode: &AddressingMode) {
-        let (addr, page_cross) = self.get_operand_address(mode);
-        let value = self.mem_read(addr);
-        self.add_to_register_a(value);
-        if page_cross {
-            self.remaining_cycles += 1;
-        }
+    fn adc(&mut self, data: u8) {
+        self.add_to_register_a(data);
     }
 
-    fn sbc(&mut self, mode: &AddressingMode) {
-        let (addr, page_cross) = self.get_operand_address(&mode);
-        let data = self.mem_read(addr);
-        self.add_to_register_a(((data as i8).wrapping_neg().wrapping_sub(1)) as u8);
-        if page_cross {
-            self.remaining_cycles += 1;
-        }
+    fn sbc(&mut self, data: u8) {
+        self.sub_from_register_a(data);
     }
-    fn and(&mut self, mode: &AddressingMode) {
-        let (addr, page_cross) = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
+
+    fn and(&mut self, data: u8) {
         self.register_a = data & self.register_a;
         self.update_zero_and_negative_flags(self.register_a);
-        if page_cross {
-            self.remaining_cycles += 1;
-        }
     }
 
-    fn eor(&mut self, mode: &AddressingMode) {
-        let (addr, page_cross) = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
+    fn eor(&mut self, data: u8) {
         self.register_a = data ^ self.register_a;
         self.update_zero_and_negative_flags(self.register_a);
-        if page_cross {
-            self.remaining_cycles += 1;
-        }
     }
 
-    fn ora(&mut self, mode: &AddressingMode) {
-        let (addr, page_cross) = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
+    fn ora(&mut self, data: u8) {
         self.register_a = data | self.register_a;
         self.update_zero_and_negative_flags(self.register_a);
-        if page_cross {
-            self.remaining_cycles += 1;
-        }
     }
 
     fn lsr_accumulator(&mut self) {
-        let mut data = self.register_a;
-        if data & 1 == 1 {
-            self.status.insert(CpuFlags::CARRY);
-        } else {
-            self.status.remove(CpuFlags::CARRY);
-        }
-        data = data >> 1;
-        self.register_a = data;
-        self.update_zero_and_negative_flags(self.register_a);
+        self.register_a = self.lsr(self.register_a);
     }
 
-    fn lsr(&mut self, mode: &AddressingMode) -> u8 {
-        let (addr, _) = self.get_operand_address(mode);
-        let mut data = self.mem_read(addr);
-        if data & 1 == 1 {
-            self.status.insert(CpuFlags::CARRY);
-        } else {
-            self.status.remove(CpuFlags::CARRY);
-        }
-        data = data >> 1;
-        self.mem_write(addr, data);
+    fn lsr(&mut self, data: u8) -> u8 {
+        self.status.set(CpuFlags::CARRY, data & 1 == 1);
+        let data = data >> 1;
         self.update_zero_and_negative_flags(data);
         data
     }
 
     fn asl_accumulator(&mut self) {
-        let mut data = self.register_a;
-        if data >> 7 == 1 {
-            self.status.insert(CpuFlags::CARRY);
-        } else {
-            self.status.remove(CpuFlags::CARRY);
-        }
-        data = data << 1;
-        self.register_a = data;
-        self.update_zero_and_negative_flags(self.register_a);
+        self.register_a = self.asl(self.register_a);
     }
 
-    fn asl(&mut self, mode: &AddressingMode) -> u8 {
-        let (addr, _) = self.get_operand_address(mode);
-        let mut data = self.mem_read(addr);
-        if data >> 7 == 1 {
-            self.status.insert(CpuFlags::CARRY);
-        } else {
-            self.status.remove(CpuFlags::CARRY);
-        }
-        data = data << 1;
-        self.mem_write(addr, data);
+    fn asl(&mut self, data: u8) -> u8 {
+        self.status.set(CpuFlags::CARRY, data >> 7 == 1);
+        let data = data << 1;
         self.update_zero_and_negative_flags(data);
         data
     }
 
     fn rol_accumulator(&mut self) {
-        let mut data = self.register_a;
-        let old_carry = self.status.contains(CpuFlags::CARRY);
-
-        if data >> 7 == 1 {
-            self.status.insert(CpuFlags::CARRY);
-        } else {
-            self.status.remove(CpuFlags::CARRY);
-        }
-        data = data << 1;
-        if old_carry {
-            data = data | 1;
-        }
-        self.register_a = data;
-        self.update_zero_and_negative_flags(self.register_a);
+        self.register_a = self.rol(self.register_a);
     }
 
-    fn rol(&mut self, mode: &AddressingMode) -> u8 {
-        let (addr, _) = self.get_operand_address(mode);
-        let mut data = self.mem_read(addr);
+    fn rol(&mut self, data: u8) -> u8 {
         let old_carry = self.status.contains(CpuFlags::CARRY);
-
-        if data >> 7 == 1 {
-            self.status.insert(CpuFlags::CARRY);
-        } else {
-            self.status.remove(CpuFlags::CARRY);
-        }
-        data = data << 1;
+        self.status.set(CpuFlags::CARRY, data >> 7 == 1);
+        let mut data = data << 1;
         if old_carry {
-            data = data | 1;
+            data |= 1;
         }
-        self.mem_write(addr, data);
         self.update_zero_and_negative_flags(data);
         data
     }
 
     fn ror_accumulator(&mut self) {
-        let mut data = self.register_a;
-        let old_carry = self.status.contains(CpuFlags::CARRY);
-
-        if data & 1 == 1 {
-            self.status.insert(CpuFlags::CARRY);
-        } else {
-            self.status.remove(CpuFlags::CARRY);
-        }
-        data = data >> 1;
-        if old_carry {
-            data = data | 0b10000000;
-        }
-        self.register_a = data;
-        self.update_zero_and_negative_flags(self.register_a);
+        self.register_a = self.ror(self.register_a);
     }
 
-    fn ror(&mut self, mode: &AddressingMode) -> u8 {
-        let (addr, _) = self.get_operand_address(mode);
-        let mut data = self.mem_read(addr);
+    fn ror(&mut self, data: u8) -> u8 {
         let old_carry = self.status.contains(CpuFlags::CARRY);
-
-        if data & 1 == 1 {
-            self.status.insert(CpuFlags::CARRY);
-        } else {
-            self.status.remove(CpuFlags::CARRY);
-        }
-        data = data >> 1;
+        self.status.set(CpuFlags::CARRY, data & 1 == 1);
+        let mut data = data >> 1;
         if old_carry {
-            data = data | 0b10000000;
+            data |= 0b10000000;
         }
-        self.mem_write(addr, data);
         self.update_zero_and_negative_flags(data);
         data
     }
 
-    fn inc(&mut self, mode: &AddressingMode) -> u8 {
-        let (addr, _) = self.get_operand_address(mode);
-        let mut data = self.mem_read(addr);
-        data = data.wrapping_add(1);
-        self.mem_write(addr, data);
+    fn inc(&mut self, data: u8) -> u8 {
+        let data = data.wrapping_add(1);
         self.update_zero_and_negative_flags(data);
         data
     }
@@ -344,11 +238,8 @@ impl Instructions for Cpu {
         self.update_zero_and_negative_flags(self.register_y);
     }
 
-    fn dec(&mut self, mode: &AddressingMode) -> u8 {
-        let (addr, _) = self.get_operand_address(mode);
-        let mut data = self.mem_read(addr);
-        data = data.wrapping_sub(1);
-        self.mem_write(addr, data);
+    fn dec(&mut self, data: u8) -> u8 {
+        let data = data.wrapping_sub(1);
         self.update_zero_and_negative_flags(data);
         data
     }
@@ -363,130 +254,77 @@ impl Instructions for Cpu {
         self.update_zero_and_negative_flags(self.register_y);
     }
 
-    fn cmp(&mut self, mode: &AddressingMode) {
-        self.compare(mode, self.register_a);
+    fn cmp(&mut self, data: u8) {
+        self.compare(data, self.register_a);
     }
-    fn cpy(&mut self, mode: &AddressingMode) {
-        self.compare(mode, self.register_y);
-    }
-    fn cpx(&mut self, mode: &AddressingMode) {
-        self.compare(mode, self.register_x);
-    }
-
-    fn jmp_indirect(&mut self) {
-        let mem_address = self.mem_read_u16(self.program_counter);
-        // 6502 bug mode with with page boundary:
-        // If address $3000 contains $40, $30FF contains $80, and $3100 contains $50,
-        // the result of JMP ($30FF) will be a transfer of control to $4080 rather than $5080 as you intended
-        // i.e. the 6502 took the low byte of the address from $30FF and the high byte from $3000
-        let indirect_ref = if mem_address & 0x00FF == 0x00FF {
-            let lo = self.mem_read(mem_address);
-            let hi = self.mem_read(mem_address & 0xFF00);
-            (hi as u16) << 8 | (lo as u16)
-        } else {
-            self.mem_read_u16(mem_address)
-        };
 
-        self.program_counter = indirect_ref;
+    fn cpy(&mut self, data: u8) {
+        self.compare(data, self.register_y);
     }
 
-    fn jmp_absolute(&mut self) {
-        let mem_address = self.mem_read_u16(self.program_counter);
-        self.program_counter = mem_address;
+    fn cpx(&mut self, data: u8) {
+        self.compare(data, self.register_x);
     }
 
-    fn jsr(&mut self) {
-        self.stack_push_u16(self.program_counter + 2 - 1);
-        let target_address = self.mem_read_u16(self.program_counter);
-        self.program_counter = target_address
+    fn rti(&mut self, data: u8) {
+        self.plp(data);
     }
 
-    fn rts(&mut self) {
-        self.program_counter = self.stack_pop_u16() + 1;
+    fn bne(&self) -> bool {
+        !self.status.contains(CpuFlags::ZERO)
     }
 
-    fn rti(&mut self) {
-        self.status.bits = self.stack_pop();
-        self.status.remove(CpuFlags::BREAK);
-        self.status.insert(CpuFlags::BREAK2);
-
-        self.program_counter = self.stack_pop_u16();
-    }
-
-    fn bne(&mut self) {
-        self.branch(!self.status.contains(CpuFlags::ZERO));
-    }
-
-    fn bvs(&mut self) {
-        self.branch(self.status.contains(CpuFlags::OVERFLOW));
+    fn bvs(&self) -> bool {
+        self.status.contains(CpuFlags::OVERFLOW)
     }
 
-    fn bvc(&mut self) {
-        self.branch(!self.status.contains(CpuFlags::OVERFLOW));
+    fn bvc(&self) -> bool {
+        !self.status.contains(CpuFlags::OVERFLOW)
     }
 
-    fn bpl(&mut self) {
-        self.branch(!self.status.contains(CpuFlags::NEGATIV));
+    fn bpl(&self) -> bool {
+        !self.status.contains(CpuFlags::NEGATIV)
     }
 
-    fn bmi(&mut self) {
-        self.branch(self.status.contains(CpuFlags::NEGATIV));
+    fn bmi(&self) -> bool {
+        self.status.contains(CpuFlags::NEGATIV)
     }
 
-    fn beq(&mut self) {
-        self.branch(self.status.contains(CpuFlags::ZERO));
+    fn beq(&self) -> bool {
+        self.status.contains(CpuFlags::ZERO)
     }
 
-    fn bcs(&mut self) {
-        self.branch(self.status.contains(CpuFlags::CARRY));
+    fn bcs(&self) -> bool {
+        self.status.contains(CpuFlags::CARRY)
     }
 
-    fn bcc(&mut self) {
-        self.branch(!self.status.contains(CpuFlags::CARRY));
+    fn bcc(&self) -> bool {
+        !self.status.contains(CpuFlags::CARRY)
     }
 
-    fn bit(&mut self, mode: &AddressingMode) {
-        let (addr, _) = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
+    fn bit(&mut self, data: u8) {
         let and = self.register_a & data;
-        if and == 0 {
-            self.status.insert(CpuFlags::ZERO);
-        } else {
-            self.status.remove(CpuFlags::ZERO);
-        }
-
+        self.status.set(CpuFlags::ZERO, and == 0);
         self.status.set(CpuFlags::NEGATIV, data & 0b10000000 > 0);
         self.status.set(CpuFlags::OVERFLOW, data & 0b01000000 > 0);
     }
 
-    fn stx(&mut self, mode: &AddressingMode) {
-        let (addr, _) = self.get_operand_address(mode);
-        self.mem_write(addr, self.register_x);
+    fn stx(&mut self) -> u8 {
+        self.register_x
     }
 
-    fn sty(&mut self, mode: &AddressingMode) {
-        let (addr, _) = self.get_operand_address(mode);
-        self.mem_write(addr, self.register_y);
+    fn sty(&mut self) -> u8 {
+        self.register_y
     }
 
-    fn ldx(&mut self, mode: &AddressingMode) {
-        let (addr, page_cross) = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
+    fn ldx(&mut self, data: u8) {
         self.register_x = data;
         self.update_zero_and_negative_flags(self.register_x);
-        if page_cross {
-            self.remaining_cycles += 1;
-        }
     }
 
-    fn ldy(&mut self, mode: &AddressingMode) {
-        let (addr, page_cross) = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
+    fn ldy(&mut self, data: u8) {
         self.register_y = data;
         self.update_zero_and_negative_flags(self.register_y);
-        if page_cross {
-            self.remaining_cycles += 1;
-        }
     }
 
     fn tay(&mut self) {
@@ -512,4 +350,6 @@ impl Instructions for Cpu {
         self.register_a = self.register_y;
         self.update_zero_and_negative_flags(self.register_a);
     }
+
+    fn nop(&mut self) {}
 }
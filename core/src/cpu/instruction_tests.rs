@@ -1,8 +1,9 @@
-use crate::{cpu::{Memory, Cpu, CpuFlags}};
+use crate::{cpu::{Memory, Cpu, CpuFlags}, memory::Ram};
 
-use super::CpuError;
+use super::{CpuError, RunMode, Variant};
 
-pub(crate) fn run_code(cpu: &mut Cpu, code: Vec<u8>) -> Result<(), CpuError>{
+pub(crate) fn run_code(cpu: &mut Cpu<Ram>, code: Vec<u8>) -> Result<(), CpuError>{
+    cpu.set_run_mode(RunMode::StopOnBrk);
     cpu.load(code);
     cpu.program_counter = cpu.mem_read_u16(0xFFFC);
     cpu.run()
@@ -10,7 +11,7 @@ pub(crate) fn run_code(cpu: &mut Cpu, code: Vec<u8>) -> Result<(), CpuError>{
 
 #[test]
 fn test_0xa9_lda_immediate_load_data() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0xa9, 0x05, 0x00]).unwrap();
     assert_eq!(cpu.register_a, 5);
     assert!(cpu.status.bits() & 0b0000_0010 == 0);
@@ -19,14 +20,14 @@ fn test_0xa9_lda_immediate_load_data() {
 
 #[test]
 fn test_0xa9_lda_zero_flag() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0xa9, 0x00, 0x00]).unwrap();
     assert!(cpu.status.bits() & 0b0000_0010 == 0b10);
 }
 
 #[test]
 fn test_0xaa_tax() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0xa9, 0x0A, 0xaa, 0x00]).unwrap();
 
     assert_eq!(cpu.register_x, 10)
@@ -34,7 +35,7 @@ fn test_0xaa_tax() {
 
 #[test]
 fn test_inx_overflow() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0xa9, 0xff, 0xaa, 0xe8, 0xe8, 0x00]).unwrap();
 
     assert_eq!(cpu.register_x, 1)
@@ -42,7 +43,7 @@ fn test_inx_overflow() {
 
 #[test]
 fn test_lda_from_memory() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.mem_write(0x10, 0x55);
 
     run_code(&mut cpu,vec![0xa5, 0x10, 0x00]).unwrap();
@@ -52,63 +53,63 @@ fn test_lda_from_memory() {
 
 #[test]
 fn test_cld() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0xd8, 0x00]).unwrap();
     assert!(cpu.status.bits() & 0b0000_1000 == 0);
 }
 
 #[test]
 fn test_cli() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0x58, 0x00]).unwrap();
     assert!(cpu.status.bits() & 0b0000_0100 == 0);
 }
 
 #[test]
 fn test_clv() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0xb8, 0x00]).unwrap();
     assert!(cpu.status.bits() & 0b0100_0000 == 0);
 }
 
 #[test]
 fn test_clc() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0x18, 0x00]).unwrap();
     assert!(cpu.status.bits() & 0b0000_0001 == 0);
 }
 
 #[test]
 fn test_sec() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0x38, 0x00]).unwrap();
     assert!(cpu.status.bits() & 0b0000_0001 == 0b1);
 }
 
 #[test]
 fn test_sei() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0x78, 0x00]).unwrap();
     assert!(cpu.status.bits() & 0b0000_0100 == 0b100);
 }
 
 #[test]
 fn test_sed() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0xf8, 0x00]).unwrap();
     assert!(cpu.status.bits() & 0b0000_1000 == 0b1000);
 }
 
 #[test]
 fn test_pha() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0xa9, 0x05, 0x48, 0x00]).unwrap();
     assert_eq!(cpu.stack_pop(), 0x05);
 }
 
 #[test]
 fn test_pla() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     // Push 5 to a, push a to stack, push 0 to a, then pop stack to a
     run_code(&mut cpu,vec![0xa9, 0x05, 0x48, 0xa9, 0x00, 0x68, 0x00]).unwrap();
     assert_eq!(cpu.register_a, 0x05);
@@ -116,21 +117,23 @@ fn test_pla() {
 
 #[test]
 fn test_php() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0x08, 0x00]).unwrap();
     assert!(cpu.stack_pop() & 0b0011_0000 == 0b110000);
 }
 
 #[test]
 fn test_plp() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0x08, 0x28, 0x00]).unwrap();
-    assert!(cpu.stack_pop() & 0b0000_0000 == 0);
+    // B flag pushed by PHP is ignored, bit 5 stays set
+    assert!(!cpu.status.contains(CpuFlags::BREAK));
+    assert!(cpu.status.contains(CpuFlags::BREAK2));
 }
 
 #[test]
 fn test_0x6d_adc() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.mem_write(0x10, 0x02);
     // Push 5 to a, add mem 0x10 to a
     run_code(&mut cpu,vec![0xa9, 0x05, 0x6d, 0x10, 0x00]).unwrap();
@@ -139,16 +142,58 @@ fn test_0x6d_adc() {
 
 #[test]
 fn test_0xed_sbc() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.mem_write(0x10, 0x02);
     // Push 5 to a, sub mem 0x10 from a
     run_code(&mut cpu,vec![0xa9, 0x05, 0xed, 0x10, 0x00]).unwrap();
     assert_eq!(cpu.register_a, 0x02);
 }
 
+#[test]
+fn test_adc_decimal_mode() {
+    let mut cpu = Cpu::new(Ram::new());
+    cpu.set_variant(Variant::Mos6502);
+    // SED, CLC, LDA #$12, ADC #$34
+    run_code(&mut cpu, vec![0xf8, 0x18, 0xa9, 0x12, 0x69, 0x34, 0x00]).unwrap();
+    assert_eq!(cpu.register_a, 0x46);
+    assert!(!cpu.status.contains(CpuFlags::CARRY));
+
+    // SED, SEC, LDA #$58, ADC #$46
+    run_code(&mut cpu, vec![0xf8, 0x38, 0xa9, 0x58, 0x69, 0x46, 0x00]).unwrap();
+    assert_eq!(cpu.register_a, 0x05);
+    assert!(cpu.status.contains(CpuFlags::CARRY));
+}
+
+#[test]
+fn test_sbc_decimal_mode() {
+    let mut cpu = Cpu::new(Ram::new());
+    cpu.set_variant(Variant::Mos6502);
+    // SED, SEC, LDA #$46, SBC #$12
+    run_code(&mut cpu, vec![0xf8, 0x38, 0xa9, 0x46, 0xe9, 0x12, 0x00]).unwrap();
+    assert_eq!(cpu.register_a, 0x34);
+    assert!(cpu.status.contains(CpuFlags::CARRY));
+
+    // SED, SEC, LDA #$40, SBC #$13
+    run_code(&mut cpu, vec![0xf8, 0x38, 0xa9, 0x40, 0xe9, 0x13, 0x00]).unwrap();
+    assert_eq!(cpu.register_a, 0x27);
+
+    // SED, SEC, LDA #$12, SBC #$21: borrows
+    run_code(&mut cpu, vec![0xf8, 0x38, 0xa9, 0x12, 0xe9, 0x21, 0x00]).unwrap();
+    assert_eq!(cpu.register_a, 0x91);
+    assert!(!cpu.status.contains(CpuFlags::CARRY));
+}
+
+#[test]
+fn test_nes_ignores_decimal_mode() {
+    let mut cpu = Cpu::new(Ram::new());
+    // SED, CLC, LDA #$09, ADC #$01
+    run_code(&mut cpu, vec![0xf8, 0x18, 0xa9, 0x09, 0x69, 0x01, 0x00]).unwrap();
+    assert_eq!(cpu.register_a, 0x0a);
+}
+
 #[test]
 fn test_0x2d_and() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.mem_write(0x10, 0x02);
     // Push 5 to a, and mem 0x10 to a
     run_code(&mut cpu,vec![0xa9, 0x05, 0x2d, 0x10, 0x00]).unwrap();
@@ -157,7 +202,7 @@ fn test_0x2d_and() {
 
 #[test]
 fn test_0x4d_eor() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.mem_write(0x10, 0x02);
     // Push 5 to a, eor mem 0x10 to a
     run_code(&mut cpu,vec![0xa9, 0x05, 0x4d, 0x10, 0x00]).unwrap();
@@ -166,7 +211,7 @@ fn test_0x4d_eor() {
 
 #[test]
 fn test_0x0d_ora() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.mem_write(0x10, 0x02);
     // Push 5 to a, ora mem 0x10 to a
     run_code(&mut cpu,vec![0xa9, 0x05, 0x0d, 0x10, 0x00]).unwrap();
@@ -175,14 +220,14 @@ fn test_0x0d_ora() {
 
 #[test]
 fn test_lsr_accumulator() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0xa9, 0b0000_00010, 0x4a, 0x00]).unwrap();
     assert_eq!(cpu.register_a, 0b0000_00001);
 }
 
 #[test]
 fn test_0x4e_lsr() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.mem_write(0x10, 0b0000_00010);
     run_code(&mut cpu,vec![0x4e, 0x10, 0x00]).unwrap();
     assert_eq!(cpu.mem_read(0x10), 0b0000_00001);
@@ -190,14 +235,14 @@ fn test_0x4e_lsr() {
 
 #[test]
 fn test_asl_accumulator() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0xa9, 0b0000_00001, 0x0a, 0x00]).unwrap();
     assert_eq!(cpu.register_a, 0b0000_00010);
 }
 
 #[test]
 fn test_0x0e_lsr() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.mem_write(0x10, 0b0000_00001);
     run_code(&mut cpu,vec![0x0e, 0x10, 0x00]).unwrap();
     assert_eq!(cpu.mem_read(0x10), 0b0000_00010);
@@ -205,7 +250,7 @@ fn test_0x0e_lsr() {
 
 #[test]
 fn test_rol_accumulator() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.status.insert(CpuFlags::CARRY);
     run_code(&mut cpu,vec![0xa9, 0b0100_00001, 0x2a, 0x00]).unwrap();
     assert_eq!(cpu.register_a, 0b0000_00011);
@@ -214,7 +259,7 @@ fn test_rol_accumulator() {
 
 #[test]
 fn test_0x2e_rol() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.status.insert(CpuFlags::CARRY);
     cpu.mem_write(0x10, 0b0100_00001);
     run_code(&mut cpu,vec![0x2e, 0x10, 0x00]).unwrap();
@@ -224,7 +269,7 @@ fn test_0x2e_rol() {
 
 #[test]
 fn test_ror_accumulator() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.status.insert(CpuFlags::CARRY);
     run_code(&mut cpu,vec![0xa9, 0b0000_00010, 0x6a, 0x00]).unwrap();
     assert_eq!(cpu.register_a, 0b0100_00001);
@@ -233,7 +278,7 @@ fn test_ror_accumulator() {
 
 #[test]
 fn test_0x6e_ror() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.status.insert(CpuFlags::CARRY);
     cpu.mem_write(0x10, 0b0000_00010);
     run_code(&mut cpu,vec![0x6e, 0x10, 0x00]).unwrap();
@@ -243,7 +288,7 @@ fn test_0x6e_ror() {
 
 #[test]
 fn test_0xee_inc() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.mem_write(0x10, 0x01);
     run_code(&mut cpu,vec![0xee, 0x10, 0x00]).unwrap();
     assert_eq!(cpu.mem_read(0x10), 0x02);
@@ -251,7 +296,7 @@ fn test_0xee_inc() {
 
 #[test]
 fn test_iny() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_y = 0x01;
     run_code(&mut cpu,vec![0xc8, 0x00]).unwrap();
     assert_eq!(cpu.register_y, 0x02);
@@ -259,7 +304,7 @@ fn test_iny() {
 
 #[test]
 fn test_0xce_dec() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.mem_write(0x10, 0x01);
     run_code(&mut cpu,vec![0xce, 0x10, 0x00]).unwrap();
     assert_eq!(cpu.mem_read(0x10), 0x0);
@@ -267,7 +312,7 @@ fn test_0xce_dec() {
 
 #[test]
 fn test_dex() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_x = 0x01;
     run_code(&mut cpu,vec![0xca, 0x00]).unwrap();
     assert_eq!(cpu.register_x, 0x0);
@@ -275,7 +320,7 @@ fn test_dex() {
 
 #[test]
 fn test_dey() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_y = 0x01;
     run_code(&mut cpu,vec![0x88, 0x00]).unwrap();
     assert_eq!(cpu.register_y, 0x0);
@@ -283,7 +328,7 @@ fn test_dey() {
 
 #[test]
 fn test_0xcd_cmp() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     // A = M
     cpu.register_a = 0x01;
     cpu.mem_write(0x10, 0x01);
@@ -306,7 +351,7 @@ fn test_0xcd_cmp() {
 
 #[test]
 fn test_0xcc_cpy() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     // X = M
     cpu.register_y = 0x01;
     cpu.mem_write(0x10, 0x01);
@@ -329,7 +374,7 @@ fn test_0xcc_cpy() {
 
 #[test]
 fn test_0xec_cpx() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     // X = M
     cpu.register_x = 0x01;
     cpu.mem_write(0x10, 0x01);
@@ -352,29 +397,30 @@ fn test_0xec_cpx() {
 
 #[test]
 fn test_jmp_indirect() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0x6c, 0x10]).unwrap();
     assert_eq!(cpu.program_counter, 0x01);
 }
 
 #[test]
 fn test_jmp_absolute() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     run_code(&mut cpu,vec![0x4c, 0x10]).unwrap();
     assert_eq!(cpu.program_counter, 0x11);
 }
 
 #[test]
 fn test_rts() {
-    let mut cpu = Cpu::new();
-    cpu.stack_push_u16(0x05);
+    let mut cpu = Cpu::new(Ram::new());
+    cpu.stack_push(0x00);
+    cpu.stack_push(0x05);
     run_code(&mut cpu,vec![0x60]).unwrap();
     assert_eq!(cpu.program_counter, 0x07);
 }
 
 #[test]
 fn test_rti() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.stack_push(0b1111_1111);
     run_code(&mut cpu,vec![0x40]).unwrap();
     cpu.status.bits();
@@ -383,7 +429,7 @@ fn test_rti() {
 
 #[test]
 fn test_bne() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.status.remove(CpuFlags::ZERO);
     // Push 5 in a if branching is ok
     run_code(&mut cpu,vec![0xd0, 0x01, 0x00, 0xa9, 0x05]).unwrap();
@@ -392,7 +438,7 @@ fn test_bne() {
 
 #[test]
 fn test_bvs() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.status.insert(CpuFlags::OVERFLOW);
     // Push 5 in a if branching is ok
     run_code(&mut cpu,vec![0x70, 0x01, 0x00, 0xa9, 0x05]).unwrap();
@@ -401,7 +447,7 @@ fn test_bvs() {
 
 #[test]
 fn test_bvc() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.status.remove(CpuFlags::OVERFLOW);
     // Push 5 in a if branching is ok
     run_code(&mut cpu,vec![0x50, 0x01, 0x00, 0xa9, 0x05]).unwrap();
@@ -410,7 +456,7 @@ fn test_bvc() {
 
 #[test]
 fn test_bpl() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.status.remove(CpuFlags::NEGATIV);
     // Push 5 in a if branching is ok
     run_code(&mut cpu,vec![0x10, 0x01, 0x00, 0xa9, 0x05]).unwrap();
@@ -419,7 +465,7 @@ fn test_bpl() {
 
 #[test]
 fn test_bmi() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.status.insert(CpuFlags::NEGATIV);
     // Push 5 in a if branching is ok
     run_code(&mut cpu,vec![0x30, 0x01, 0x00, 0xa9, 0x05]).unwrap();
@@ -428,7 +474,7 @@ fn test_bmi() {
 
 #[test]
 fn test_beq() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.status.insert(CpuFlags::ZERO);
     // Push 5 in a if branching is ok
     run_code(&mut cpu,vec![0xf0, 0x01, 0x00, 0xa9, 0x05]).unwrap();
@@ -437,7 +483,7 @@ fn test_beq() {
 
 #[test]
 fn test_bcs() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.status.insert(CpuFlags::CARRY);
     // Push 5 in a if branching is ok
     run_code(&mut cpu,vec![0xb0, 0x01, 0x00, 0xa9, 0x05]).unwrap();
@@ -446,7 +492,7 @@ fn test_bcs() {
 
 #[test]
 fn test_bcc() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.status.remove(CpuFlags::CARRY);
     // Push 5 in a if branching is ok
     run_code(&mut cpu,vec![0x90, 0x01, 0x00, 0xa9, 0x05]).unwrap();
@@ -455,7 +501,7 @@ fn test_bcc() {
 
 #[test]
 fn test_0x2c_bit() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_a = 0x05;
     run_code(&mut cpu,vec![0x2c, 0x05, 0x00]).unwrap();
     assert!(cpu.status.contains(CpuFlags::ZERO));
@@ -463,7 +509,7 @@ fn test_0x2c_bit() {
 
 #[test]
 fn test_0x8d_sta() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_a = 0x05;
     run_code(&mut cpu,vec![0x8d, 0x10, 0x00]).unwrap();
     assert_eq!(cpu.mem_read(0x10), 0x05);
@@ -471,7 +517,7 @@ fn test_0x8d_sta() {
 
 #[test]
 fn test_0x8e_stx() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_x = 0x05;
     run_code(&mut cpu,vec![0x8e, 0x10, 0x00]).unwrap();
     assert_eq!(cpu.mem_read(0x10), 0x05);
@@ -479,7 +525,7 @@ fn test_0x8e_stx() {
 
 #[test]
 fn test_0x8c_sty() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_y = 0x05;
     run_code(&mut cpu,vec![0x8c, 0x10, 0x00]).unwrap();
     assert_eq!(cpu.mem_read(0x10), 0x05);
@@ -487,7 +533,7 @@ fn test_0x8c_sty() {
 
 #[test]
 fn test_0xae_ldx() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.mem_write(0x10, 0x05);
     run_code(&mut cpu,vec![0xae, 0x10, 0x00]).unwrap();
     assert_eq!(cpu.register_x, 0x05);
@@ -495,7 +541,7 @@ fn test_0xae_ldx() {
 
 #[test]
 fn test_0xac_ldy() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.mem_write(0x10, 0x05);
     run_code(&mut cpu,vec![0xac, 0x10, 0x00]).unwrap();
     assert_eq!(cpu.register_y, 0x05);
@@ -503,7 +549,7 @@ fn test_0xac_ldy() {
 
 #[test]
 fn test_tay() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_a = 0x05;
     run_code(&mut cpu,vec![0xa8, 0x00]).unwrap();
     assert_eq!(cpu.register_y, 0x05);
@@ -511,7 +557,7 @@ fn test_tay() {
 
 #[test]
 fn test_tsx() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.stack_pointer = 0x05;
     run_code(&mut cpu,vec![0xba, 0x00]).unwrap();
     assert_eq!(cpu.register_x, 0x05);
@@ -519,7 +565,7 @@ fn test_tsx() {
 
 #[test]
 fn test_txa() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_x = 0x05;
     run_code(&mut cpu,vec![0x8a, 0x00]).unwrap();
     assert_eq!(cpu.register_a, 0x05);
@@ -527,7 +573,7 @@ fn test_txa() {
 
 #[test]
 fn test_txs() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_x = 0x05;
     run_code(&mut cpu,vec![0x9a, 0x00]).unwrap();
     assert_eq!(cpu.stack_pointer, 0x05);
@@ -535,7 +581,7 @@ fn test_txs() {
 
 #[test]
 fn test_tya() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_y = 0x05;
     run_code(&mut cpu, vec![0x98, 0x00]).unwrap();
     assert_eq!(cpu.register_a, 0x05);
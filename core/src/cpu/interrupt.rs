@@ -1,26 +1,40 @@
-#[derive(PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InterruptType {
     Nmi,
+    Irq,
     Brk,
+    Reset,
 }
 
-#[derive(PartialEq, Eq)]
+/// Interrupt sequence: 7 cycles pushing PC and status, then fetching the vector.
+/// Reset uses the same sequence, with stack writes turned into reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) struct Interrupt {
     pub(super) itype: InterruptType,
     pub(super) vector_addr: u16,
     pub(super) b_flag_mask: u8,
-    pub(super) cpu_cycles: u8,
 }
+
 pub(crate) const NMI: Interrupt = Interrupt {
     itype: InterruptType::Nmi,
     vector_addr: 0xfffA,
     b_flag_mask: 0b00100000,
-    cpu_cycles: 2,
+};
+
+pub(crate) const IRQ: Interrupt = Interrupt {
+    itype: InterruptType::Irq,
+    vector_addr: 0xfffe,
+    b_flag_mask: 0b00100000,
 };
 
 pub(super) const BRK: Interrupt = Interrupt {
     itype: InterruptType::Brk,
     vector_addr: 0xfffe,
     b_flag_mask: 0b00110000,
-    cpu_cycles: 1,
+};
+
+pub(super) const RESET: Interrupt = Interrupt {
+    itype: InterruptType::Reset,
+    vector_addr: 0xfffc,
+    b_flag_mask: 0b00100000,
 };
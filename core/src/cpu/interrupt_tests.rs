@@ -0,0 +1,329 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    bus::cpu_bus::{CpuBus, IrqSource},
+    cartridge::Cartridge,
+    cpu::{Cpu, CpuFlags, RunMode},
+    memory::Memory,
+    ppu::Ppu,
+};
+
+const NMI_HANDLER: u16 = 0x9000;
+const IRQ_HANDLER: u16 = 0xA000;
+
+/// Test system running code at $8000.
+/// PRG ROM is filled with NOPs, so interrupt handlers are NOP slides.
+struct TestSystem {
+    cpu: Cpu,
+    bus: Rc<RefCell<CpuBus>>,
+    ppu: Rc<RefCell<Ppu>>,
+}
+
+impl TestSystem {
+    fn new(code: &[u8]) -> Self {
+        Self::with_irq_handler(code, &[])
+    }
+
+    fn with_irq_handler(code: &[u8], irq_handler: &[u8]) -> Self {
+        let mut cartridge = Cartridge::from_code(code);
+        let prg_rom = &mut cartridge.prg_rom;
+        let irq_handler_start = (IRQ_HANDLER - 0x8000) as usize;
+        prg_rom[irq_handler_start..irq_handler_start + irq_handler.len()]
+            .copy_from_slice(irq_handler);
+        prg_rom[0x7FFA..0x7FFC].copy_from_slice(&NMI_HANDLER.to_le_bytes());
+        prg_rom[0x7FFE..0x8000].copy_from_slice(&IRQ_HANDLER.to_le_bytes());
+
+        let bus = Rc::new(RefCell::new(CpuBus::new()));
+        let ppu = Rc::new(RefCell::new(Ppu::new()));
+        bus.borrow_mut().connect_cartridge(&cartridge);
+        bus.borrow_mut().connect_ppu(&ppu);
+        let mut cpu = Cpu::new(Rc::clone(&bus));
+        cpu.reset();
+
+        let mut system = Self { cpu, bus, ppu };
+        // Reset sequence
+        system.step();
+        system
+    }
+
+    /// Runs until next instruction (or interrupt handler) starts.
+    fn step(&mut self) {
+        self.cpu.tick().unwrap();
+        while !self.cpu.instruction_changed() {
+            self.cpu.tick().unwrap();
+        }
+    }
+
+    fn set_irq(&mut self, asserted: bool) {
+        self.bus.borrow_mut().set_irq(IrqSource::EXTERNAL, asserted);
+    }
+
+    fn trigger_nmi(&mut self) {
+        self.ppu.borrow_mut().trigger_nmi();
+    }
+
+    /// Return address pushed by last interrupt.
+    fn pushed_return_address(&mut self) -> u16 {
+        let sp = self.cpu.stack_pointer() as u16;
+        self.bus.borrow_mut().mem_read_u16(0x0100 + sp + 2)
+    }
+
+    /// Status pushed by last interrupt.
+    fn pushed_status(&mut self) -> CpuFlags {
+        let sp = self.cpu.stack_pointer() as u16;
+        CpuFlags::from_bits_truncate(self.bus.borrow_mut().mem_read(0x0100 + sp + 1))
+    }
+}
+
+#[test]
+fn test_nmi() {
+    let mut system = TestSystem::new(&[]);
+    system.trigger_nmi();
+    system.step();
+    assert_eq!(system.cpu.program_counter(), NMI_HANDLER);
+    assert_eq!(system.pushed_return_address(), 0x8001);
+    assert!(!system.ppu.borrow().nmi_interrupt());
+}
+
+#[test]
+fn test_irq() {
+    // CLI
+    let mut system = TestSystem::new(&[0x58]);
+    system.set_irq(true);
+
+    // CLI takes effect after next instruction
+    system.step();
+    assert_eq!(system.cpu.program_counter(), 0x8001);
+    system.step();
+    assert_eq!(system.cpu.program_counter(), IRQ_HANDLER);
+    assert_eq!(system.pushed_return_address(), 0x8002);
+    assert!(!system.pushed_status().contains(CpuFlags::INTERRUPT_DISABLE));
+    assert!(system.cpu.status().contains(CpuFlags::INTERRUPT_DISABLE));
+
+    // Handler runs with interrupts disabled, line is still asserted
+    system.step();
+    system.step();
+    assert_eq!(system.cpu.program_counter(), IRQ_HANDLER + 2);
+}
+
+#[test]
+fn test_irq_ignored_when_disabled() {
+    // Interrupts are disabled on reset
+    let mut system = TestSystem::new(&[]);
+    system.set_irq(true);
+    for _ in 0..3 {
+        system.step();
+    }
+    assert_eq!(system.cpu.program_counter(), 0x8003);
+}
+
+#[test]
+fn test_irq_after_release() {
+    // CLI
+    let mut system = TestSystem::new(&[0x58]);
+    system.set_irq(true);
+    system.step();
+    // Line released before polling
+    system.set_irq(false);
+    system.step();
+    system.step();
+    assert_eq!(system.cpu.program_counter(), 0x8003);
+}
+
+#[test]
+fn test_sei_delay() {
+    // CLI, NOP, SEI
+    let mut system = TestSystem::new(&[0x58, 0xea, 0x78]);
+    system.step();
+    system.step();
+
+    // IRQ is still taken after SEI, with interrupt disable flag pushed
+    system.set_irq(true);
+    system.step();
+    assert_eq!(system.cpu.program_counter(), IRQ_HANDLER);
+    assert_eq!(system.pushed_return_address(), 0x8003);
+    assert!(system.pushed_status().contains(CpuFlags::INTERRUPT_DISABLE));
+}
+
+#[test]
+fn test_plp_delay() {
+    // LDA #$04, PHA, CLI, NOP, PLP
+    let mut system = TestSystem::new(&[0xa9, 0x04, 0x48, 0x58, 0xea, 0x28]);
+    for _ in 0..4 {
+        system.step();
+    }
+
+    // IRQ is still taken after PLP disables interrupts
+    system.set_irq(true);
+    system.step();
+    assert_eq!(system.cpu.program_counter(), IRQ_HANDLER);
+    assert_eq!(system.pushed_return_address(), 0x8006);
+}
+
+#[test]
+fn test_rti_no_delay() {
+    // Pushes return address $8010 and status with interrupts enabled, then RTI
+    let mut system = TestSystem::new(&[0xa9, 0x80, 0x48, 0xa9, 0x10, 0x48, 0xa9, 0x00, 0x48, 0x40]);
+    system.set_irq(true);
+    for _ in 0..6 {
+        system.step();
+    }
+    assert_eq!(system.cpu.program_counter(), 0x8009);
+
+    // RTI enables interrupts immediately
+    system.step();
+    assert_eq!(system.cpu.program_counter(), IRQ_HANDLER);
+    assert_eq!(system.pushed_return_address(), 0x8010);
+}
+
+#[test]
+fn test_nmi_priority() {
+    // CLI
+    let mut system = TestSystem::new(&[0x58]);
+    system.step();
+    system.set_irq(true);
+    system.trigger_nmi();
+    system.step();
+    assert_eq!(system.cpu.program_counter(), NMI_HANDLER);
+}
+
+#[test]
+fn test_nmi_hijacks_irq() {
+    // CLI
+    let mut system = TestSystem::new(&[0x58]);
+    system.set_irq(true);
+    system.step();
+
+    // NOP (2 cycles), then NMI occurs early in IRQ sequence (2nd cycle)
+    for _ in 0..4 {
+        system.cpu.tick().unwrap();
+    }
+    system.trigger_nmi();
+    system.step();
+
+    // IRQ is hijacked: NMI handler runs with IRQ return address
+    assert_eq!(system.cpu.program_counter(), NMI_HANDLER);
+    assert_eq!(system.pushed_return_address(), 0x8002);
+    assert!(!system.ppu.borrow().nmi_interrupt());
+}
+
+#[test]
+fn test_nmi_after_hijack_window() {
+    // CLI
+    let mut system = TestSystem::new(&[0x58]);
+    system.set_irq(true);
+    system.step();
+
+    // NOP (2 cycles), then NMI occurs late in IRQ sequence (after status push)
+    for _ in 0..7 {
+        system.cpu.tick().unwrap();
+    }
+    system.trigger_nmi();
+    system.step();
+    assert_eq!(system.cpu.program_counter(), IRQ_HANDLER);
+
+    // First handler instruction runs before NMI
+    system.step();
+    assert_eq!(system.cpu.program_counter(), NMI_HANDLER);
+    assert_eq!(system.pushed_return_address(), IRQ_HANDLER + 1);
+}
+
+#[test]
+fn test_brk() {
+    // BRK runs even when interrupts are disabled
+    let mut system = TestSystem::new(&[0x00]);
+    system.step();
+    assert_eq!(system.cpu.program_counter(), IRQ_HANDLER);
+    // Padding byte is skipped
+    assert_eq!(system.pushed_return_address(), 0x8002);
+    assert!(system.cpu.status().contains(CpuFlags::INTERRUPT_DISABLE));
+}
+
+#[test]
+fn test_brk_rti() {
+    // BRK, padding, NOP; handler: RTI
+    let mut system = TestSystem::with_irq_handler(&[0x00, 0xff, 0xea], &[0x40]);
+    system.step();
+    assert_eq!(system.cpu.program_counter(), IRQ_HANDLER);
+    system.step();
+    assert_eq!(system.cpu.program_counter(), 0x8002);
+}
+
+#[test]
+fn test_nmi_hijacks_brk() {
+    let mut system = TestSystem::new(&[0x00]);
+    system.cpu.tick().unwrap();
+    system.cpu.tick().unwrap();
+    system.trigger_nmi();
+    system.step();
+    assert_eq!(system.cpu.program_counter(), NMI_HANDLER);
+    assert_eq!(system.pushed_return_address(), 0x8002);
+    // B flag is still pushed
+    assert!(system.pushed_status().contains(CpuFlags::BREAK));
+}
+
+#[test]
+fn test_stop_on_brk() {
+    let mut system = TestSystem::new(&[0xea, 0x00]);
+    system.cpu.set_run_mode(RunMode::StopOnBrk);
+    system.step();
+    assert!(!system.cpu.tick().unwrap());
+}
+
+#[test]
+fn test_nmi_pushed_status() {
+    let mut system = TestSystem::new(&[]);
+    system.trigger_nmi();
+    system.step();
+    // Bit 5 set, B clear
+    assert_eq!(system.pushed_status().bits(), 0b0010_0100);
+}
+
+#[test]
+fn test_irq_pushed_status() {
+    // CLI
+    let mut system = TestSystem::new(&[0x58]);
+    system.set_irq(true);
+    system.step();
+    system.step();
+    // Bit 5 set, B clear
+    assert_eq!(system.pushed_status().bits(), 0b0010_0000);
+}
+
+#[test]
+fn test_brk_pushed_status() {
+    let mut system = TestSystem::new(&[0x00]);
+    system.step();
+    // Bit 5 and B set
+    assert_eq!(system.pushed_status().bits(), 0b0011_0100);
+    // B only exists on stack
+    assert!(!system.cpu.status().contains(CpuFlags::BREAK));
+}
+
+#[test]
+fn test_php_pushed_status() {
+    // CLI, PHP
+    let mut system = TestSystem::new(&[0x58, 0x08]);
+    system.step();
+    system.step();
+    let sp = system.cpu.stack_pointer() as u16;
+    // Bit 5 and B set
+    assert_eq!(
+        system.bus.borrow_mut().mem_read(0x0100 + sp + 1),
+        0b0011_0000
+    );
+}
+
+#[test]
+fn test_plp_ignores_bits_4_and_5() {
+    // LDA #$FF, PHA, PLP, LDA #$00, PHA, PLP
+    let mut system = TestSystem::new(&[0xa9, 0xff, 0x48, 0x28, 0xa9, 0x00, 0x48, 0x28]);
+    for _ in 0..3 {
+        system.step();
+    }
+    assert_eq!(system.cpu.status().bits(), 0b1110_1111);
+    for _ in 0..3 {
+        system.step();
+    }
+    assert_eq!(system.cpu.status().bits(), 0b0010_0000);
+}
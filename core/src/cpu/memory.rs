@@ -1,41 +1,34 @@
-use crate::memory::Memory;
+use crate::{bus::Bus, memory::Memory};
 
 use super::Cpu;
 
-impl Memory for Cpu {
+/// Bus access of a CPU cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusAccess {
+    Read(u16, u8),
+    Write(u16, u8),
+}
+
+impl<B: Bus> Memory for Cpu<B> {
     fn mem_read(&mut self, addr: u16) -> u8 {
-        if let Some(bus) = &self.bus {
-            bus.borrow_mut().mem_read(addr)
-        } else {
-            self.memory[addr as usize]
+        let data = self.bus.mem_read(addr);
+        #[cfg(test)]
+        if let Some(log) = &mut self.bus_log {
+            log.push(BusAccess::Read(addr, data));
         }
+        data
     }
 
     fn mem_write(&mut self, addr: u16, data: u8) {
-        if let Some(bus) = &self.bus {
-            bus.borrow_mut().mem_write(addr, data);
-        } else {
-            self.memory[addr as usize] = data;
-        }
-    }
-    fn mem_read_u16(&mut self, pos: u16) -> u16 {
-        if let Some(bus) = &self.bus {
-            bus.borrow_mut().mem_read_u16(pos)
-        } else {
-            let lo = self.mem_read(pos) as u16;
-            let hi = self.mem_read(pos + 1) as u16;
-            (hi << 8) | (lo as u16)
+        #[cfg(test)]
+        if let Some(log) = &mut self.bus_log {
+            log.push(BusAccess::Write(addr, data));
         }
+        self.bus.mem_write(addr, data);
     }
 
-    fn mem_write_u16(&mut self, pos: u16, data: u16) {
-        if let Some(bus) = &self.bus {
-            bus.borrow_mut().mem_write_u16(pos, data);
-        } else {
-            let hi = (data >> 8) as u8;
-            let lo = (data & 0xff) as u8;
-            self.mem_write(pos, lo);
-            self.mem_write(pos + 1, hi);
-        }
+    /// Not recorded in bus log: peeking does not access the bus.
+    fn mem_peek(&self, addr: u16) -> u8 {
+        self.bus.mem_peek(addr)
     }
 }
@@ -1,19 +1,30 @@
 use bitflags::bitflags;
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{cell::RefCell, rc::Rc};
 
 use crate::{
-    bus::cpu_bus::CpuBus,
+    bus::{cpu_bus::CpuBus, Bus},
     memory::{page_cross, Memory},
 };
 
-use self::{
-    instruction::{AddressingMode, Instructions},
-    unofficial_instruction::UnofficialInstructions,
-};
+use self::{cycle::Access, instruction::Instructions, interrupt::Interrupt};
+
+pub use self::instruction::AddressingMode;
 
 #[cfg(test)]
 pub mod mod_tests;
 
+pub mod assembler;
+#[cfg(test)]
+mod assembler_tests;
+
+mod cycle;
+#[cfg(test)]
+mod cycle_tests;
+
+pub mod disassembler;
+#[cfg(test)]
+mod disassembler_tests;
+
 mod instruction;
 #[cfg(test)]
 mod instruction_tests;
@@ -23,14 +34,22 @@ mod unofficial_instruction;
 mod unofficial_instruction_tests;
 
 mod interrupt;
+#[cfg(test)]
+mod interrupt_tests;
 
 pub mod memory;
 mod opcode;
+pub mod single_step;
+#[cfg(test)]
+mod single_step_tests;
 pub mod trace;
-
 #[cfg(test)]
 mod trace_tests;
 
+pub mod trace_format;
+#[cfg(test)]
+mod trace_format_tests;
+
 bitflags! {
     /// # Status Register (P) http://wiki.nesdev.com/w/index.php/Status_flags
     ///
@@ -59,32 +78,81 @@ bitflags! {
 const STACK: u16 = 0x0100;
 const STACK_RESET: u8 = 0xfd;
 
-/// CPU.
+/// CPU run mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunMode {
+    /// BRK triggers a software interrupt, as on hardware.
+    Normal,
+    /// BRK stops execution: tick returns false.
+    /// Used by unit tests running code snippets ended by BRK.
+    StopOnBrk,
+}
+
+/// CPU variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    /// NES CPU: decimal mode flag has no effect.
+    Ricoh2A03,
+    /// Original 6502: ADC and SBC use BCD arithmetic when decimal mode flag is set.
+    Mos6502,
+}
+
+/// 6502 CPU, driving a bus.
+/// Defaults to NES CPU, driving the NES CPU bus shared with the console.
 #[derive(Debug, Clone)]
-pub struct Cpu {
+pub struct Cpu<B = Rc<RefCell<CpuBus>>> {
     register_a: u8,
     register_x: u8,
     register_y: u8,
     status: CpuFlags,
     program_counter: u16,
     stack_pointer: u8,
-    memory: [u8; 0xFFFF],
-    /// Remaining cycles count before moving to the next instruction.
-    remaining_cycles: u8,
-    bus: Option<Rc<RefCell<CpuBus>>>,
+    /// Current instruction opcode.
+    opcode: u8,
+    /// Current instruction addressing mode.
+    mode: AddressingMode,
+    /// Current instruction bus access pattern.
+    access: Access<B>,
+    /// Current cycle of current instruction, 0 when next cycle starts a new instruction.
+    cycle: u8,
+    /// Effective address of current instruction.
+    address: u16,
+    /// Zero page pointer of indirect addressing modes.
+    pointer: u8,
+    /// Data kept between cycles of current instruction.
+    data: u8,
+    /// Indexed address or branch crossed a page.
+    page_crossed: bool,
+    /// Current interrupt sequence (BRK or hardware interrupt).
+    interrupt: Interrupt,
+    /// Interrupt sequence to run instead of next instruction.
+    pending_interrupt: Option<Interrupt>,
+    /// Remaining cycles during which the CPU is halted by DMC DMA.
+    stall_cycles: u8,
+    /// Cycles since power on.
+    cycles: u64,
+    /// Interrupt disable flag as seen by interrupt polling.
+    /// CLI, SEI and PLP change it after polling, so their effect is delayed by one instruction.
+    irq_inhibit: bool,
+    run_mode: RunMode,
+    variant: Variant,
+    bus: B,
+    /// Bus accesses recorded by tests.
+    #[cfg(test)]
+    bus_log: Option<Vec<memory::BusAccess>>,
 }
 
 /// CPU Error.
+/// No longer produced: all 256 opcodes are implemented, unofficial ones included.
+/// Kept as the error type of tick and run, for future errors.
 #[derive(Debug)]
 pub enum CpuError {
     UnknownOpCode(u8),
 }
 
-impl Cpu {
-    /// Creates a CPU.
-    /// Used internal memory until bus is connected.
-    /// Call connect_bus to connect to an external bus.
-    pub fn new() -> Self {
+impl<B: Bus> Cpu<B> {
+    /// Creates a CPU driving bus, NES variant by default (see set_variant).
+    pub fn new(bus: B) -> Self {
         Self {
             register_a: 0,
             register_x: 0,
@@ -92,9 +160,24 @@ impl Cpu {
             stack_pointer: STACK_RESET,
             status: CpuFlags::from_bits_truncate(0b100100),
             program_counter: 0,
-            memory: [0; 0xFFFF],
-            remaining_cycles: 0,
-            bus: None,
+            opcode: 0,
+            mode: AddressingMode::NoneAddressing,
+            access: Access::Implied(Self::nop),
+            cycle: 0,
+            address: 0,
+            pointer: 0,
+            data: 0,
+            page_crossed: false,
+            interrupt: interrupt::BRK,
+            pending_interrupt: None,
+            stall_cycles: 0,
+            cycles: 0,
+            irq_inhibit: true,
+            run_mode: RunMode::Normal,
+            variant: Variant::Ricoh2A03,
+            bus,
+            #[cfg(test)]
+            bus_log: None,
         }
     }
 
@@ -122,58 +205,83 @@ impl Cpu {
         self.stack_pointer
     }
 
+    /// Cycles since power on.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
     pub fn set_program_counter(&mut self, addr: u16) {
         self.program_counter = addr;
     }
 
-    /// Connects CPU to bus.
-    pub fn connect_bus(&mut self, bus: &Rc<RefCell<CpuBus>>) {
-        self.bus = Some(Rc::clone(bus));
+    pub fn set_run_mode(&mut self, run_mode: RunMode) {
+        self.run_mode = run_mode;
+    }
+
+    pub fn run_mode(&self) -> RunMode {
+        self.run_mode
+    }
+
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    pub fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut B {
+        &mut self.bus
     }
 
     /// Gets absolute address according to address + addressing mode.
     /// Returns absolute address and bool to detect page crossing
-    pub fn get_absolute_address(&mut self, mode: &AddressingMode, addr: u16) -> (u16, bool) {
+    /// Memory is peeked: no side effect on the bus.
+    pub fn get_absolute_address(&self, mode: &AddressingMode, addr: u16) -> (u16, bool) {
         match mode {
-            AddressingMode::ZeroPage => (self.mem_read(addr) as u16, false),
+            AddressingMode::ZeroPage => (self.mem_peek(addr) as u16, false),
 
-            AddressingMode::Absolute => (self.mem_read_u16(addr), false),
+            AddressingMode::Absolute => (self.mem_peek_u16(addr), false),
 
             AddressingMode::ZeroPage_X => {
-                let pos = self.mem_read(addr);
+                let pos = self.mem_peek(addr);
                 let addr = pos.wrapping_add(self.register_x) as u16;
                 (addr, false)
             }
             AddressingMode::ZeroPage_Y => {
-                let pos = self.mem_read(addr);
+                let pos = self.mem_peek(addr);
                 let addr = pos.wrapping_add(self.register_y) as u16;
                 (addr, false)
             }
 
             AddressingMode::Absolute_X => {
-                let base = self.mem_read_u16(addr);
+                let base = self.mem_peek_u16(addr);
                 let addr = base.wrapping_add(self.register_x as u16);
                 (addr, page_cross(base, addr))
             }
             AddressingMode::Absolute_Y => {
-                let base = self.mem_read_u16(addr);
+                let base = self.mem_peek_u16(addr);
                 let addr = base.wrapping_add(self.register_y as u16);
                 (addr, page_cross(base, addr))
             }
 
             AddressingMode::Indirect_X => {
-                let base = self.mem_read(addr);
+                let base = self.mem_peek(addr);
 
                 let ptr: u8 = (base as u8).wrapping_add(self.register_x);
-                let lo = self.mem_read(ptr as u16);
-                let hi = self.mem_read(ptr.wrapping_add(1) as u16);
+                let lo = self.mem_peek(ptr as u16);
+                let hi = self.mem_peek(ptr.wrapping_add(1) as u16);
                 ((hi as u16) << 8 | (lo as u16), false)
             }
             AddressingMode::Indirect_Y => {
-                let base = self.mem_read(addr);
+                let base = self.mem_peek(addr);
 
-                let lo = self.mem_read(base as u16);
-                let hi = self.mem_read((base as u8).wrapping_add(1) as u16);
+                let lo = self.mem_peek(base as u16);
+                let hi = self.mem_peek(base.wrapping_add(1) as u16);
                 let deref_base = (hi as u16) << 8 | (lo as u16);
                 let deref = deref_base.wrapping_add(self.register_y as u16);
                 (deref, page_cross(deref, deref_base))
@@ -185,15 +293,6 @@ impl Cpu {
         }
     }
 
-    /// Gets address according to addressing mode.
-    /// Returns address and bool to detect page crossing
-    fn get_operand_address(&mut self, mode: &AddressingMode) -> (u16, bool) {
-        match mode {
-            AddressingMode::Immediate => (self.program_counter, false),
-            _ => self.get_absolute_address(mode, self.program_counter),
-        }
-    }
-
     /// Updates zero and neg flags in status.
     fn update_zero_and_negative_flags(&mut self, result: u8) {
         if result == 0 {
@@ -209,10 +308,8 @@ impl Cpu {
         }
     }
 
-    /// Compares address value with other value.
-    fn compare(&mut self, mode: &AddressingMode, compare_with: u8) {
-        let (addr, page_cross) = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
+    /// Compares value with other value.
+    fn compare(&mut self, data: u8, compare_with: u8) {
         if data <= compare_with {
             self.status.insert(CpuFlags::CARRY);
         } else {
@@ -220,38 +317,34 @@ impl Cpu {
         }
 
         self.update_zero_and_negative_flags(compare_with.wrapping_sub(data));
-
-        if page_cross {
-            self.remaining_cycles += 1;
-        }
     }
 
-    /// Handles branching.
-    fn branch(&mut self, condition: bool) {
-        if condition {
-            self.remaining_cycles += 1;
-
-            let jump: i8 = self.mem_read(self.program_counter) as i8;
-            let jump_addr = self
-                .program_counter
-                .wrapping_add(1)
-                .wrapping_add(jump as u16);
-
-            if self.program_counter.wrapping_add(1) & 0xFF00 != jump_addr & 0xFF00 {
-                self.remaining_cycles += 1;
-            }
-
-            self.program_counter = jump_addr;
-        }
+    /// Indicates if ADC and SBC use BCD arithmetic.
+    fn decimal_mode(&self) -> bool {
+        self.variant == Variant::Mos6502 && self.status.contains(CpuFlags::DECIMAL_MODE)
     }
 
     /// Substracts from register a.
     fn sub_from_register_a(&mut self, data: u8) {
-        self.add_to_register_a(((data as i8).wrapping_neg().wrapping_sub(1)) as u8);
+        let register_a = self.register_a;
+        let carry = self.status.contains(CpuFlags::CARRY);
+        self.binary_add_to_register_a(((data as i8).wrapping_neg().wrapping_sub(1)) as u8);
+        if self.decimal_mode() {
+            self.register_a = decimal_sub(register_a, data, carry);
+        }
     }
 
-    /// http://www.righto.com/2012/12/the-6502-overflow-flag-explained.html
+    /// Adds to register a.
     fn add_to_register_a(&mut self, data: u8) {
+        if self.decimal_mode() {
+            self.decimal_add_to_register_a(data);
+        } else {
+            self.binary_add_to_register_a(data);
+        }
+    }
+
+    /// http://www.righto.com/2012/12/the-6502-overflow-flag-explained.html
+    fn binary_add_to_register_a(&mut self, data: u8) {
         let sum = self.register_a as u16
             + data as u16
             + (if self.status.contains(CpuFlags::CARRY) {
@@ -280,6 +373,32 @@ impl Cpu {
         self.update_zero_and_negative_flags(self.register_a);
     }
 
+    /// Adds to register a in BCD.
+    /// As on NMOS 6502, Z flag is set from binary sum, N and V flags before high digit adjustment.
+    /// Source: http://www.6502.org/tutorials/decimal_mode.html#A
+    fn decimal_add_to_register_a(&mut self, data: u8) {
+        let carry = self.status.contains(CpuFlags::CARRY) as u16;
+        let (a, b) = (self.register_a as u16, data as u16);
+
+        let mut low = (a & 0x0f) + (b & 0x0f) + carry;
+        if low >= 0x0a {
+            low = ((low + 0x06) & 0x0f) + 0x10;
+        }
+        let mut sum = (a & 0xf0) + (b & 0xf0) + low;
+        let signed_sum = (a & 0xf0) as u8 as i8 as i16 + (b & 0xf0) as u8 as i8 as i16 + low as i16;
+
+        self.status.set(CpuFlags::ZERO, (a + b + carry) as u8 == 0);
+        self.status.set(CpuFlags::NEGATIV, sum & 0x80 != 0);
+        self.status
+            .set(CpuFlags::OVERFLOW, !(-128..=127).contains(&signed_sum));
+
+        if sum >= 0xa0 {
+            sum += 0x60;
+        }
+        self.status.set(CpuFlags::CARRY, sum >= 0x100);
+        self.register_a = sum as u8;
+    }
+
     /// Pops stack (u8).
     fn stack_pop(&mut self) -> u8 {
         self.stack_pointer = self.stack_pointer.wrapping_add(1);
@@ -292,22 +411,6 @@ impl Cpu {
         self.stack_pointer = self.stack_pointer.wrapping_sub(1)
     }
 
-    /// Pushed to stack (u16).
-    fn stack_push_u16(&mut self, data: u16) {
-        let hi = (data >> 8) as u8;
-        let lo = (data & 0xff) as u8;
-        self.stack_push(hi);
-        self.stack_push(lo);
-    }
-
-    /// Pops stack (u16).
-    fn stack_pop_u16(&mut self) -> u16 {
-        let lo = self.stack_pop() as u16;
-        let hi = self.stack_pop() as u16;
-
-        hi << 8 | lo
-    }
-
     /// Loads and runs program.
     pub fn load_and_run(&mut self, program: Vec<u8>) -> Result<(), CpuError> {
         self.load(program);
@@ -315,376 +418,93 @@ impl Cpu {
         self.run()
     }
 
-    /// Loads program into memory.
+    /// Loads program into memory at $8600, and sets reset vector to it.
     pub fn load(&mut self, program: Vec<u8>) {
-        self.memory[0x8600..(0x8600 + program.len())].copy_from_slice(&program[..]);
+        for (addr, data) in (0x8600..).zip(program) {
+            self.mem_write(addr, data);
+        }
         self.mem_write_u16(0xFFFC, 0x8600);
     }
 
     /// Resets CPU.
+    /// Next ticks run the 7-cycle reset sequence, then the program at reset vector.
     pub fn reset(&mut self) {
         self.register_a = 0;
         self.register_x = 0;
         self.register_y = 0;
-        self.stack_pointer = STACK_RESET;
+        // Reset sequence moves stack pointer down by 3
+        self.stack_pointer = STACK_RESET.wrapping_add(3);
         self.status = CpuFlags::from_bits_truncate(0b100100);
 
-        self.program_counter = self.mem_read_u16(0xFFFC);
-        self.remaining_cycles = 0;
+        self.cycle = 0;
+        self.pending_interrupt = Some(interrupt::RESET);
+        self.stall_cycles = 0;
+        self.irq_inhibit = true;
     }
 
-    fn interrupt(&mut self, interrupt: interrupt::Interrupt) {
-        self.stack_push_u16(self.program_counter);
-        let mut flag = self.status.clone();
-        flag.set(CpuFlags::BREAK, interrupt.b_flag_mask & 0b010000 == 1);
-        flag.set(CpuFlags::BREAK2, interrupt.b_flag_mask & 0b100000 == 1);
-
-        self.stack_push(flag.bits);
-        self.status.insert(CpuFlags::INTERRUPT_DISABLE);
-
-        self.remaining_cycles += interrupt.cpu_cycles;
-
-        self.program_counter = self.mem_read_u16(interrupt.vector_addr);
+    /// Polls NMI line (NMI is edge triggered: polling acknowledges it).
+    fn poll_nmi(&mut self) -> bool {
+        self.bus.poll_nmi_status()
     }
 
-    /// Processes next cycle.
-    /// Returns false if BRK is called.
-    pub fn tick(&mut self) -> Result<bool, CpuError> {
-        if self.remaining_cycles > 0 {
-            self.remaining_cycles -= 1;
-        } else {
-            // Handle NMI interrupt
-            if let Some(bus) = &self.bus {
-                if bus.borrow_mut().poll_nmi_status() {
-                    self.interrupt(interrupt::NMI);
-                }
-            }
-
-            let ref opcodes: HashMap<u8, &'static opcode::OpCode> = *opcode::OPCODES_MAP;
-            let code = self.mem_read(self.program_counter);
-
-            let opcode = match opcodes.get(&code) {
-                Some(c) => c,
-                None => return Err(CpuError::UnknownOpCode(code)),
-            };
-
-            //println!("{:#06x} - {}", self.program_counter, opcode.mnemonic);
-
-            self.remaining_cycles = opcode.cycles;
-
-            self.program_counter += 1;
-            let program_counter_state = self.program_counter;
-
-            let mut brk = false;
-
-            match code {
-                /* LDA */
-                0xa9 | 0xa5 | 0xb5 | 0xad | 0xbd | 0xb9 | 0xa1 | 0xb1 => self.lda(&opcode.mode),
-
-                /* TAX */
-                0xAA => self.tax(),
-
-                /* INX */
-                0xe8 => self.inx(),
-
-                /* BRK */
-                0x00 => return Ok(false),
-                // Belows results in "attempt to write to cartridge PRG ROM space"
-                /*0x00 => {
-                    self.program_counter += 1;
-                    if !self.status.contains(CPUFlags::INTERRUPT_DISABLE) {
-                        self.interrupt(interrupt::BRK);
-                    }
-                }*/
-
-                /* CLD */
-                0xd8 => self.cld(),
-
-                /* CLI */
-                0x58 => self.cli(),
-
-                /* CLV */
-                0xb8 => self.clv(),
-
-                /* CLC */
-                0x18 => self.clc(),
-
-                /* SEC */
-                0x38 => self.sec(),
-
-                /* SEI */
-                0x78 => self.sei(),
+    /// Polls interrupt lines at the end of an instruction, and schedules interrupt sequence if needed.
+    /// NMI has priority over IRQ, IRQ is ignored while interrupts are disabled.
+    fn poll_interrupts(&mut self) {
+        let (nmi, irq) = (self.bus.poll_nmi_status(), self.bus.poll_irq_status());
 
-                /* SED */
-                0xf8 => self.sed(),
-
-                /* PHA */
-                0x48 => self.pha(),
-
-                /* PLA */
-                0x68 => self.pla(),
-
-                /* PHP */
-                0x08 => self.php(),
-
-                /* PHP */
-                0x28 => self.plp(),
-
-                /* ADC */
-                0x69 | 0x65 | 0x75 | 0x6d | 0x7d | 0x79 | 0x61 | 0x71 => self.adc(&opcode.mode),
-
-                /* SBC */
-                0xe9 | 0xe5 | 0xf5 | 0xed | 0xfd | 0xf9 | 0xe1 | 0xf1 => self.sbc(&opcode.mode),
-
-                /* AND */
-                0x29 | 0x25 | 0x35 | 0x2d | 0x3d | 0x39 | 0x21 | 0x31 => self.and(&opcode.mode),
-
-                /* EOR */
-                0x49 | 0x45 | 0x55 | 0x4d | 0x5d | 0x59 | 0x41 | 0x51 => self.eor(&opcode.mode),
-
-                /* ORA */
-                0x09 | 0x05 | 0x15 | 0x0d | 0x1d | 0x19 | 0x01 | 0x11 => self.ora(&opcode.mode),
-
-                /* LSR */
-                0x4a => self.lsr_accumulator(),
-
-                /* LSR */
-                0x46 | 0x56 | 0x4e | 0x5e => {
-                    self.lsr(&opcode.mode);
-                }
-
-                /* ASL */
-                0x0a => self.asl_accumulator(),
-
-                /* ASL */
-                0x06 | 0x16 | 0x0e | 0x1e => {
-                    self.asl(&opcode.mode);
-                }
-
-                /*ROL*/ 0x2a => self.rol_accumulator(),
-
-                /* ROL */
-                0x26 | 0x36 | 0x2e | 0x3e => {
-                    self.rol(&opcode.mode);
-                }
-
-                /* ROR */ 0x6a => self.ror_accumulator(),
-
-                /* ROR */
-                0x66 | 0x76 | 0x6e | 0x7e => {
-                    self.ror(&opcode.mode);
-                }
-
-                /* INC */
-                0xe6 | 0xf6 | 0xee | 0xfe => {
-                    self.inc(&opcode.mode);
-                }
-
-                /* INY */
-                0xc8 => self.iny(),
-
-                /* DEC */
-                0xc6 | 0xd6 | 0xce | 0xde => {
-                    self.dec(&opcode.mode);
-                }
-
-                /* DEX */
-                0xca => self.dex(),
-
-                /* DEY */
-                0x88 => self.dey(),
-
-                /* CMP */
-                0xc9 | 0xc5 | 0xd5 | 0xcd | 0xdd | 0xd9 | 0xc1 | 0xd1 => self.cmp(&opcode.mode),
-
-                /* CPY */
-                0xc0 | 0xc4 | 0xcc => self.cpy(&opcode.mode),
-
-                /* CPX */
-                0xe0 | 0xe4 | 0xec => self.cpx(&opcode.mode),
-
-                /* JMP Absolute */
-                0x4c => self.jmp_absolute(),
-
-                /* JMP Indirect */
-                0x6c => self.jmp_indirect(),
-
-                /* JSR */
-                0x20 => self.jsr(),
-
-                /* RTS */
-                0x60 => self.rts(),
-
-                /* RTI */
-                0x40 => self.rti(),
-
-                /* BNE */
-                0xd0 => self.bne(),
-
-                /* BVS */
-                0x70 => self.bvs(),
-
-                /* BVC */
-                0x50 => self.bvc(),
-
-                /* BPL */
-                0x10 => self.bpl(),
-
-                /* BMI */
-                0x30 => self.bmi(),
-
-                /* BEQ */
-                0xf0 => self.beq(),
-
-                /* BCS */
-                0xb0 => self.bcs(),
-
-                /* BCC */
-                0x90 => self.bcc(),
-
-                /* BIT */
-                0x24 | 0x2c => self.bit(&opcode.mode),
-
-                /* STA */
-                0x85 | 0x95 | 0x8d | 0x9d | 0x99 | 0x81 | 0x91 => self.sta(&opcode.mode),
-
-                /* STX */
-                0x86 | 0x96 | 0x8e => self.stx(&opcode.mode),
-
-                /* STY */
-                0x84 | 0x94 | 0x8c => self.sty(&opcode.mode),
-
-                /* LDX */
-                0xa2 | 0xa6 | 0xb6 | 0xae | 0xbe => self.ldx(&opcode.mode),
-
-                /* LDY */
-                0xa0 | 0xa4 | 0xb4 | 0xac | 0xbc => self.ldy(&opcode.mode),
-
-                /* NOP */
-                0xea => {
-                    // Does nothing
-                }
-
-                /* TAY */
-                0xa8 => self.tay(),
-
-                /* TSX */
-                0xba => self.tsx(),
-
-                /* TXA */
-                0x8a => self.txa(),
-
-                /* TXS */
-                0x9a => self.txs(),
-
-                /* TYA */
-                0x98 => self.tya(),
-
-                /* *LAX */
-                0xa7 | 0xb7 | 0xaf | 0xbf | 0xa3 | 0xb3 => self.lax(&opcode.mode),
-
-                /* *SAX */
-                0x87 | 0x97 | 0x8f | 0x83 => self.sax(&opcode.mode),
-
-                /* *NOP */
-                0x02 | 0x12 | 0x22 | 0x32 | 0x42 | 0x52 | 0x62 | 0x72 | 0x92 | 0xb2 | 0xd2
-                | 0xf2 => { /* Nothing */ }
-
-                /* *NOP */
-                0x1a | 0x3a | 0x5a | 0x7a | 0xda | 0xfa => { /* Nothing */ }
-
-                /* *NOP read */
-                0x04 | 0x44 | 0x64 | 0x14 | 0x34 | 0x54 | 0x74 | 0xd4 | 0xf4 | 0x0c | 0x1c
-                | 0x3c | 0x5c | 0x7c | 0xdc | 0xfc => {
-                    let (addr, page_cross) = self.get_operand_address(&opcode.mode);
-                    let data = self.mem_read(addr);
-                    if page_cross {
-                        self.remaining_cycles += 1;
-                    }
-                    /* Nothing */
-                }
-
-                /* *SBC */
-                0xeb => self.unofficial_sbc(&opcode.mode),
-
-                /* *DCP */
-                0xc7 | 0xd7 | 0xCF | 0xdF | 0xdb | 0xd3 | 0xc3 => self.dcp(&opcode.mode),
-
-                /* *ISB */
-                0xe7 | 0xf7 | 0xef | 0xff | 0xfb | 0xe3 | 0xf3 => self.isb(&opcode.mode),
-
-                /* *SLO */
-                0x07 | 0x17 | 0x0F | 0x1f | 0x1b | 0x03 | 0x13 => self.slo(&opcode.mode),
-
-                /* *RLA */
-                0x27 | 0x37 | 0x2F | 0x3F | 0x3b | 0x33 | 0x23 => self.rla(&opcode.mode),
-
-                /* *SRE */
-                0x47 | 0x57 | 0x4F | 0x5f | 0x5b | 0x43 | 0x53 => self.sre(&opcode.mode),
-
-                /* *RRA */
-                0x67 | 0x77 | 0x6f | 0x7f | 0x7b | 0x63 | 0x73 => self.rra(&opcode.mode),
-
-                /* *SKB */
-                0x80 | 0x82 | 0x89 | 0xc2 | 0xe2 => {
-                    /* 2 byte NOP (immediate ) */
-                    // TODO might be worth doing the read (like NOP) ?
-                }
-
-                /* *AXS */
-                0xcb => self.axs(&opcode.mode),
-
-                /* *ARR */
-                0x6b => self.arr(&opcode.mode),
-
-                /* *ANC */
-                0x0b | 0x2b => self.anc(&opcode.mode),
-
-                /* *ALR */
-                0x4b => self.alr(&opcode.mode),
-
-                /* *LXA */
-                0xab => self.lxa(&opcode.mode),
-
-                /* *XAA */
-                0x8b => self.xaa(&opcode.mode),
-
-                /* *LAS */
-                0xbb => self.las(&opcode.mode),
-
-                /* *TAS */
-                0x9b => self.tas(),
+        if nmi {
+            self.pending_interrupt = Some(interrupt::NMI);
+        } else if irq && !self.irq_inhibit {
+            self.pending_interrupt = Some(interrupt::IRQ);
+        }
+    }
 
-                /* *AHX  Indirect Y */
-                0x93 => self.ahx_indirect_y(),
+    /// Indicates if the CPU is halted by a JAM opcode, until reset.
+    pub fn jammed(&self) -> bool {
+        self.cycle > 0 && matches!(self.access, Access::Jam)
+    }
 
-                /* *AHX  Absolute Y */
-                0x9f => self.ahx_absolute_y(),
+    /// Halts the CPU until reset, as a JAM opcode does.
+    pub fn jam(&mut self) {
+        self.access = Access::Jam;
+        self.cycle = 2;
+    }
 
-                /* *SHX */
-                0x9e => self.shx(),
+    /// Processes next cycle: each cycle performs one bus read or write.
+    /// Returns false if BRK is called in StopOnBrk run mode.
+    pub fn tick(&mut self) -> Result<bool, CpuError> {
+        // OAM DMA reads on even (get) cycles, and writes on odd (put) cycles
+        let get_cycle = self.cycles.is_multiple_of(2);
+        self.cycles += 1;
+
+        if self.bus.dma_cycle(get_cycle) {
+            // CPU is halted by OAM DMA
+        } else if self.stall_cycles > 0 {
+            self.stall_cycles -= 1;
+        } else if self.cycle > 0 {
+            self.step();
+        } else if let Some(interrupt) = self.pending_interrupt.take() {
+            self.start_interrupt(interrupt);
+        } else if !self.fetch_opcode() {
+            return Ok(false);
+        }
 
-                /* *SHY */
-                0x9c => self.shy(),
-            };
+        // DMC sample fetch stalls the CPU
+        self.stall_cycles += self.bus.poll_dmc_dma();
 
-            if program_counter_state == self.program_counter {
-                self.program_counter += (opcode.len - 1) as u16;
-            }
-        }
         Ok(true)
     }
 
-    /// Runs loaded program.
+    /// Runs loaded program, until tick returns false (see RunMode::StopOnBrk).
     pub fn run_with_callback<F>(&mut self, mut callback: F) -> Result<(), CpuError>
     where
-        F: FnMut(&mut Cpu),
+        F: FnMut(&mut Self),
     {
         let mut cont = true;
         while cont {
             // Callback is called only on instruction change
             // Not for every cycle
-            if self.remaining_cycles == 0 {
+            if self.instruction_changed() {
                 callback(self);
             }
             cont = self.tick()?;
@@ -696,8 +516,26 @@ impl Cpu {
         self.run_with_callback(|_| {})
     }
 
-    /// Indicates if CPU instruction changed since last tick.
+    /// Indicates if next tick starts a new instruction.
     pub fn instruction_changed(&self) -> bool {
-        self.remaining_cycles == 0
+        self.cycle == 0
+            && self.pending_interrupt.is_none()
+            && self.stall_cycles == 0
+            && !self.bus.dma_in_progress()
+    }
+}
+
+/// Substracts in BCD (flags are set as in binary mode).
+/// Source: http://www.6502.org/tutorials/decimal_mode.html#A
+fn decimal_sub(a: u8, b: u8, carry: bool) -> u8 {
+    let (a, b) = (a as i16, b as i16);
+    let mut low = (a & 0x0f) - (b & 0x0f) + carry as i16 - 1;
+    if low < 0 {
+        low = ((low - 0x06) & 0x0f) - 0x10;
+    }
+    let mut difference = (a & 0xf0) - (b & 0xf0) + low;
+    if difference < 0 {
+        difference -= 0x60;
     }
+    difference as u8
 }
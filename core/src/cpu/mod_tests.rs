@@ -1,10 +1,69 @@
-use crate::cpu::Cpu;
+use crate::{
+    bus::Bus,
+    cpu::{Cpu, CpuFlags, RunMode},
+    memory::{Memory, Ram},
+};
 
 #[test]
 fn test_5_ops_working_together() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
+    cpu.set_run_mode(RunMode::StopOnBrk);
     cpu.load_and_run(vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00])
         .unwrap();
 
     assert_eq!(cpu.register_x, 0xc1)
 }
+
+/// RAM with an IRQ line, as a minimal non-NES system.
+struct IrqRam {
+    ram: Ram,
+    irq: bool,
+}
+
+impl Memory for IrqRam {
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        self.ram.mem_read(addr)
+    }
+
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        self.ram.mem_write(addr, data)
+    }
+
+    fn mem_peek(&self, addr: u16) -> u8 {
+        self.ram.mem_peek(addr)
+    }
+}
+
+impl Bus for IrqRam {
+    fn poll_irq_status(&self) -> bool {
+        self.irq
+    }
+}
+
+#[test]
+fn test_custom_bus() {
+    let mut cpu = Cpu::new(IrqRam {
+        ram: Ram::new(),
+        irq: false,
+    });
+    cpu.mem_write_u16(0xfffe, 0x1234);
+    // CLI, then NOP slide
+    let mut code = vec![0x58];
+    code.extend([0xea; 0x20]);
+    cpu.load(code);
+    cpu.reset();
+    for _ in 0..20 {
+        cpu.tick().unwrap();
+    }
+    assert!((0x8601..0x8620).contains(&cpu.program_counter()));
+
+    cpu.bus_mut().irq = true;
+    // IRQ sequence takes 7 cycles, after current instruction
+    let mut cycles = 0;
+    while cpu.program_counter() != 0x1234 {
+        cpu.tick().unwrap();
+        cycles += 1;
+        assert!(cycles <= 9);
+    }
+    assert!(cpu.status().contains(CpuFlags::INTERRUPT_DISABLE));
+}
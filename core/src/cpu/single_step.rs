@@ -0,0 +1,491 @@
+//! Harness for SingleStepTests processor tests (https://github.com/SingleStepTests/65x02).
+//!
+//! Each test file holds cases for one opcode: initial CPU state and RAM, final state and RAM,
+//! and bus activity of each cycle. A case runs one instruction on a flat RAM bus.
+
+use std::{
+    fmt, fs,
+    iter::Peekable,
+    path::{Path, PathBuf},
+    str::Chars,
+};
+
+use crate::{
+    bus::Bus,
+    memory::{Memory, Ram},
+};
+
+use super::{memory::BusAccess, Cpu, CpuFlags, Variant};
+
+/// CPU registers and RAM content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuState {
+    pub pc: u16,
+    pub s: u8,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    /// Address and value of RAM bytes, others are 0.
+    pub ram: Vec<(u16, u8)>,
+}
+
+/// Single instruction test case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCase {
+    pub name: String,
+    pub initial: CpuState,
+    pub expected: CpuState,
+    /// Bus access of each cycle.
+    pub cycles: Vec<BusAccess>,
+}
+
+/// Difference between expected and actual results.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    Register {
+        name: &'static str,
+        expected: u16,
+        actual: u16,
+    },
+    Memory {
+        address: u16,
+        expected: u8,
+        actual: u8,
+    },
+    /// Bus access of a cycle (None if no access).
+    Cycle {
+        cycle: usize,
+        expected: Option<BusAccess>,
+        actual: Option<BusAccess>,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Register {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{}: expected ${:02X}, actual ${:02X}",
+                name, expected, actual
+            ),
+            Mismatch::Memory {
+                address,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "${:04X}: expected ${:02X}, actual ${:02X}",
+                address, expected, actual
+            ),
+            Mismatch::Cycle {
+                cycle,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "cycle {}: expected {}, actual {}",
+                cycle,
+                access_text(expected),
+                access_text(actual)
+            ),
+        }
+    }
+}
+
+fn access_text(access: &Option<BusAccess>) -> String {
+    match access {
+        Some(BusAccess::Read(address, value)) => format!("read ${:02X} at ${:04X}", value, address),
+        Some(BusAccess::Write(address, value)) => {
+            format!("write ${:02X} at ${:04X}", value, address)
+        }
+        None => String::from("none"),
+    }
+}
+
+/// Failed test case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub file: PathBuf,
+    pub name: String,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} [{}]", self.file.display(), self.name)?;
+        for mismatch in &self.mismatches {
+            writeln!(f, "    {}", mismatch)?;
+        }
+        Ok(())
+    }
+}
+
+/// Results of test files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub passed: usize,
+    pub failures: Vec<Failure>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for failure in &self.failures {
+            write!(f, "{}", failure)?;
+        }
+        writeln!(f, "{} passed, {} failed", self.passed, self.failures.len())
+    }
+}
+
+/// Flat RAM bus recording bus accesses.
+struct TestBus {
+    ram: Ram,
+    accesses: Vec<BusAccess>,
+}
+
+impl Memory for TestBus {
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        let data = self.ram.mem_read(addr);
+        self.accesses.push(BusAccess::Read(addr, data));
+        data
+    }
+
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        self.accesses.push(BusAccess::Write(addr, data));
+        self.ram.mem_write(addr, data);
+    }
+
+    fn mem_peek(&self, addr: u16) -> u8 {
+        self.ram.mem_peek(addr)
+    }
+}
+
+impl Bus for TestBus {}
+
+/// Runs test case: returns mismatches, empty if test passes.
+pub fn run_test(case: &TestCase, variant: Variant) -> Vec<Mismatch> {
+    let mut bus = TestBus {
+        ram: Ram::new(),
+        accesses: vec![],
+    };
+    for (address, value) in &case.initial.ram {
+        bus.ram.mem_write(*address, *value);
+    }
+    let mut cpu = Cpu::new(bus);
+    cpu.set_variant(variant);
+    cpu.program_counter = case.initial.pc;
+    cpu.stack_pointer = case.initial.s;
+    cpu.register_a = case.initial.a;
+    cpu.register_x = case.initial.x;
+    cpu.register_y = case.initial.y;
+    cpu.status = CpuFlags::from_bits_truncate(case.initial.p);
+    cpu.irq_inhibit = cpu.status.contains(CpuFlags::INTERRUPT_DISABLE);
+
+    // JAM never completes: it runs for the expected cycles
+    let max_cycles = case.cycles.len().max(1) + 8;
+    for cycle in 1..=max_cycles {
+        cpu.tick().unwrap();
+        if cpu.instruction_changed() || (cpu.jammed() && cycle >= case.cycles.len()) {
+            break;
+        }
+    }
+
+    let expected = &case.expected;
+    let mut mismatches: Vec<Mismatch> = [
+        ("PC", expected.pc, cpu.program_counter),
+        ("S", expected.s as u16, cpu.stack_pointer as u16),
+        ("A", expected.a as u16, cpu.register_a as u16),
+        ("X", expected.x as u16, cpu.register_x as u16),
+        ("Y", expected.y as u16, cpu.register_y as u16),
+        ("P", expected.p as u16, cpu.status.bits() as u16),
+    ]
+    .into_iter()
+    .filter(|(_, expected, actual)| expected != actual)
+    .map(|(name, expected, actual)| Mismatch::Register {
+        name,
+        expected,
+        actual,
+    })
+    .collect();
+
+    for (address, value) in &expected.ram {
+        let actual = cpu.bus.ram.mem_peek(*address);
+        if actual != *value {
+            mismatches.push(Mismatch::Memory {
+                address: *address,
+                expected: *value,
+                actual,
+            });
+        }
+    }
+
+    let accesses = &cpu.bus.accesses;
+    for cycle in 0..case.cycles.len().max(accesses.len()) {
+        let (expected, actual) = (case.cycles.get(cycle), accesses.get(cycle));
+        if expected != actual {
+            mismatches.push(Mismatch::Cycle {
+                cycle,
+                expected: expected.copied(),
+                actual: actual.copied(),
+            });
+        }
+    }
+    mismatches
+}
+
+/// Loads test cases from a JSON file.
+pub fn load_tests<P: AsRef<Path>>(path: P) -> Result<Vec<TestCase>, String> {
+    let json = fs::read_to_string(&path)
+        .map_err(|error| format!("{}: {}", path.as_ref().display(), error))?;
+    parse_tests(&json).map_err(|error| format!("{}: {}", path.as_ref().display(), error))
+}
+
+/// Runs test files of a directory (e.g. 65x02/6502/v1), in file name order.
+/// File names are filtered by filter, if any (e.g. "a9" for a9.json).
+pub fn run_directory<P: AsRef<Path>>(
+    dir: P,
+    variant: Variant,
+    filter: Option<&str>,
+) -> Result<Report, String> {
+    let mut files: Vec<PathBuf> = fs::read_dir(&dir)
+        .map_err(|error| format!("{}: {}", dir.as_ref().display(), error))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .filter(|path| match filter {
+            Some(filter) => path
+                .file_stem()
+                .is_some_and(|stem| stem.to_string_lossy().contains(filter)),
+            None => true,
+        })
+        .collect();
+    files.sort();
+
+    let mut report = Report::default();
+    for file in files {
+        for case in load_tests(&file)? {
+            let mismatches = run_test(&case, variant);
+            if mismatches.is_empty() {
+                report.passed += 1;
+            } else {
+                report.failures.push(Failure {
+                    file: file.clone(),
+                    name: case.name,
+                    mismatches,
+                });
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Parses test cases: a JSON array of objects with name, initial, final and cycles fields.
+/// {"name": "a9 62 c5", "initial": {"pc": 2117, "s": 82, "a": 203, "x": 102, "y": 119, "p": 234,
+/// "ram": [[2117, 169], [2118, 98]]}, "final": {...}, "cycles": [[2117, 169, "read"], [2118, 98, "read"]]}
+pub fn parse_tests(json: &str) -> Result<Vec<TestCase>, String> {
+    let mut chars = json.chars().peekable();
+    let value = parse_value(&mut chars)?;
+    skip_whitespace(&mut chars);
+    if chars.next().is_some() {
+        return Err(String::from("trailing characters"));
+    }
+    value.array()?.iter().map(test_case).collect()
+}
+
+fn test_case(value: &Json) -> Result<TestCase, String> {
+    let name = value.field("name")?.string()?.to_string();
+    let context = |error: String| format!("{}: {}", name, error);
+    let cycles = value
+        .field("cycles")
+        .and_then(Json::array)
+        .and_then(|cycles| {
+            cycles
+                .iter()
+                .map(|cycle| {
+                    let cycle = cycle.array()?;
+                    let (address, value) = (number(cycle.first())?, number(cycle.get(1))?);
+                    match cycle.get(2).map(Json::string) {
+                        Some(Ok("read")) => Ok(BusAccess::Read(address, value as u8)),
+                        Some(Ok("write")) => Ok(BusAccess::Write(address, value as u8)),
+                        _ => Err(String::from("invalid cycle")),
+                    }
+                })
+                .collect()
+        })
+        .map_err(context)?;
+    Ok(TestCase {
+        initial: value
+            .field("initial")
+            .and_then(cpu_state)
+            .map_err(context)?,
+        expected: value.field("final").and_then(cpu_state).map_err(context)?,
+        cycles,
+        name,
+    })
+}
+
+fn cpu_state(value: &Json) -> Result<CpuState, String> {
+    let register = |name| number(value.field(name).ok());
+    let ram = value
+        .field("ram")?
+        .array()?
+        .iter()
+        .map(|byte| {
+            let byte = byte.array()?;
+            Ok((number(byte.first())?, number(byte.get(1))? as u8))
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(CpuState {
+        pc: register("pc")?,
+        s: register("s")? as u8,
+        a: register("a")? as u8,
+        x: register("x")? as u8,
+        y: register("y")? as u8,
+        p: register("p")? as u8,
+        ram,
+    })
+}
+
+fn number(value: Option<&Json>) -> Result<u16, String> {
+    match value {
+        Some(Json::Number(number)) if (0..=0xffff).contains(number) => Ok(*number as u16),
+        _ => Err(String::from("invalid number")),
+    }
+}
+
+/// JSON value (numbers are integers).
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn field(&self, name: &str) -> Result<&Json, String> {
+        match self {
+            Json::Object(fields) => fields
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value)
+                .ok_or_else(|| format!("missing field '{}'", name)),
+            _ => Err(format!("object expected for field '{}'", name)),
+        }
+    }
+
+    fn array(&self) -> Result<&Vec<Json>, String> {
+        match self {
+            Json::Array(values) => Ok(values),
+            _ => Err(String::from("array expected")),
+        }
+    }
+
+    fn string(&self) -> Result<&str, String> {
+        match self {
+            Json::String(string) => Ok(string),
+            _ => Err(String::from("string expected")),
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Json, String> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some('[') => {
+            chars.next();
+            let mut values = vec![];
+            skip_whitespace(chars);
+            if chars.next_if_eq(&']').is_none() {
+                loop {
+                    values.push(parse_value(chars)?);
+                    skip_whitespace(chars);
+                    match chars.next() {
+                        Some(',') => {}
+                        Some(']') => break,
+                        _ => return Err(String::from("',' or ']' expected")),
+                    }
+                }
+            }
+            Ok(Json::Array(values))
+        }
+        Some('{') => {
+            chars.next();
+            let mut fields = vec![];
+            skip_whitespace(chars);
+            if chars.next_if_eq(&'}').is_none() {
+                loop {
+                    let key = match parse_value(chars)? {
+                        Json::String(key) => key,
+                        _ => return Err(String::from("field name expected")),
+                    };
+                    skip_whitespace(chars);
+                    if chars.next() != Some(':') {
+                        return Err(String::from("':' expected"));
+                    }
+                    fields.push((key, parse_value(chars)?));
+                    skip_whitespace(chars);
+                    match chars.next() {
+                        Some(',') => {}
+                        Some('}') => break,
+                        _ => return Err(String::from("',' or '}' expected")),
+                    }
+                }
+            }
+            Ok(Json::Object(fields))
+        }
+        Some('"') => {
+            chars.next();
+            let mut string = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => string.push('\n'),
+                        Some('t') => string.push('\t'),
+                        Some(c @ ('"' | '\\' | '/')) => string.push(c),
+                        _ => return Err(String::from("unsupported string escape")),
+                    },
+                    Some(c) => string.push(c),
+                    None => return Err(String::from("unterminated string")),
+                }
+            }
+            Ok(Json::String(string))
+        }
+        Some(c) if *c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(c) = chars.next_if(|c| *c == '-' || c.is_ascii_digit()) {
+                number.push(c);
+            }
+            number
+                .parse()
+                .map(Json::Number)
+                .map_err(|_| format!("invalid number '{}'", number))
+        }
+        Some(_) => {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphabetic()) {
+                word.push(c);
+            }
+            match word.as_str() {
+                "true" => Ok(Json::Bool(true)),
+                "false" => Ok(Json::Bool(false)),
+                "null" => Ok(Json::Null),
+                _ => Err(format!("unexpected '{}'", word)),
+            }
+        }
+        None => Err(String::from("unexpected end of file")),
+    }
+}
@@ -0,0 +1,174 @@
+use std::{env, fs};
+
+use crate::cpu::{
+    memory::BusAccess::{Read, Write},
+    single_step::{parse_tests, run_directory, run_test, CpuState, Mismatch},
+    Variant,
+};
+
+/// LDA #$62
+const LDA_IMMEDIATE: &str = r#"[
+    {
+        "name": "a9 62 c5",
+        "initial": {"pc": 2117, "s": 82, "a": 203, "x": 102, "y": 119, "p": 234,
+            "ram": [[2117, 169], [2118, 98], [2119, 197]]},
+        "final": {"pc": 2119, "s": 82, "a": 98, "x": 102, "y": 119, "p": 104,
+            "ram": [[2117, 169], [2118, 98], [2119, 197]]},
+        "cycles": [[2117, 169, "read"], [2118, 98, "read"]]
+    }
+]"#;
+
+/// STA $10
+const STA_ZERO_PAGE: &str = r#"[
+    {
+        "name": "85 10 00",
+        "initial": {"pc": 512, "s": 253, "a": 66, "x": 0, "y": 0, "p": 36,
+            "ram": [[512, 133], [513, 16], [16, 7]]},
+        "final": {"pc": 514, "s": 253, "a": 66, "x": 0, "y": 0, "p": 36,
+            "ram": [[512, 133], [513, 16], [16, 66]]},
+        "cycles": [[512, 133, "read"], [513, 16, "read"], [16, 66, "write"]]
+    }
+]"#;
+
+/// ADC #$01 in decimal mode: $09 + $01 = $10
+const ADC_DECIMAL: &str = r#"[
+    {
+        "name": "69 01 00",
+        "initial": {"pc": 768, "s": 253, "a": 9, "x": 0, "y": 0, "p": 40,
+            "ram": [[768, 105], [769, 1]]},
+        "final": {"pc": 770, "s": 253, "a": 16, "x": 0, "y": 0, "p": 40,
+            "ram": [[768, 105], [769, 1]]},
+        "cycles": [[768, 105, "read"], [769, 1, "read"]]
+    }
+]"#;
+
+#[test]
+fn test_parse_tests() {
+    let cases = parse_tests(LDA_IMMEDIATE).unwrap();
+    assert_eq!(cases.len(), 1);
+    assert_eq!(cases[0].name, "a9 62 c5");
+    assert_eq!(
+        cases[0].initial,
+        CpuState {
+            pc: 0x0845,
+            s: 0x52,
+            a: 0xcb,
+            x: 0x66,
+            y: 0x77,
+            p: 0xea,
+            ram: vec![(0x0845, 0xa9), (0x0846, 0x62), (0x0847, 0xc5)],
+        }
+    );
+    assert_eq!(cases[0].expected.a, 0x62);
+    assert_eq!(cases[0].cycles, [Read(0x0845, 0xa9), Read(0x0846, 0x62)]);
+
+    assert_eq!(parse_tests("[]").unwrap(), []);
+    assert_eq!(
+        parse_tests(r#"[{"name": "x"}]"#).unwrap_err(),
+        "x: missing field 'cycles'"
+    );
+    assert_eq!(parse_tests("[1, 2").unwrap_err(), "',' or ']' expected");
+}
+
+#[test]
+fn test_run_test() {
+    for json in [LDA_IMMEDIATE, STA_ZERO_PAGE] {
+        let case = &parse_tests(json).unwrap()[0];
+        assert_eq!(run_test(case, Variant::Mos6502), [], "{}", case.name);
+    }
+}
+
+#[test]
+fn test_decimal_mode() {
+    let case = &parse_tests(ADC_DECIMAL).unwrap()[0];
+    assert_eq!(run_test(case, Variant::Mos6502), []);
+    // NES CPU ignores decimal mode
+    assert_eq!(
+        run_test(case, Variant::Ricoh2A03),
+        [Mismatch::Register {
+            name: "A",
+            expected: 0x10,
+            actual: 0x0a,
+        }]
+    );
+}
+
+#[test]
+fn test_mismatches() {
+    let mut case = parse_tests(STA_ZERO_PAGE).unwrap().remove(0);
+    case.expected.x = 1;
+    case.expected.ram[2] = (0x10, 0x43);
+    case.cycles[2] = Write(0x10, 0x43);
+    case.cycles.push(Read(0x0202, 0xea));
+
+    let mismatches = run_test(&case, Variant::Mos6502);
+    assert_eq!(
+        mismatches,
+        [
+            Mismatch::Register {
+                name: "X",
+                expected: 1,
+                actual: 0,
+            },
+            Mismatch::Memory {
+                address: 0x10,
+                expected: 0x43,
+                actual: 0x42,
+            },
+            Mismatch::Cycle {
+                cycle: 2,
+                expected: Some(Write(0x10, 0x43)),
+                actual: Some(Write(0x10, 0x42)),
+            },
+            Mismatch::Cycle {
+                cycle: 3,
+                expected: Some(Read(0x0202, 0xea)),
+                actual: None,
+            },
+        ]
+    );
+    let texts: Vec<String> = mismatches.iter().map(|m| m.to_string()).collect();
+    assert_eq!(
+        texts,
+        [
+            "X: expected $01, actual $00",
+            "$0010: expected $43, actual $42",
+            "cycle 2: expected write $43 at $0010, actual write $42 at $0010",
+            "cycle 3: expected read $EA at $0202, actual none",
+        ]
+    );
+}
+
+#[test]
+fn test_run_directory() {
+    let dir = env::temp_dir().join(format!("emultendo-single-step-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a9.json"), LDA_IMMEDIATE).unwrap();
+    fs::write(dir.join("69.json"), ADC_DECIMAL).unwrap();
+    fs::write(dir.join("notes.txt"), "not a test").unwrap();
+
+    let report = run_directory(&dir, Variant::Mos6502, None).unwrap();
+    assert_eq!((report.passed, report.failures.len()), (2, 0));
+    assert_eq!(report.to_string(), "2 passed, 0 failed\n");
+
+    let report = run_directory(&dir, Variant::Ricoh2A03, Some("69")).unwrap();
+    assert_eq!((report.passed, report.failures.len()), (0, 1));
+    assert_eq!(report.failures[0].name, "69 01 00");
+    assert!(report
+        .to_string()
+        .ends_with("69.json [69 01 00]\n    A: expected $10, actual $0A\n0 passed, 1 failed\n"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Runs SingleStepTests 6502 tests, from SINGLE_STEP_TESTS directory (e.g. 65x02/6502/v1).
+/// Skipped if not set.
+#[test]
+fn test_single_step_tests() {
+    let Ok(dir) = env::var("SINGLE_STEP_TESTS") else {
+        return;
+    };
+    let filter = env::var("SINGLE_STEP_FILTER").ok();
+    let report = run_directory(dir, Variant::Mos6502, filter.as_deref()).unwrap();
+    assert!(report.failures.is_empty(), "{}", report);
+}
@@ -1,140 +1,172 @@
-use std::collections::HashMap;
+use crate::{bus::Bus, memory::Memory};
+
+use super::{
+    cycle::Access,
+    instruction::AddressingMode,
+    trace_format::{NestestFormatter, TraceFormatter},
+    Cpu, CpuFlags,
+};
+
+/// Kind of memory access performed by an instruction on its operand address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryAccess {
+    Read,
+    Write,
+    ReadModifyWrite,
+}
 
-use crate::memory::Memory;
+/// Memory effect of an instruction: operand address, and value at it before execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryEffect {
+    pub address: u16,
+    pub value: u8,
+    pub access: MemoryAccess,
+}
 
-use super::{instruction::AddressingMode, Cpu, opcode};
+/// CPU state before an instruction, with the instruction itself.
+/// Captured without allocation, formatted by a TraceFormatter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub program_counter: u16,
+    /// Memory bank mapped at program counter, if banked.
+    pub bank: Option<u16>,
+    /// Instruction bytes (opcode and operand), first len bytes are valid.
+    pub bytes: [u8; 3],
+    pub len: u8,
+    /// Mnemonic, unofficial opcodes are prefixed with '*'.
+    pub mnemonic: &'static str,
+    pub mode: AddressingMode,
+    pub register_a: u8,
+    pub register_x: u8,
+    pub register_y: u8,
+    pub status: CpuFlags,
+    pub stack_pointer: u8,
+    /// Total CPU cycles.
+    pub cycles: u64,
+    /// Video chip position (scanline, dot), if the bus has one.
+    pub video_position: Option<(u16, u16)>,
+    /// Operand memory effect, for instructions addressing memory.
+    pub memory: Option<MemoryEffect>,
+    /// Resolved address of indirect JMP.
+    pub jump_target: Option<u16>,
+}
 
+impl TraceRecord {
+    /// Instruction bytes.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
 
-/// CPU tracing.
-pub trait Trace {
-    fn trace(&mut self) -> String;
-}
+    /// Operand byte.
+    pub fn operand_u8(&self) -> u8 {
+        self.bytes[1]
+    }
 
-impl Trace for Cpu {
+    /// Operand word.
+    pub fn operand_u16(&self) -> u16 {
+        u16::from_le_bytes([self.bytes[1], self.bytes[2]])
+    }
 
-    /// Traces CPU state to String.
-    fn trace(&mut self) -> String {
-    
-        let ref opscodes: HashMap<u8, &'static opcode::OpCode> = *opcode::OPCODES_MAP;
+    /// Relative branch target.
+    pub fn branch_target(&self) -> u16 {
+        self.program_counter
+            .wrapping_add(2)
+            .wrapping_add(self.operand_u8() as i8 as u16)
+    }
 
-        let code = self.mem_read(self.program_counter);
-        let ops = opscodes.get(&code).expect(format!("unknown opcode {:#06x}", code).as_str());
+    /// Indicates if operand is the accumulator (ASL A, LSR A, ROL A, ROR A).
+    pub fn accumulator_operand(&self) -> bool {
+        matches!(self.bytes[0], 0x0a | 0x4a | 0x2a | 0x6a)
+    }
+}
 
+/// CPU tracing.
+pub trait Trace {
+    /// Captures CPU state and next instruction.
+    /// Memory is peeked, so tracing does not change emulation.
+    fn trace_record(&self) -> TraceRecord;
+
+    /// Traces CPU state to String, in nestest.log format.
+    /// PPU column is only present if the bus has a video chip.
+    fn trace(&self) -> String {
+        NestestFormatter.format(&self.trace_record())
+    }
+
+    /// Traces CPU state to String, with given formatter.
+    fn trace_with(&self, formatter: &dyn TraceFormatter) -> String {
+        formatter.format(&self.trace_record())
+    }
+}
+
+impl<B: Bus> Trace for Cpu<B> {
+    fn trace_record(&self) -> TraceRecord {
         let begin = self.program_counter;
-        let mut hex_dump = vec![];
-        hex_dump.push(code);
+        let code = self.mem_peek(begin);
+        let ops = &Self::OPCODES[code as usize];
 
-        let (mem_addr, stored_value) = match ops.mode {
-            AddressingMode::Immediate | AddressingMode::NoneAddressing => (0, 0),
+        let mut bytes = [code, 0, 0];
+        for i in 1..ops.len {
+            bytes[i as usize] = self.mem_peek(begin.wrapping_add(i as u16));
+        }
+
+        let memory = match ops.mode {
+            AddressingMode::Immediate | AddressingMode::NoneAddressing => None,
             _ => {
-                let addr = self.get_absolute_address(&ops.mode, begin + 1).0;
-                (addr, self.mem_read(addr))
+                let address = self
+                    .get_absolute_address(&ops.mode, begin.wrapping_add(1))
+                    .0;
+                let access = match ops.access {
+                    Access::Write(_) => MemoryAccess::Write,
+                    Access::ReadModifyWrite(_) => MemoryAccess::ReadModifyWrite,
+                    _ => MemoryAccess::Read,
+                };
+                Some(MemoryEffect {
+                    address,
+                    value: self.traced_value(address),
+                    access,
+                })
             }
         };
 
-        let tmp = match ops.len {
-            1 => match ops.code {
-                0x0a | 0x4a | 0x2a | 0x6a => format!("A "),
-                _ => String::from(""),
-            },
-            2 => {
-                let address: u8 = self.mem_read(begin + 1);
-                hex_dump.push(address);
-
-                match ops.mode {
-                    AddressingMode::Immediate => format!("#${:02x}", address),
-                    AddressingMode::ZeroPage => format!("${:02x} = {:02x}", mem_addr, stored_value),
-                    AddressingMode::ZeroPage_X => format!(
-                        "${:02x},X @ {:02x} = {:02x}",
-                        address, mem_addr, stored_value
-                    ),
-                    AddressingMode::ZeroPage_Y => format!(
-                        "${:02x},Y @ {:02x} = {:02x}",
-                        address, mem_addr, stored_value
-                    ),
-                    AddressingMode::Indirect_X => format!(
-                        "(${:02x},X) @ {:02x} = {:04x} = {:02x}",
-                        address,
-                        (address.wrapping_add(self.register_x)),
-                        mem_addr,
-                        stored_value
-                    ),
-                    AddressingMode::Indirect_Y => format!(
-                        "(${:02x}),Y = {:04x} @ {:04x} = {:02x}",
-                        address,
-                        (mem_addr.wrapping_sub(self.register_y as u16)),
-                        mem_addr,
-                        stored_value
-                    ),
-                    AddressingMode::NoneAddressing => {
-                        // assuming local jumps: BNE, BVS, etc....
-                        let address: usize =
-                            (begin as usize + 2).wrapping_add((address as i8) as usize);
-                        format!("${:04x}", address)
-                    }
-
-                    _ => panic!(
-                        "unexpected addressing mode {:?} has ops-len 2. code {:02x}",
-                        ops.mode, ops.code
-                    ),
-                }
-            }
-            3 => {
-                let address_lo = self.mem_read(begin + 1);
-                let address_hi = self.mem_read(begin + 2);
-                hex_dump.push(address_lo);
-                hex_dump.push(address_hi);
-
-                let address = self.mem_read_u16(begin + 1);
-
-                match ops.mode {
-                    AddressingMode::NoneAddressing => {
-                        if ops.code == 0x6c {
-                            //jmp indirect
-                            let jmp_addr = if address & 0x00FF == 0x00FF {
-                                let lo = self.mem_read(address);
-                                let hi = self.mem_read(address & 0xFF00);
-                                (hi as u16) << 8 | (lo as u16)
-                            } else {
-                                self.mem_read_u16(address)
-                            };
-
-                            format!("(${:04x}) = {:04x}", address, jmp_addr)
-                        } else {
-                            format!("${:04x}", address)
-                        }
-                    }
-                    AddressingMode::Absolute => format!("${:04x} = {:02x}", mem_addr, stored_value),
-                    AddressingMode::Absolute_X => format!(
-                        "${:04x},X @ {:04x} = {:02x}",
-                        address, mem_addr, stored_value
-                    ),
-                    AddressingMode::Absolute_Y => format!(
-                        "${:04x},Y @ {:04x} = {:02x}",
-                        address, mem_addr, stored_value
-                    ),
-                    _ => panic!(
-                        "unexpected addressing mode {:?} has ops-len 3. code {:02x}",
-                        ops.mode, ops.code
-                    ),
-                }
-            }
-            _ => String::from(""),
+        let jump_target = if code == 0x6c {
+            // 6502 bug: pointer high byte is not incremented across pages
+            let pointer = u16::from_le_bytes([bytes[1], bytes[2]]);
+            let lo = self.mem_peek(pointer);
+            let hi = self.mem_peek((pointer & 0xFF00) | (pointer as u8).wrapping_add(1) as u16);
+            Some(u16::from_le_bytes([lo, hi]))
+        } else {
+            None
         };
 
-        let hex_str = hex_dump
-            .iter()
-            .map(|z| format!("{:02x}", z))
-            .collect::<Vec<String>>()
-            .join(" ");
-        let asm_str = format!("{:04x}  {:8} {: >4} {}", begin, hex_str, ops.mnemonic, tmp)
-            .trim()
-            .to_string();
-
-        format!(
-            "{:47} A:{:02x} X:{:02x} Y:{:02x} P:{:02x} SP:{:02x}",
-            asm_str, self.register_a, self.register_x, self.register_y, self.status, self.stack_pointer,
-        )
-        .to_ascii_uppercase()
+        TraceRecord {
+            program_counter: begin,
+            bank: self.bus.bank(begin),
+            bytes,
+            len: ops.len,
+            mnemonic: ops.mnemonic,
+            mode: ops.mode,
+            register_a: self.register_a,
+            register_x: self.register_x,
+            register_y: self.register_y,
+            status: self.status,
+            stack_pointer: self.stack_pointer,
+            cycles: self.cycles,
+            video_position: self.bus.video_position(),
+            memory,
+            jump_target,
+        }
+    }
+}
+
+impl<B: Bus> Cpu<B> {
+    /// Value shown for an operand address.
+    /// As in nestest.log (traced by Nintendulator), NES APU and I/O registers are not read, and shown as $FF.
+    fn traced_value(&self, addr: u16) -> u8 {
+        let nes = self.bus.video_position().is_some();
+        if nes && (0x4000..=0x4017).contains(&addr) {
+            0xff
+        } else {
+            self.mem_peek(addr)
+        }
     }
 }
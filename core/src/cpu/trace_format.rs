@@ -0,0 +1,318 @@
+use std::str::FromStr;
+
+use super::{
+    instruction::AddressingMode,
+    trace::{MemoryAccess, TraceRecord},
+    CpuFlags,
+};
+
+/// Formats trace records to lines (without line ending).
+pub trait TraceFormatter {
+    fn format(&self, record: &TraceRecord) -> String;
+}
+
+/// Trace formats, to select a formatter at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    Nestest,
+    Mesen,
+    Fceux,
+    JsonLines,
+}
+
+impl TraceFormat {
+    pub fn formatter(self) -> &'static dyn TraceFormatter {
+        match self {
+            TraceFormat::Nestest => &NestestFormatter,
+            TraceFormat::Mesen => &MesenFormatter,
+            TraceFormat::Fceux => &FceuxFormatter,
+            TraceFormat::JsonLines => &JsonFormatter,
+        }
+    }
+}
+
+impl TraceFormatter for TraceFormat {
+    fn format(&self, record: &TraceRecord) -> String {
+        self.formatter().format(record)
+    }
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "nestest" => Ok(TraceFormat::Nestest),
+            "mesen" => Ok(TraceFormat::Mesen),
+            "fceux" => Ok(TraceFormat::Fceux),
+            "json" | "jsonl" => Ok(TraceFormat::JsonLines),
+            _ => Err(format!("unknown trace format '{}'", s)),
+        }
+    }
+}
+
+/// nestest.log format (Nintendulator).
+/// C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+pub struct NestestFormatter;
+
+impl TraceFormatter for NestestFormatter {
+    fn format(&self, record: &TraceRecord) -> String {
+        let (addr, value) = match record.memory {
+            Some(memory) => (memory.address, memory.value),
+            None => (0, 0),
+        };
+
+        let operand = match (record.len, record.mode) {
+            (1, _) if record.accumulator_operand() => String::from("A "),
+            (1, _) => String::from(""),
+            (2, AddressingMode::Immediate) => format!("#${:02x}", record.operand_u8()),
+            (2, AddressingMode::ZeroPage) => format!("${:02x} = {:02x}", addr, value),
+            (2, AddressingMode::ZeroPage_X) => {
+                format!(
+                    "${:02x},X @ {:02x} = {:02x}",
+                    record.operand_u8(),
+                    addr,
+                    value
+                )
+            }
+            (2, AddressingMode::ZeroPage_Y) => {
+                format!(
+                    "${:02x},Y @ {:02x} = {:02x}",
+                    record.operand_u8(),
+                    addr,
+                    value
+                )
+            }
+            (2, AddressingMode::Indirect_X) => format!(
+                "(${:02x},X) @ {:02x} = {:04x} = {:02x}",
+                record.operand_u8(),
+                record.operand_u8().wrapping_add(record.register_x),
+                addr,
+                value
+            ),
+            (2, AddressingMode::Indirect_Y) => format!(
+                "(${:02x}),Y = {:04x} @ {:04x} = {:02x}",
+                record.operand_u8(),
+                addr.wrapping_sub(record.register_y as u16),
+                addr,
+                value
+            ),
+            (2, _) => format!("${:04x}", record.branch_target()),
+            (3, AddressingMode::Absolute) => format!("${:04x} = {:02x}", addr, value),
+            (3, AddressingMode::Absolute_X) => {
+                format!(
+                    "${:04x},X @ {:04x} = {:02x}",
+                    record.operand_u16(),
+                    addr,
+                    value
+                )
+            }
+            (3, AddressingMode::Absolute_Y) => {
+                format!(
+                    "${:04x},Y @ {:04x} = {:02x}",
+                    record.operand_u16(),
+                    addr,
+                    value
+                )
+            }
+            (3, _) => match record.jump_target {
+                Some(target) => format!("(${:04x}) = {:04x}", record.operand_u16(), target),
+                None => format!("${:04x}", record.operand_u16()),
+            },
+            _ => String::from(""),
+        };
+
+        let hex_str = record
+            .bytes()
+            .iter()
+            .map(|z| format!("{:02x}", z))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let asm_str = format!(
+            "{:04x}  {:8} {: >4} {}",
+            record.program_counter, hex_str, record.mnemonic, operand
+        )
+        .trim()
+        .to_string();
+
+        let mut line = format!(
+            "{:47} A:{:02x} X:{:02x} Y:{:02x} P:{:02x} SP:{:02x}",
+            asm_str,
+            record.register_a,
+            record.register_x,
+            record.register_y,
+            record.status,
+            record.stack_pointer,
+        )
+        .to_ascii_uppercase();
+        if let Some((scanline, dot)) = record.video_position {
+            line.push_str(&format!(" PPU:{:3},{:3}", scanline, dot));
+        }
+        line.push_str(&format!(" CYC:{}", record.cycles));
+        line
+    }
+}
+
+/// Mesen trace logger style: disassembly with effective address, flags as letters,
+/// scanline (V) and dot (H).
+/// C5F7  STX $00 = $00               A:00 X:00 Y:00 S:FD P:nv--dIZc V:0   H:36  Cycle:12
+pub struct MesenFormatter;
+
+impl TraceFormatter for MesenFormatter {
+    fn format(&self, record: &TraceRecord) -> String {
+        let disassembly = disassemble(record, "[$", "]", "$");
+        let mut line = format!(
+            "{:04X}  {:<27} A:{:02X} X:{:02X} Y:{:02X} S:{:02X} P:{}",
+            record.program_counter,
+            disassembly,
+            record.register_a,
+            record.register_x,
+            record.register_y,
+            record.stack_pointer,
+            flag_letters(record.status, "NV--DIZC"),
+        );
+        if let Some((scanline, dot)) = record.video_position {
+            line.push_str(&format!(" V:{:<3} H:{:<3}", scanline, dot));
+        }
+        line.push_str(&format!(" Cycle:{}", record.cycles));
+        line
+    }
+}
+
+/// FCEUX trace logger style: instruction bytes, disassembly with effective address,
+/// all status bits as letters.
+/// $C5F7:86 00     STX $00 = #$00                  A:00 X:00 Y:00 S:FD P:nvUbdIZc
+pub struct FceuxFormatter;
+
+impl TraceFormatter for FceuxFormatter {
+    fn format(&self, record: &TraceRecord) -> String {
+        let hex_str = record
+            .bytes()
+            .iter()
+            .map(|z| format!("{:02X}", z))
+            .collect::<Vec<String>>()
+            .join(" ");
+        format!(
+            "${:04X}:{:<9} {:<31} A:{:02X} X:{:02X} Y:{:02X} S:{:02X} P:{}",
+            record.program_counter,
+            hex_str,
+            disassemble(record, "@ $", "", "#$"),
+            record.register_a,
+            record.register_x,
+            record.register_y,
+            record.stack_pointer,
+            flag_letters(record.status, "NVUBDIZC"),
+        )
+    }
+}
+
+/// Machine-readable JSON object per line: registers, cycle, video position and memory effect.
+/// {"pc":50679,"bytes":[134,0],"mnemonic":"STX","a":0,"x":0,"y":0,"p":38,"sp":253,"cycle":12,
+/// "scanline":0,"dot":36,"memory":{"address":0,"value":0,"access":"write"}}
+pub struct JsonFormatter;
+
+impl TraceFormatter for JsonFormatter {
+    fn format(&self, record: &TraceRecord) -> String {
+        let bytes = record
+            .bytes()
+            .iter()
+            .map(|z| z.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+        let (scanline, dot) = match record.video_position {
+            Some((scanline, dot)) => (scanline.to_string(), dot.to_string()),
+            None => (String::from("null"), String::from("null")),
+        };
+        let memory = match record.memory {
+            Some(memory) => format!(
+                "{{\"address\":{},\"value\":{},\"access\":\"{}\"}}",
+                memory.address,
+                memory.value,
+                match memory.access {
+                    MemoryAccess::Read => "read",
+                    MemoryAccess::Write => "write",
+                    MemoryAccess::ReadModifyWrite => "read_modify_write",
+                }
+            ),
+            None => String::from("null"),
+        };
+        format!(
+            "{{\"pc\":{},\"bytes\":[{}],\"mnemonic\":\"{}\",\"a\":{},\"x\":{},\"y\":{},\"p\":{},\"sp\":{},\"cycle\":{},\"scanline\":{},\"dot\":{},\"memory\":{}}}",
+            record.program_counter,
+            bytes,
+            record.mnemonic,
+            record.register_a,
+            record.register_x,
+            record.register_y,
+            record.status.bits(),
+            record.stack_pointer,
+            record.cycles,
+            scanline,
+            dot,
+            memory
+        )
+    }
+}
+
+/// Disassembles instruction, followed by effective address (for indexed and indirect modes)
+/// and value at operand address.
+/// Unofficial opcodes are not marked.
+fn disassemble(
+    record: &TraceRecord,
+    address_prefix: &str,
+    address_suffix: &str,
+    value_prefix: &str,
+) -> String {
+    let mnemonic = record.mnemonic.trim_start_matches('*');
+    let effective = |address: u16| format!(" {}{:04X}{}", address_prefix, address, address_suffix);
+
+    let (operand, indexed) = match (record.len, record.mode) {
+        (1, _) if record.accumulator_operand() => (String::from("A"), false),
+        (1, _) => (String::from(""), false),
+        (2, AddressingMode::Immediate) => (format!("#${:02X}", record.operand_u8()), false),
+        (2, AddressingMode::ZeroPage) => (format!("${:02X}", record.operand_u8()), false),
+        (2, AddressingMode::ZeroPage_X) => (format!("${:02X},X", record.operand_u8()), true),
+        (2, AddressingMode::ZeroPage_Y) => (format!("${:02X},Y", record.operand_u8()), true),
+        (2, AddressingMode::Indirect_X) => (format!("(${:02X},X)", record.operand_u8()), true),
+        (2, AddressingMode::Indirect_Y) => (format!("(${:02X}),Y", record.operand_u8()), true),
+        (2, _) => (format!("${:04X}", record.branch_target()), false),
+        (3, AddressingMode::Absolute) => (format!("${:04X}", record.operand_u16()), false),
+        (3, AddressingMode::Absolute_X) => (format!("${:04X},X", record.operand_u16()), true),
+        (3, AddressingMode::Absolute_Y) => (format!("${:04X},Y", record.operand_u16()), true),
+        (3, _) => match record.jump_target {
+            Some(target) => (
+                format!("(${:04X}){}", record.operand_u16(), effective(target)),
+                false,
+            ),
+            None => (format!("${:04X}", record.operand_u16()), false),
+        },
+        _ => (String::from(""), false),
+    };
+
+    let mut text = format!("{} {}", mnemonic, operand).trim_end().to_string();
+    if let Some(memory) = record.memory {
+        if indexed {
+            text.push_str(&effective(memory.address));
+        }
+        text.push_str(&format!(" = {}{:02X}", value_prefix, memory.value));
+    }
+    text
+}
+
+/// Status flags as letters, from bit 7 to bit 0: uppercase if set, lowercase if clear.
+/// '-' letters are always shown.
+fn flag_letters(status: CpuFlags, letters: &str) -> String {
+    letters
+        .chars()
+        .enumerate()
+        .map(|(i, letter)| {
+            if letter == '-' {
+                letter
+            } else if status.bits() & (0x80 >> i) != 0 {
+                letter.to_ascii_uppercase()
+            } else {
+                letter.to_ascii_lowercase()
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,124 @@
+use crate::cpu::{
+    trace::{MemoryAccess, MemoryEffect, TraceRecord},
+    trace_format::TraceFormat,
+    AddressingMode, CpuFlags,
+};
+
+/// STA $0200,X with X=5, at $C5F7.
+fn record() -> TraceRecord {
+    TraceRecord {
+        program_counter: 0xc5f7,
+        bank: Some(1),
+        bytes: [0x9d, 0x00, 0x02],
+        len: 3,
+        mnemonic: "STA",
+        mode: AddressingMode::Absolute_X,
+        register_a: 0x42,
+        register_x: 0x05,
+        register_y: 0x00,
+        status: CpuFlags::from_bits_truncate(0x26),
+        stack_pointer: 0xfd,
+        cycles: 12,
+        video_position: Some((0, 36)),
+        memory: Some(MemoryEffect {
+            address: 0x0205,
+            value: 0x11,
+            access: MemoryAccess::Write,
+        }),
+        jump_target: None,
+    }
+}
+
+#[test]
+fn test_nestest_format() {
+    assert_eq!(
+        TraceFormat::Nestest.formatter().format(&record()),
+        "C5F7  9D 00 02  STA $0200,X @ 0205 = 11         A:42 X:05 Y:00 P:26 SP:FD PPU:  0, 36 CYC:12"
+    );
+}
+
+#[test]
+fn test_mesen_format() {
+    assert_eq!(
+        TraceFormat::Mesen.formatter().format(&record()),
+        "C5F7  STA $0200,X [$0205] = $11   A:42 X:05 Y:00 S:FD P:nv--dIZc V:0   H:36  Cycle:12"
+    );
+}
+
+#[test]
+fn test_fceux_format() {
+    assert_eq!(
+        TraceFormat::Fceux.formatter().format(&record()),
+        "$C5F7:9D 00 02  STA $0200,X @ $0205 = #$11      A:42 X:05 Y:00 S:FD P:nvUbdIZc"
+    );
+}
+
+#[test]
+fn test_json_format() {
+    assert_eq!(
+        TraceFormat::JsonLines.formatter().format(&record()),
+        "{\"pc\":50679,\"bytes\":[157,0,2],\"mnemonic\":\"STA\",\"a\":66,\"x\":5,\"y\":0,\"p\":38,\"sp\":253,\
+         \"cycle\":12,\"scanline\":0,\"dot\":36,\
+         \"memory\":{\"address\":517,\"value\":17,\"access\":\"write\"}}"
+    );
+
+    let mut record = record();
+    record.video_position = None;
+    record.memory = None;
+    assert!(TraceFormat::JsonLines
+        .formatter()
+        .format(&record)
+        .ends_with("\"scanline\":null,\"dot\":null,\"memory\":null}"));
+}
+
+#[test]
+fn test_jump_indirect() {
+    let record = TraceRecord {
+        bytes: [0x6c, 0x00, 0x02],
+        mnemonic: "JMP",
+        mode: AddressingMode::NoneAddressing,
+        memory: None,
+        jump_target: Some(0xc000),
+        ..record()
+    };
+    assert!(TraceFormat::Nestest
+        .formatter()
+        .format(&record)
+        .starts_with("C5F7  6C 00 02  JMP ($0200) = C000 "));
+    assert!(TraceFormat::Mesen
+        .formatter()
+        .format(&record)
+        .starts_with("C5F7  JMP ($0200) [$C000] "));
+}
+
+#[test]
+fn test_unofficial_mnemonic() {
+    let record = TraceRecord {
+        bytes: [0x04, 0x10, 0x00],
+        len: 2,
+        mnemonic: "*NOP",
+        mode: AddressingMode::ZeroPage,
+        memory: Some(MemoryEffect {
+            address: 0x0010,
+            value: 0x00,
+            access: MemoryAccess::Read,
+        }),
+        ..record()
+    };
+    assert!(TraceFormat::Nestest
+        .formatter()
+        .format(&record)
+        .starts_with("C5F7  04 10    *NOP $10 = 00 "));
+    assert!(TraceFormat::Fceux
+        .formatter()
+        .format(&record)
+        .starts_with("$C5F7:04 10     NOP $10 = #$00 "));
+}
+
+#[test]
+fn test_format_from_str() {
+    assert_eq!("mesen".parse(), Ok(TraceFormat::Mesen));
+    assert_eq!("FCEUX".parse(), Ok(TraceFormat::Fceux));
+    assert_eq!("jsonl".parse(), Ok(TraceFormat::JsonLines));
+    assert!("bizhawk".parse::<TraceFormat>().is_err());
+}
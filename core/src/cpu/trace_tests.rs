@@ -2,8 +2,9 @@ use std::{cell::RefCell, rc::Rc};
 
 use crate::{
     bus::cpu_bus::CpuBus,
-    cpu::{trace::Trace, Cpu},
-    memory::Memory, ppu::Ppu,
+    cpu::{trace::Trace, Cpu, RunMode},
+    memory::{Memory, Ram},
+    ppu::Ppu,
 };
 
 #[test]
@@ -17,8 +18,8 @@ fn test_trace() {
     bus.borrow_mut().mem_write(103, 0x88);
     bus.borrow_mut().mem_write(104, 0x00);
 
-    let mut cpu = Cpu::new();
-    cpu.connect_bus(&bus);
+    let mut cpu = Cpu::new(bus);
+    cpu.set_run_mode(RunMode::StopOnBrk);
     cpu.program_counter = 0x64;
     cpu.register_a = 1;
     cpu.register_x = 2;
@@ -29,15 +30,51 @@ fn test_trace() {
     })
     .unwrap();
     assert_eq!(
-        "0064  A2 01     LDX #$01                        A:01 X:02 Y:03 P:24 SP:FD",
+        "0064  A2 01     LDX #$01                        A:01 X:02 Y:03 P:24 SP:FD PPU:  0,  0 CYC:0",
         result[0]
     );
     assert_eq!(
-        "0066  CA        DEX                             A:01 X:01 Y:03 P:24 SP:FD",
+        "0066  CA        DEX                             A:01 X:01 Y:03 P:24 SP:FD PPU:  0,  0 CYC:2",
         result[1]
     );
     assert_eq!(
-        "0067  88        DEY                             A:01 X:00 Y:03 P:26 SP:FD",
+        "0067  88        DEY                             A:01 X:00 Y:03 P:26 SP:FD PPU:  0,  0 CYC:4",
         result[2]
     );
 }
+
+#[test]
+fn test_trace_has_no_side_effect() {
+    let ppu = Rc::new(RefCell::new(Ppu::new()));
+    ppu.borrow_mut().set_vblank();
+    let bus = Rc::new(RefCell::new(CpuBus::new()));
+    bus.borrow_mut().connect_ppu(&ppu);
+    // LDA $2002
+    bus.borrow_mut().mem_write(100, 0xad);
+    bus.borrow_mut().mem_write(101, 0x02);
+    bus.borrow_mut().mem_write(102, 0x20);
+
+    let mut cpu = Cpu::new(bus);
+    cpu.program_counter = 0x64;
+    assert_eq!(
+        "0064  AD 02 20  LDA $2002 = 80                  A:00 X:00 Y:00 P:24 SP:FD PPU:  0,  0 CYC:0",
+        cpu.trace()
+    );
+    // Vblank flag is still set
+    assert!(ppu.borrow().status().is_in_vblank());
+}
+
+#[test]
+fn test_trace_without_video() {
+    let mut cpu = Cpu::new(Ram::new());
+    // LDA $4015: not an I/O register outside the NES
+    cpu.mem_write(0x4015, 0x42);
+    cpu.mem_write(0x0064, 0xad);
+    cpu.mem_write(0x0065, 0x15);
+    cpu.mem_write(0x0066, 0x40);
+    cpu.program_counter = 0x64;
+    assert_eq!(
+        "0064  AD 15 40  LDA $4015 = 42                  A:00 X:00 Y:00 P:24 SP:FD CYC:0",
+        cpu.trace()
+    );
+}
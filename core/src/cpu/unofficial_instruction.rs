@@ -1,100 +1,90 @@
-use crate::memory::Memory;
+use crate::bus::Bus;
 
-use super::{
-    instruction::{AddressingMode, Instructions},
-    CpuFlags, Cpu,
-};
+use super::{instruction::Instructions, CpuFlags, Cpu};
 
 /// NES CPU unofficial instructions.
+/// Same conventions as official instructions (see Instructions).
 /// Reference: https://www.nesdev.org/undocumented_opcodes.txt.
 pub(crate) trait UnofficialInstructions {
-    fn lax(&mut self, mode: &AddressingMode);
-    fn sax(&mut self, mode: &AddressingMode);
-    fn unofficial_sbc(&mut self, mode: &AddressingMode);
-    fn dcp(&mut self, mode: &AddressingMode);
-    fn isb(&mut self, mode: &AddressingMode);
-    fn slo(&mut self, mode: &AddressingMode);
-    fn rla(&mut self, mode: &AddressingMode);
-    fn sre(&mut self, mode: &AddressingMode);
-    fn rra(&mut self, mode: &AddressingMode);
-    fn axs(&mut self, mode: &AddressingMode);
-    fn arr(&mut self, mode: &AddressingMode);
-    fn anc(&mut self, mode: &AddressingMode);
-    fn alr(&mut self, mode: &AddressingMode);
-    fn lxa(&mut self, mode: &AddressingMode);
-    fn xaa(&mut self, mode: &AddressingMode);
-    fn las(&mut self, mode: &AddressingMode);
-    fn tas(&mut self);
-    fn ahx_indirect_y(&mut self);
-    fn ahx_absolute_y(&mut self);
-    fn shx(&mut self);
-    fn shy(&mut self);
+    fn lax(&mut self, data: u8);
+    fn sax(&mut self) -> u8;
+    fn unofficial_sbc(&mut self, data: u8);
+    fn dcp(&mut self, data: u8) -> u8;
+    fn isb(&mut self, data: u8) -> u8;
+    fn slo(&mut self, data: u8) -> u8;
+    fn rla(&mut self, data: u8) -> u8;
+    fn sre(&mut self, data: u8) -> u8;
+    fn rra(&mut self, data: u8) -> u8;
+    fn axs(&mut self, data: u8);
+    fn arr(&mut self, data: u8);
+    fn anc(&mut self, data: u8);
+    fn alr(&mut self, data: u8);
+    fn lxa(&mut self, data: u8);
+    fn xaa(&mut self, data: u8);
+    fn las(&mut self, data: u8);
+    fn tas(&mut self) -> u8;
+    fn ahx(&mut self) -> u8;
+    fn shx(&mut self) -> u8;
+    fn shy(&mut self) -> u8;
+    /// NOP reading its operand.
+    fn nop_read(&mut self, data: u8);
 }
 
-impl UnofficialInstructions for Cpu {
-    fn lax(&mut self, mode: &AddressingMode) {
-        let (addr, _) = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
+impl<B: Bus> UnofficialInstructions for Cpu<B> {
+    fn lax(&mut self, data: u8) {
         self.register_a = data;
         self.update_zero_and_negative_flags(self.register_a);
         self.register_x = self.register_a;
     }
 
-    fn sax(&mut self, mode: &AddressingMode) {
-        let data = self.register_a & self.register_x;
-        let (addr, _) = self.get_operand_address(mode);
-        self.mem_write(addr, data);
+    fn sax(&mut self) -> u8 {
+        self.register_a & self.register_x
     }
 
-    fn unofficial_sbc(&mut self, mode: &AddressingMode) {
-        let (addr, _) = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
+    fn unofficial_sbc(&mut self, data: u8) {
         self.sub_from_register_a(data);
     }
 
-    fn dcp(&mut self, mode: &AddressingMode) {
-        let (addr, _) = self.get_operand_address(mode);
-        let mut data = self.mem_read(addr);
-        data = data.wrapping_sub(1);
-        self.mem_write(addr, data);
-        if data <= self.register_a {
-            self.status.insert(CpuFlags::CARRY);
-        }
-
-        self.update_zero_and_negative_flags(self.register_a.wrapping_sub(data));
+    fn dcp(&mut self, data: u8) -> u8 {
+        let data = data.wrapping_sub(1);
+        self.compare(data, self.register_a);
+        data
     }
 
-    fn isb(&mut self, mode: &AddressingMode) {
-        let data = self.inc(mode);
+    fn isb(&mut self, data: u8) -> u8 {
+        let data = self.inc(data);
         self.sub_from_register_a(data);
+        data
     }
 
-    fn slo(&mut self, mode: &AddressingMode) {
-        let data = self.asl(mode);
+    fn slo(&mut self, data: u8) -> u8 {
+        let data = self.asl(data);
         self.register_a = data | self.register_a;
         self.update_zero_and_negative_flags(self.register_a);
+        data
     }
 
-    fn rla(&mut self, mode: &AddressingMode) {
-        let data = self.rol(mode);
+    fn rla(&mut self, data: u8) -> u8 {
+        let data = self.rol(data);
         self.register_a = data & self.register_a;
         self.update_zero_and_negative_flags(self.register_a);
+        data
     }
 
-    fn sre(&mut self, mode: &AddressingMode) {
-        let data = self.lsr(mode);
+    fn sre(&mut self, data: u8) -> u8 {
+        let data = self.lsr(data);
         self.register_a = data ^ self.register_a;
         self.update_zero_and_negative_flags(self.register_a);
+        data
     }
 
-    fn rra(&mut self, mode: &AddressingMode) {
-        let data = self.ror(mode);
+    fn rra(&mut self, data: u8) -> u8 {
+        let data = self.ror(data);
         self.add_to_register_a(data);
+        data
     }
 
-    fn axs(&mut self, mode: &AddressingMode) {
-        let (addr, _) = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
+    fn axs(&mut self, data: u8) {
         let x_and_a = self.register_x & self.register_a;
         let result = x_and_a.wrapping_sub(data);
 
@@ -105,9 +95,8 @@ impl UnofficialInstructions for Cpu {
 
         self.register_x = result;
     }
-    fn arr(&mut self, mode: &AddressingMode) {
-        let (addr, _) = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
+
+    fn arr(&mut self, data: u8) {
         self.register_a = data & self.register_a;
         self.update_zero_and_negative_flags(self.register_a);
         self.ror_accumulator();
@@ -131,9 +120,7 @@ impl UnofficialInstructions for Cpu {
         self.update_zero_and_negative_flags(result);
     }
 
-    fn anc(&mut self, mode: &AddressingMode) {
-        let (addr, _) = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
+    fn anc(&mut self, data: u8) {
         self.register_a = data & self.register_a;
         self.update_zero_and_negative_flags(self.register_a);
         if self.status.contains(CpuFlags::NEGATIV) {
@@ -143,68 +130,48 @@ impl UnofficialInstructions for Cpu {
         }
     }
 
-    fn alr(&mut self, mode: &AddressingMode) {
-        let (addr, _) = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
+    fn alr(&mut self, data: u8) {
         self.register_a = data & self.register_a;
         self.update_zero_and_negative_flags(self.register_a);
         self.lsr_accumulator();
     }
 
-    fn lxa(&mut self, mode: &AddressingMode) {
-        self.lda(mode);
+    fn lxa(&mut self, data: u8) {
+        self.lda(data);
         self.tax();
     }
 
-    fn xaa(&mut self, mode: &AddressingMode) {
+    fn xaa(&mut self, data: u8) {
         self.register_a = self.register_x;
         self.update_zero_and_negative_flags(self.register_a);
-        let (addr, _) = self.get_operand_address(mode);
-        let data = self.mem_read(addr);
         self.register_a = data & self.register_a;
         self.update_zero_and_negative_flags(self.register_a);
     }
 
-    fn las(&mut self, mode: &AddressingMode) {
-        let (addr, _) = self.get_operand_address(mode);
-        let mut data = self.mem_read(addr);
-        data = data & self.stack_pointer;
+    fn las(&mut self, data: u8) {
+        let data = data & self.stack_pointer;
         self.register_a = data;
         self.register_x = data;
         self.stack_pointer = data;
         self.update_zero_and_negative_flags(data);
     }
 
-    fn tas(&mut self) {
-        let data = self.register_a & self.register_x;
-        self.stack_pointer = data;
-        let mem_address = self.mem_read_u16(self.program_counter) + self.register_y as u16;
-        let data = ((mem_address >> 8) as u8 + 1) & self.stack_pointer;
-        self.mem_write(mem_address, data);
+    fn tas(&mut self) -> u8 {
+        self.stack_pointer = self.register_a & self.register_x;
+        self.unstable_store(self.stack_pointer)
     }
 
-    fn ahx_indirect_y(&mut self) {
-        let pos: u8 = self.mem_read(self.program_counter);
-        let mem_address = self.mem_read_u16(pos as u16) + self.register_y as u16;
-        let data = self.register_a & self.register_x & (mem_address >> 8) as u8;
-        self.mem_write(mem_address, data);
+    fn ahx(&mut self) -> u8 {
+        self.unstable_store(self.register_a & self.register_x)
     }
 
-    fn ahx_absolute_y(&mut self) {
-        let mem_address = self.mem_read_u16(self.program_counter) + self.register_y as u16;
-        let data = self.register_a & self.register_x & (mem_address >> 8) as u8;
-        self.mem_write(mem_address, data);
+    fn shx(&mut self) -> u8 {
+        self.unstable_store(self.register_x)
     }
 
-    fn shx(&mut self) {
-        let mem_address = self.mem_read_u16(self.program_counter) + self.register_y as u16;
-        let data = self.register_x & ((mem_address >> 8) as u8 + 1);
-        self.mem_write(mem_address, data);
+    fn shy(&mut self) -> u8 {
+        self.unstable_store(self.register_y)
     }
 
-    fn shy(&mut self) {
-        let mem_address = self.mem_read_u16(self.program_counter) + self.register_x as u16;
-        let data = self.register_y & ((mem_address >> 8) as u8 + 1);
-        self.mem_write(mem_address, data);
-    }
+    fn nop_read(&mut self, _data: u8) {}
 }
@@ -1,11 +1,11 @@
 use crate::{
     cpu::{instruction_tests::run_code, CpuFlags, Cpu},
-    memory::Memory,
+    memory::{Memory, Ram},
 };
 
 #[test]
 fn test_0xaf_lax() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.mem_write(0x10, 0x02);
     run_code(&mut cpu, vec![0xaf, 0x10, 0x00]).unwrap();
     assert_eq!(cpu.register_a, 0x02);
@@ -14,7 +14,7 @@ fn test_0xaf_lax() {
 
 #[test]
 fn test_0x8f_sax() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_a = 0b1;
     cpu.register_x = 0b1;
     run_code(&mut cpu, vec![0x8f, 0x10, 0x00]).unwrap();
@@ -23,7 +23,7 @@ fn test_0x8f_sax() {
 
 #[test]
 fn test_unofficial_sbc() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.status.insert(CpuFlags::CARRY);
     cpu.register_a = 0x05;
     run_code(&mut cpu, vec![0xeb, 0x02, 0x00]).unwrap();
@@ -32,7 +32,7 @@ fn test_unofficial_sbc() {
 
 #[test]
 fn test_0xcf_dcp() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.mem_write(0x10, 0x02);
     run_code(&mut cpu, vec![0xcf, 0x10, 0x00]).unwrap();
     assert_eq!(cpu.mem_read(0x10), 0x01);
@@ -40,7 +40,7 @@ fn test_0xcf_dcp() {
 
 #[test]
 fn test_0xef_isb() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.status.insert(CpuFlags::CARRY);
     cpu.register_a = 0x06;
     cpu.mem_write(0x10, 0x02);
@@ -51,7 +51,7 @@ fn test_0xef_isb() {
 
 #[test]
 fn test_0x0f_slo() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_a = 0b1;
     cpu.mem_write(0x10, 0b1);
     run_code(&mut cpu, vec![0x0f, 0x10, 0x00]).unwrap();
@@ -60,7 +60,7 @@ fn test_0x0f_slo() {
 
 #[test]
 fn test_0x2f_rla() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_a = 0b10;
     cpu.mem_write(0x10, 0b1);
     run_code(&mut cpu, vec![0x2f, 0x10, 0x00]).unwrap();
@@ -69,7 +69,7 @@ fn test_0x2f_rla() {
 
 #[test]
 fn test_0x4f_sre() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_a = 0b10;
     cpu.mem_write(0x10, 0b10);
     run_code(&mut cpu, vec![0x4f, 0x10, 0x00]).unwrap();
@@ -78,7 +78,7 @@ fn test_0x4f_sre() {
 
 #[test]
 fn test_0x6f_rra() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_a = 0b10;
     cpu.mem_write(0x10, 0b10);
     run_code(&mut cpu, vec![0x6f, 0x10, 0x00]).unwrap();
@@ -87,7 +87,7 @@ fn test_0x6f_rra() {
 
 #[test]
 fn test_axs() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_a = 0x01;
     cpu.register_x = 0x01;
     run_code(&mut cpu, vec![0xcb, 0x01, 0x00]).unwrap();
@@ -96,7 +96,7 @@ fn test_axs() {
 
 #[test]
 fn test_arr() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_a = 0b1;
     run_code(&mut cpu, vec![0x6b, 0b1, 0x00]).unwrap();
     assert_eq!(cpu.register_a, 0b0);
@@ -106,7 +106,7 @@ fn test_arr() {
 
 #[test]
 fn test_0x0b_anc() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_a = 0b1;
     run_code(&mut cpu, vec![0x0b, 0b1, 0x00]).unwrap();
     assert_eq!(cpu.register_a, 0b1);
@@ -115,7 +115,7 @@ fn test_0x0b_anc() {
 
 #[test]
 fn test_alr() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_a = 0b1;
     run_code(&mut cpu, vec![0x4b, 0b1, 0x00]).unwrap();
     assert_eq!(cpu.register_a, 0b0);
@@ -124,7 +124,7 @@ fn test_alr() {
 
 #[test]
 fn test_lxa() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_a = 0b1;
     run_code(&mut cpu, vec![0xab, 0b1, 0x00]).unwrap();
     assert_eq!(cpu.register_x, 0b1);
@@ -132,7 +132,7 @@ fn test_lxa() {
 
 #[test]
 fn test_xaa() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_x = 0b1;
     run_code(&mut cpu, vec![0x8b, 0b1, 0x00]).unwrap();
     assert_eq!(cpu.register_a, 0b1);
@@ -140,7 +140,7 @@ fn test_xaa() {
 
 #[test]
 fn test_las() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.stack_pointer = 0b1;
     cpu.mem_write(0x10, 0b1);
     run_code(&mut cpu, vec![0xbb, 0x10, 0x00]).unwrap();
@@ -151,7 +151,7 @@ fn test_las() {
 
 #[test]
 fn test_tas() {
-    let mut cpu = Cpu::new();
+    let mut cpu = Cpu::new(Ram::new());
     cpu.register_a = 0b1;
     cpu.register_x = 0b1;
     run_code(&mut cpu, vec![0x9b, 0x00]).unwrap();
@@ -1,6 +1,8 @@
 #[macro_use]
 extern crate lazy_static;
 
+pub mod apu;
+pub mod audio;
 pub mod cpu;
 pub mod ppu;
 pub mod bus;
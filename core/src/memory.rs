@@ -1,3 +1,7 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::bus::Bus;
+
 /// Checks page cross (are 2 address in the same 256 bytes)
 pub(crate) fn page_cross(addr1: u16, addr2: u16) -> bool {
     addr1 & 0xFF00 != addr2 & 0xFF00
@@ -8,6 +12,16 @@ pub trait Memory {
 
     fn mem_write(&mut self, addr: u16, data: u8);
 
+    /// Reads memory address without side effects (for tracing and debugging).
+    /// Returns the value mem_read would return, without clearing flags, advancing buffers or shift registers.
+    fn mem_peek(&self, addr: u16) -> u8;
+
+    fn mem_peek_u16(&self, pos: u16) -> u16 {
+        let lo = self.mem_peek(pos) as u16;
+        let hi = self.mem_peek(pos.wrapping_add(1)) as u16;
+        (hi << 8) | lo
+    }
+
     fn mem_read_u16(&mut self, pos: u16) -> u16 {
         let lo = self.mem_read(pos) as u16;
         let hi = self.mem_read(pos + 1) as u16;
@@ -21,3 +35,64 @@ pub trait Memory {
         self.mem_write(pos + 1, hi);
     }
 }
+
+/// Memory shared with other components.
+impl<M: Memory> Memory for Rc<RefCell<M>> {
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        self.borrow_mut().mem_read(addr)
+    }
+
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        self.borrow_mut().mem_write(addr, data)
+    }
+
+    fn mem_peek(&self, addr: u16) -> u8 {
+        self.borrow().mem_peek(addr)
+    }
+
+    fn mem_read_u16(&mut self, pos: u16) -> u16 {
+        self.borrow_mut().mem_read_u16(pos)
+    }
+
+    fn mem_write_u16(&mut self, pos: u16, data: u16) {
+        self.borrow_mut().mem_write_u16(pos, data)
+    }
+}
+
+/// 64KB of RAM covering the whole address space.
+/// Simplest bus for a 6502 system: no I/O, no interrupt.
+#[derive(Debug, Clone)]
+pub struct Ram {
+    data: Box<[u8; 0x10000]>,
+}
+
+impl Default for Ram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ram {
+    /// Creates zero-filled RAM.
+    pub fn new() -> Self {
+        Self {
+            data: Box::new([0; 0x10000]),
+        }
+    }
+}
+
+impl Memory for Ram {
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        self.data[addr as usize]
+    }
+
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        self.data[addr as usize] = data;
+    }
+
+    fn mem_peek(&self, addr: u16) -> u8 {
+        self.data[addr as usize]
+    }
+}
+
+impl Bus for Ram {}
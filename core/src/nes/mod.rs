@@ -1,23 +1,39 @@
 use std::{
     cell::RefCell,
+    io,
+    path::Path,
     rc::Rc,
-    time::{Duration, SystemTimeError},
+    time::{Duration, Instant, SystemTimeError},
 };
 
-use spin_sleep::LoopHelper;
-
 use crate::{
-    bus::{cpu_bus::CpuBus, ppu_bus::PpuBus},
+    apu::Apu,
+    audio::{
+        recorder::{Recorder, RecordingOptions},
+        AudioConfig, AudioOutput, AudioSink,
+    },
+    bus::{cpu_bus::CpuBus, ppu_bus::PpuBus, BusError},
     cartridge::Cartridge,
     controller::Joypad,
-    cpu::{Cpu, CpuError},
+    cpu::{trace::Trace, Cpu, CpuError},
     ppu::{frame::Frame, Ppu, PpuError},
 };
 
+use self::trace_logger::TraceLogger;
+
+pub mod test_rom;
 pub mod tools;
+pub mod trace_diff;
+pub mod trace_logger;
 
 #[cfg(test)]
 mod mod_tests;
+#[cfg(test)]
+mod test_rom_tests;
+#[cfg(test)]
+mod trace_diff_tests;
+#[cfg(test)]
+mod trace_logger_tests;
 
 // Source: https://www.nesdev.org/wiki/Cycle_reference_chart
 pub const CPU_MHZ: f32 = 1.789773;
@@ -27,7 +43,9 @@ pub const CPU_MHZ: f32 = 1.789773;
 pub enum NesError {
     Cpu(CpuError),
     Ppu(PpuError),
+    Bus(BusError),
     Clock(String),
+    Io(String),
 }
 
 impl From<CpuError> for NesError {
@@ -42,31 +60,80 @@ impl From<PpuError> for NesError {
     }
 }
 
+impl From<BusError> for NesError {
+    fn from(value: BusError) -> Self {
+        Self::Bus(value)
+    }
+}
+
 impl From<SystemTimeError> for NesError {
     fn from(value: SystemTimeError) -> Self {
         Self::Clock(value.to_string())
     }
 }
 
+impl From<io::Error> for NesError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value.to_string())
+    }
+}
+
+/// Action taken when emulation reaches an unexpected state (see NesError),
+/// such as a write to cartridge ROM (see BusError).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Stops emulation: run returns the error.
+    Error,
+    /// Halts the CPU until reset, as a JAM opcode does.
+    Halt,
+    /// Logs the error to stderr and goes on.
+    LogAndContinue,
+}
+
+/// Emulation speed control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pacing {
+    /// Runs as fast as possible.
+    /// The frontend paces emulation from its callbacks (audio buffer fill level, display refresh...).
+    Unthrottled,
+    /// Sleeps at the end of each frame to match the CPU clock speed (see set_cpu_mhz).
+    CpuClock,
+}
+
 /// NES console.
 pub struct Nes {
     cpu: Cpu,
     cpu_bus: Rc<RefCell<CpuBus>>,
     cpu_mhz: f32,
+    pacing: Pacing,
+    error_policy: ErrorPolicy,
+    stop_on_jam: bool,
     ppu: Rc<RefCell<Ppu>>,
     ppu_bus: Rc<RefCell<PpuBus>>,
+    apu: Rc<RefCell<Apu>>,
+    audio: Option<AudioOutput>,
+    recorder: Option<Recorder>,
     joypad1: Option<Rc<RefCell<Joypad>>>,
     joypad2: Option<Rc<RefCell<Joypad>>>,
+    trace_logger: Option<TraceLogger>,
 }
 
 impl Nes {
     pub fn new(joypad1: Option<Joypad>, joypad2: Option<Joypad>) -> Self {
-        let mut this = Self {
-            cpu: Cpu::new(),
-            cpu_bus: Rc::new(RefCell::new(CpuBus::new())),
+        // Connects CPU bus to CPU
+        let cpu_bus = Rc::new(RefCell::new(CpuBus::new()));
+        let this = Self {
+            cpu: Cpu::new(Rc::clone(&cpu_bus)),
+            cpu_bus,
             cpu_mhz: CPU_MHZ,
+            pacing: Pacing::Unthrottled,
+            error_policy: ErrorPolicy::Error,
+            stop_on_jam: false,
             ppu: Rc::new(RefCell::new(Ppu::new())),
             ppu_bus: Rc::new(RefCell::new(PpuBus::new())),
+            apu: Rc::new(RefCell::new(Apu::new())),
+            audio: None,
+            recorder: None,
             joypad1: match joypad1 {
                 Some(j) => Some(Rc::new(RefCell::new(j))),
                 None => None,
@@ -75,13 +142,14 @@ impl Nes {
                 Some(j) => Some(Rc::new(RefCell::new(j))),
                 None => None,
             },
+            trace_logger: None,
         };
-        // Connects CPU bus to CPU
-        this.cpu.connect_bus(&this.cpu_bus);
         // Connects PPU to CPU bus
         this.cpu_bus.borrow_mut().connect_ppu(&this.ppu);
         // Connects PPU bus to PPU
         this.ppu.borrow_mut().connect_bus(&this.ppu_bus);
+        // Connects APU to CPU bus
+        this.cpu_bus.borrow_mut().connect_apu(&this.apu);
         // Connects Joypad 1 to CPU bus
         if let Some(joypad1) = &this.joypad1 {
             this.cpu_bus.borrow_mut().connect_joypad1(&joypad1);
@@ -102,6 +170,107 @@ impl Nes {
         self.cpu_mhz
     }
 
+    pub fn set_pacing(&mut self, pacing: Pacing) {
+        self.pacing = pacing;
+    }
+
+    pub fn pacing(&self) -> Pacing {
+        self.pacing
+    }
+
+    pub fn set_error_policy(&mut self, error_policy: ErrorPolicy) {
+        self.error_policy = error_policy;
+    }
+
+    pub fn error_policy(&self) -> ErrorPolicy {
+        self.error_policy
+    }
+
+    /// Makes run return when the CPU is jammed (JAM opcode, or error with Halt policy).
+    /// Otherwise frames go on, and the CPU callback is not called until reset.
+    pub fn set_stop_on_jam(&mut self, stop_on_jam: bool) {
+        self.stop_on_jam = stop_on_jam;
+    }
+
+    pub fn stop_on_jam(&self) -> bool {
+        self.stop_on_jam
+    }
+
+    /// Applies error policy.
+    /// Trace logger history is dumped first, if any.
+    fn handle_error(&mut self, error: NesError) -> Result<(), NesError> {
+        if let Some(trace_logger) = &mut self.trace_logger {
+            trace_logger.post_mortem(&format!("Emulation error: {:?}", error))?;
+        }
+        match self.error_policy {
+            ErrorPolicy::Error => return Err(error),
+            ErrorPolicy::Halt => self.cpu.jam(),
+            ErrorPolicy::LogAndContinue => eprintln!("Emulation error: {:?}", error),
+        }
+        Ok(())
+    }
+
+    /// Takes unexpected bus access of last cycle, if any.
+    fn take_bus_error(&self) -> Option<BusError> {
+        let error = self.cpu_bus.borrow_mut().take_error();
+        error.or_else(|| self.ppu_bus.borrow_mut().take_error())
+    }
+
+    /// Sets trace logger, called for each executed instruction.
+    pub fn set_trace_logger(&mut self, trace_logger: TraceLogger) {
+        self.trace_logger = Some(trace_logger);
+    }
+
+    /// Removes trace logger, to inspect its history or change settings.
+    pub fn remove_trace_logger(&mut self) -> Option<TraceLogger> {
+        self.trace_logger.take()
+    }
+
+    pub fn trace_logger(&self) -> Option<&TraceLogger> {
+        self.trace_logger.as_ref()
+    }
+
+    /// APU, to inspect channels or change mixer settings.
+    /// Can be borrowed from run callbacks.
+    pub fn apu(&self) -> &Rc<RefCell<Apu>> {
+        &self.apu
+    }
+
+    /// Sets audio sink receiving APU output samples.
+    pub fn set_audio_sink(&mut self, config: AudioConfig, sink: Box<dyn AudioSink>) {
+        self.audio = Some(AudioOutput::new(config, sink));
+    }
+
+    /// Removes audio sink.
+    pub fn remove_audio_sink(&mut self) {
+        self.audio = None;
+    }
+
+    /// Starts recording APU output to a WAV file (see Recorder).
+    /// A recording in progress is stopped first.
+    pub fn start_recording<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        options: RecordingOptions,
+    ) -> Result<(), NesError> {
+        self.stop_recording()?;
+        self.recorder = Some(Recorder::new(path, options)?);
+        Ok(())
+    }
+
+    /// Stops recording and completes WAV files.
+    /// Does nothing if no recording is in progress.
+    pub fn stop_recording(&mut self) -> Result<(), NesError> {
+        if let Some(recorder) = self.recorder.take() {
+            recorder.finish()?;
+        }
+        Ok(())
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
     pub fn insert(&mut self, cartridge: Cartridge) {
         self.cpu_bus.borrow_mut().connect_cartridge(&cartridge);
         self.ppu_bus.borrow_mut().connect_cartridge(&cartridge);
@@ -112,11 +281,23 @@ impl Nes {
     }
 
     #[cfg(test)]
-    pub fn start_at(&mut self, addr: u16) {
-        self.cpu.set_program_counter(addr);
+    pub fn set_cpu_run_mode(&mut self, run_mode: crate::cpu::RunMode) {
+        self.cpu.set_run_mode(run_mode);
     }
 
-    pub fn run<F1, F2>(
+    /// Runs emulation until a callback returns false.
+    /// Pending samples and trace lines are delivered when run returns, even on error.
+    pub fn run<F1, F2>(&mut self, cpu_callback: F1, ppu_callback: F2) -> Result<(), NesError>
+    where
+        F1: FnMut(&mut Cpu) -> bool,
+        F2: FnMut(&Ppu, Option<&Rc<RefCell<Joypad>>>, Option<&Rc<RefCell<Joypad>>>) -> bool,
+    {
+        let result = self.emulate(cpu_callback, ppu_callback);
+        let flushed = self.flush_outputs();
+        result.and(flushed)
+    }
+
+    fn emulate<F1, F2>(
         &mut self,
         mut cpu_callback: F1,
         mut ppu_callback: F2,
@@ -127,27 +308,69 @@ impl Nes {
     {
         let mut cont = true;
 
-        let mut loop_helper = LoopHelper::builder().build_without_target_rate();
+        // Frame pacing state
+        let mut frame_start = Instant::now();
+        let mut frame_cycles: u64 = 0;
 
         while cont {
+            if self.stop_on_jam && self.cpu.jammed() {
+                break;
+            }
             if self.ppu_bus.borrow_mut().cartridge_connected() {
-                let delta = loop_helper.loop_start();
-
-                let cycle_duration =
-                    Duration::from_nanos(1_000_000_000 / (self.cpu_mhz * 1_000_000.0) as u64);
-
                 let nmi_before = self.ppu.borrow_mut().nmi_interrupt();
 
                 // CPU callback is called only on instruction change
                 // Not for every cycle
                 if self.cpu.instruction_changed() {
                     cont = cont && cpu_callback(&mut self.cpu);
+                    // Logged after callback, which may change CPU state
+                    if let (true, Some(trace_logger)) = (cont, &mut self.trace_logger) {
+                        trace_logger.log(&self.cpu.trace_record())?;
+                    }
+                }
+                if cont {
+                    let jammed = self.cpu.jammed();
+                    let ticked = self.cpu.tick();
+                    // Trace logger history is dumped when the CPU jams
+                    if !jammed && self.cpu.jammed() {
+                        if let Some(trace_logger) = &mut self.trace_logger {
+                            trace_logger.post_mortem("CPU jammed")?;
+                        }
+                    }
+                    cont = match ticked {
+                        Ok(cont) => cont,
+                        Err(error) => {
+                            self.handle_error(error.into())?;
+                            true
+                        }
+                    };
+                    if let Some(error) = self.take_bus_error() {
+                        self.handle_error(error.into())?;
+                    }
+                }
+                frame_cycles += 1;
+
+                // APU runs at CPU speed
+                self.apu.borrow_mut().tick();
+                if let Some(audio) = &mut self.audio {
+                    audio.clock(self.apu.borrow().output());
+                }
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.clock(&self.apu.borrow());
                 }
-                cont = cont && self.cpu.tick()?;
 
                 // PPU runs 3x faster than CPU
+                let mut frame_ended = false;
                 for _ in 0..3 {
-                    self.ppu.borrow_mut().tick()?;
+                    let ticked = self.ppu.borrow_mut().tick();
+                    match ticked {
+                        Ok(new_line) => {
+                            if new_line && self.ppu.borrow().scanline() == 241 {
+                                frame_ended = true;
+                            }
+                        }
+                        Err(error) => self.handle_error(error.into())?,
+                    }
                 }
 
                 let nmi_after = self.ppu.borrow_mut().nmi_interrupt();
@@ -167,10 +390,19 @@ impl Nes {
                         );
                 }
 
-                // Wait for cycle duration end
-                // DO NOT use a thread::sleep : not accurate enough !
-                if let Some(wait) = cycle_duration.checked_sub(delta) {
-                    spin_sleep::sleep(wait);
+                // Wait for frame duration end
+                if frame_ended {
+                    if self.pacing == Pacing::CpuClock {
+                        let frame_duration = Duration::from_secs_f64(
+                            frame_cycles as f64 / (self.cpu_mhz as f64 * 1_000_000.0),
+                        );
+                        // DO NOT use a thread::sleep : not accurate enough !
+                        if let Some(wait) = frame_duration.checked_sub(frame_start.elapsed()) {
+                            spin_sleep::sleep(wait);
+                        }
+                    }
+                    frame_start = Instant::now();
+                    frame_cycles = 0;
                 }
             } else {
                 cont = ppu_callback(
@@ -189,4 +421,15 @@ impl Nes {
 
         Ok(())
     }
+
+    /// Delivers pending samples and trace lines.
+    fn flush_outputs(&mut self) -> Result<(), NesError> {
+        if let Some(audio) = &mut self.audio {
+            audio.flush();
+        }
+        if let Some(trace_logger) = &mut self.trace_logger {
+            trace_logger.flush()?;
+        }
+        Ok(())
+    }
 }
@@ -1,12 +1,22 @@
 use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-    path::{Path, PathBuf},
+    cell::RefCell,
+    fs,
+    path::PathBuf,
+    rc::Rc,
 };
 
-use crate::{cartridge::Cartridge, cpu::trace::Trace, nes::Nes};
+use crate::{
+    audio::{recorder::RecordingOptions, AudioConfig},
+    bus::BusError,
+    cartridge::Cartridge,
+    cpu::{assembler::assemble, RunMode},
+    nes::{ErrorPolicy, Nes, NesError, Pacing},
+};
 
-use super::tools::load_trace;
+use super::{
+    tools::load_trace,
+    trace_diff::{diff_trace, TraceDiffOptions},
+};
 
 fn run_test_suite(cartridge_file: &str, log_file: &str, start_at: Option<u16>) {
     let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -19,47 +29,21 @@ fn run_test_suite(cartridge_file: &str, log_file: &str, start_at: Option<u16>) {
     let mut nes = Nes::new(None, None);
     nes.insert(cartridge);
     nes.reset();
-    if let Some(start_at) = start_at {
-        nes.start_at(start_at);
+    let mut options = TraceDiffOptions::new();
+    options.start_at = start_at;
+    if let Some(mismatch) = diff_trace(&mut nes, &expected, &options).unwrap() {
+        panic!("{} failed: {}", log_file, mismatch);
     }
-    let mut counter = 0;
-    nes.run(|cpu| {
-        assert_eq!(
-            expected[counter].split(" PPU").collect::<Vec<&str>>()[0],
-            cpu.trace(),
-            "failure at line {} of {}, previous instruction: {}",
-            counter + 1,
-            log_file,
-            expected[counter - 1].split(" PPU").collect::<Vec<&str>>()[0]
-        );
-        counter += 1;
-        true
-    }, |_ ,_, _| {true})
-    .unwrap();
 }
 
 #[test]
 fn test_run() {
     // Push 5 to a, then push 2 to a (that is: 2 instructions)
-    let code = vec![0xa9, 0x05, 0xa9, 0x02, 0x00];
-
-    let mut prg_rom: [u8; 0xFFFF] = [0; 0xFFFF];
-    prg_rom[0..code.len()].copy_from_slice(&code[..]);
-
-    // Set program start at address 0xFFFC (0x8000)
-    let hi = (0x8000 >> 8) as u8;
-    let lo = (0x8000 & 0xff) as u8;
-    prg_rom[0xFFFC - 0x8000] = lo;
-    prg_rom[0xFFFC + 1 - 0x8000] = hi;
-
-    let cartridge = Cartridge {
-        prg_rom: prg_rom.to_vec(),
-        chr_rom: [0; 2048].to_vec(),
-        mapper: 0,
-        screen_mirroring: crate::cartridge::Mirroring::Vertical,
-    };
+    let code = assemble("LDA #$05\nLDA #$02\nBRK", 0x8000).unwrap().bytes;
+
     let mut nes = Nes::new(None, None);
-    nes.insert(cartridge);
+    nes.set_cpu_run_mode(RunMode::StopOnBrk);
+    nes.insert(Cartridge::from_code(&code));
     nes.reset();
     let mut inst_count = 0;
     nes.run(|_| {
@@ -70,7 +54,218 @@ fn test_run() {
     assert_eq!(inst_count, 3)
 }
 
-/*#[test]
+#[test]
+fn test_jam() {
+    // NMI on vblank, then JAM
+    let code = assemble("LDA #$80\nSTA $2000\nJAM", 0x8000).unwrap().bytes;
+
+    let mut nes = Nes::new(None, None);
+    nes.insert(Cartridge::from_code(&code));
+    nes.reset();
+
+    // CPU is halted, but frames are still rendered
+    let mut inst_count = 0;
+    let mut jammed = false;
+    let mut frame_count = 0;
+    nes.run(
+        |cpu| {
+            inst_count += 1;
+            jammed = cpu.jammed();
+            true
+        },
+        |_, _, _| {
+            frame_count += 1;
+            frame_count < 3
+        },
+    )
+    .unwrap();
+    assert_eq!(inst_count, 3);
+    assert!(!jammed);
+    assert!(nes.cpu.jammed());
+
+    nes.reset();
+    assert!(!nes.cpu.jammed());
+}
+
+#[test]
+fn test_error_policy() {
+    // LDA #$80, STA $2000 (NMI on vblank), STA $8000 (PRG ROM write), JMP $8008
+    let code = [
+        0xa9, 0x80, 0x8d, 0x00, 0x20, 0x8d, 0x00, 0x80, 0x4c, 0x08, 0x80,
+    ];
+
+    // Runs 2 frames, returns result, instruction count and CPU state
+    let run = |policy| {
+        let mut nes = Nes::new(None, None);
+        nes.insert(Cartridge::from_code(&code));
+        nes.set_error_policy(policy);
+        nes.reset();
+        let mut inst_count = 0;
+        let mut frame_count = 0;
+        let result = nes.run(
+            |_| {
+                inst_count += 1;
+                true
+            },
+            |_, _, _| {
+                frame_count += 1;
+                frame_count < 2
+            },
+        );
+        (result, inst_count, nes.cpu.jammed())
+    };
+
+    let (result, inst_count, jammed) = run(ErrorPolicy::Error);
+    assert!(matches!(
+        result,
+        Err(NesError::Bus(BusError::PrgRomWrite {
+            addr: 0x8000,
+            data: 0x80
+        }))
+    ));
+    assert_eq!(inst_count, 3);
+    assert!(!jammed);
+
+    let (result, inst_count, jammed) = run(ErrorPolicy::Halt);
+    assert!(result.is_ok());
+    assert_eq!(inst_count, 3);
+    assert!(jammed);
+
+    let (result, inst_count, jammed) = run(ErrorPolicy::LogAndContinue);
+    assert!(result.is_ok());
+    assert!(inst_count > 3);
+    assert!(!jammed);
+}
+
+#[test]
 fn test_nestest() {
     run_test_suite("res/nestest.nes", "res/nestest.log", Some(0xC000));
-}*/
+}
+
+/// Cartridge playing a 440Hz tone on pulse 1, then looping forever.
+fn tone_cartridge() -> Cartridge {
+    let code = assemble(
+        "
+        LDA #$01
+        STA $4015
+        LDA #$BF ; duty 50%, constant volume 15
+        STA $4000
+        LDA #$FD
+        STA $4002
+        LDA #$00
+        STA $4003
+        loop: JMP loop
+        ",
+        0x8000,
+    )
+    .unwrap()
+    .bytes;
+
+    Cartridge::from_code(&code)
+}
+
+#[test]
+fn test_audio_output() {
+    let samples = Rc::new(RefCell::new(Vec::<f32>::new()));
+    let mut nes = Nes::new(None, None);
+    nes.set_audio_sink(AudioConfig::new(44_100), Box::new(Rc::clone(&samples)));
+    nes.insert(tone_cartridge());
+    nes.reset();
+    let mut inst_count = 0;
+    nes.run(|_| {
+        inst_count += 1;
+        inst_count < 100_000
+    }, |_,_, _| {true})
+    .unwrap();
+
+    // Tone is audible
+    let samples = samples.borrow();
+    assert!(samples.len() > 1000);
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    assert!(peak > 0.05);
+}
+
+#[test]
+fn test_recording() {
+    let dir = std::env::temp_dir().join("emultendo_test_recording");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("tone.wav");
+
+    let mut nes = Nes::new(None, None);
+    nes.insert(tone_cartridge());
+    nes.reset();
+    let mut options = RecordingOptions::new(44_100);
+    options.split_channels = true;
+    nes.start_recording(&path, options).unwrap();
+    assert!(nes.is_recording());
+    let mut inst_count = 0;
+    nes.run(|_| {
+        inst_count += 1;
+        inst_count < 100_000
+    }, |_,_, _| {true})
+    .unwrap();
+    nes.stop_recording().unwrap();
+    assert!(!nes.is_recording());
+
+    // Mixed output and pulse 1 track hold the tone, other tracks are silent
+    let samples = |name: &str| -> Vec<i16> {
+        let bytes = fs::read(dir.join(name)).unwrap();
+        let data_size = u32::from_le_bytes(bytes[40..44].try_into().unwrap()) as usize;
+        assert_eq!(bytes.len(), 44 + data_size);
+        assert!(data_size > 2000);
+        bytes[44..]
+            .chunks(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect()
+    };
+    let peak = |samples: &[i16]| samples.iter().map(|s| s.unsigned_abs()).max().unwrap();
+    assert!(peak(&samples("tone.wav")) > 1000);
+    assert!(peak(&samples("tone.pulse1.wav")) > 1000);
+    assert_eq!(peak(&samples("tone.pulse2.wav")), 0);
+    assert_eq!(peak(&samples("tone.noise.wav")), 0);
+    assert_eq!(peak(&samples("tone.dmc.wav")), 0);
+    // Triangle outputs a constant level at power up, filtered out after a while
+    let triangle = samples("tone.triangle.wav");
+    assert!(peak(&triangle[triangle.len() / 2..]) < 100);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_recording_completed_on_drop() {
+    let dir = std::env::temp_dir().join("emultendo_test_recording_drop");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("tone.wav");
+
+    let mut nes = Nes::new(None, None);
+    nes.insert(tone_cartridge());
+    nes.reset();
+    nes.start_recording(&path, RecordingOptions::new(44_100)).unwrap();
+    let mut inst_count = 0;
+    nes.run(
+        |_| {
+            inst_count += 1;
+            inst_count < 10_000
+        },
+        |_, _, _| true,
+    )
+    .unwrap();
+    // Recording is not stopped
+    drop(nes);
+
+    // Header holds RIFF and data sizes
+    let bytes = fs::read(&path).unwrap();
+    let riff_size = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+    let data_size = u32::from_le_bytes(bytes[40..44].try_into().unwrap()) as usize;
+    assert!(data_size > 0);
+    assert_eq!(bytes.len(), 44 + data_size);
+    assert_eq!(riff_size, bytes.len() - 8);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_default_pacing_is_unthrottled() {
+    let nes = Nes::new(None, None);
+    assert_eq!(nes.pacing(), Pacing::Unthrottled);
+}
@@ -0,0 +1,144 @@
+use std::{fmt, time::Duration};
+
+use crate::memory::Memory;
+
+use super::{Nes, NesError};
+
+// Test ROM status protocol, used by blargg's test ROMs.
+// Source: readme.txt of blargg's test ROMs (e.g. instr_test-v5)
+const STATUS: u16 = 0x6000;
+const SIGNATURE: u16 = 0x6001;
+const SIGNATURE_BYTES: [u8; 3] = [0xde, 0xb0, 0x61];
+const MESSAGE: u16 = 0x6004;
+const MESSAGE_END: u16 = 0x7fff;
+const RUNNING: u8 = 0x80;
+const RESET_NEEDED: u8 = 0x81;
+
+/// Test ROM outcome.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestRomStatus {
+    Passed,
+    /// Failed with result code (1 to $7F).
+    Failed(u8),
+    /// Still running, or no status reported, when timeout elapsed.
+    Timeout,
+    /// CPU jammed before reporting a result.
+    Jammed,
+}
+
+impl fmt::Display for TestRomStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestRomStatus::Passed => write!(f, "passed"),
+            TestRomStatus::Failed(code) => write!(f, "failed (code {})", code),
+            TestRomStatus::Timeout => write!(f, "timed out"),
+            TestRomStatus::Jammed => write!(f, "CPU jammed"),
+        }
+    }
+}
+
+/// Test ROM outcome and message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestRomResult {
+    pub status: TestRomStatus,
+    /// Text output by the ROM, empty if none.
+    pub message: String,
+}
+
+impl TestRomResult {
+    pub fn passed(&self) -> bool {
+        self.status == TestRomStatus::Passed
+    }
+}
+
+/// Test ROM run options.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestRomOptions {
+    /// Emulated time after which the ROM is stopped.
+    pub timeout: Duration,
+    /// Emulated time between a reset request and the reset.
+    pub reset_delay: Duration,
+}
+
+impl TestRomOptions {
+    /// Creates options with a 30 s timeout, and a 100 ms reset delay.
+    pub fn new() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            reset_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+impl Default for TestRomOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs a test ROM reporting its status at $6000 (the cartridge must be inserted),
+/// until it reports a result, timeout elapses or the CPU jams.
+/// Resets the CPU when the ROM asks for it: PPU, APU and mapper state are kept.
+/// Status is valid once the signature $DE $B0 $61 is written at $6001,
+/// and the zero-terminated message is read at $6004.
+pub fn run_test_rom(nes: &mut Nes, options: &TestRomOptions) -> Result<TestRomResult, NesError> {
+    let cycles_per_second = nes.cpu_mhz() as f64 * 1_000_000.0;
+    let to_cycles = |duration: Duration| (duration.as_secs_f64() * cycles_per_second) as u64;
+    let (timeout, reset_delay) = (to_cycles(options.timeout), to_cycles(options.reset_delay));
+
+    let mut start = None;
+    let mut reset_at = None;
+    let stop_on_jam = nes.stop_on_jam();
+    nes.set_stop_on_jam(true);
+    let result = nes.run(
+        |cpu| {
+            let cycles = cpu.cycles() - *start.get_or_insert(cpu.cycles());
+            match status(&*cpu) {
+                _ if cycles >= timeout => false,
+                Some(RESET_NEEDED) => {
+                    if cycles >= *reset_at.get_or_insert(cycles + reset_delay) {
+                        reset_at = None;
+                        cpu.reset();
+                    }
+                    true
+                }
+                Some(status) => status >= RUNNING,
+                None => true,
+            }
+        },
+        |_, _, _| true,
+    );
+    nes.set_stop_on_jam(stop_on_jam);
+    result?;
+
+    let status = match status(&nes.cpu) {
+        Some(0) => TestRomStatus::Passed,
+        Some(code) if code < RUNNING => TestRomStatus::Failed(code),
+        _ if nes.cpu.jammed() => TestRomStatus::Jammed,
+        _ => TestRomStatus::Timeout,
+    };
+    Ok(TestRomResult {
+        status,
+        message: message(&nes.cpu),
+    })
+}
+
+/// Status byte, if signature is valid.
+fn status<M: Memory>(memory: &M) -> Option<u8> {
+    let signature_valid = (SIGNATURE..)
+        .zip(SIGNATURE_BYTES)
+        .all(|(addr, byte)| memory.mem_peek(addr) == byte);
+    signature_valid.then(|| memory.mem_peek(STATUS))
+}
+
+/// Zero-terminated message, without trailing whitespace (empty if signature is not valid).
+fn message<M: Memory>(memory: &M) -> String {
+    if status(memory).is_none() {
+        return String::new();
+    }
+    let bytes: Vec<u8> = (MESSAGE..=MESSAGE_END)
+        .map(|addr| memory.mem_peek(addr))
+        .take_while(|byte| *byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).trim_end().to_string()
+}
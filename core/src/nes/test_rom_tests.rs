@@ -0,0 +1,175 @@
+use std::time::Duration;
+
+use crate::{cartridge::Cartridge, cpu::assembler::assemble, nes::Nes};
+
+use super::test_rom::{run_test_rom, TestRomOptions, TestRomResult, TestRomStatus};
+
+/// Writes signature, message and status: prefix of test ROM sources.
+const REPORT: &str = r#"
+    report:
+        LDA #$DE
+        STA $6001
+        LDA #$B0
+        STA $6002
+        LDA #$61
+        STA $6003
+        LDX #0
+    copy:
+        LDA text,X
+        STA $6004,X
+        BEQ copied
+        INX
+        JMP copy
+    copied:
+        RTS
+"#;
+
+/// Runs test ROM source at $8000 (source must start with label reset).
+fn run(source: &str, timeout: Duration) -> TestRomResult {
+    let source = format!("{}\n{}", source, REPORT);
+    let code = assemble(&source, 0x8000).unwrap().bytes;
+    let mut nes = Nes::new(None, None);
+    nes.insert(Cartridge::from_code(&code));
+    nes.reset();
+
+    let mut options = TestRomOptions::new();
+    options.timeout = timeout;
+    run_test_rom(&mut nes, &options).unwrap()
+}
+
+#[test]
+fn test_passed() {
+    let result = run(
+        r#"
+        reset:
+            LDA #$80
+            STA $6000
+            JSR report
+            LDA #0
+            STA $6000
+        done:
+            JMP done
+        text:
+            .byte "All 2 tests passed", $0A, $0A, 0
+        "#,
+        Duration::from_secs(1),
+    );
+    assert_eq!(
+        result,
+        TestRomResult {
+            status: TestRomStatus::Passed,
+            message: String::from("All 2 tests passed"),
+        }
+    );
+    assert!(result.passed());
+}
+
+#[test]
+fn test_failed() {
+    let result = run(
+        r#"
+        reset:
+            LDA #$80
+            STA $6000
+            JSR report
+            LDA #3
+            STA $6000
+        done:
+            JMP done
+        text:
+            .byte "BIT", $0A, "Failed #3", 0
+        "#,
+        Duration::from_secs(1),
+    );
+    assert_eq!(result.status, TestRomStatus::Failed(3));
+    assert_eq!(result.message, "BIT\nFailed #3");
+    assert!(!result.passed());
+    assert_eq!(result.status.to_string(), "failed (code 3)");
+}
+
+#[test]
+fn test_reset() {
+    // Asks for reset, and passes after reset
+    let result = run(
+        r#"
+        reset:
+            LDA $6000
+            CMP #$81
+            BEQ after_reset
+            JSR report
+            LDA #$81
+            STA $6000
+        wait:
+            JMP wait
+        after_reset:
+            LDA #0
+            STA $6000
+        done:
+            JMP done
+        text:
+            .byte 0
+        "#,
+        Duration::from_secs(1),
+    );
+    assert_eq!(result.status, TestRomStatus::Passed);
+    assert_eq!(result.message, "");
+}
+
+#[test]
+fn test_timeout() {
+    // Never reports
+    let result = run(
+        "reset: JMP reset\ntext: .byte 0",
+        Duration::from_millis(100),
+    );
+    assert_eq!(
+        result,
+        TestRomResult {
+            status: TestRomStatus::Timeout,
+            message: String::new(),
+        }
+    );
+
+    // Still running
+    let result = run(
+        r#"
+        reset:
+            LDA #$80
+            STA $6000
+            JSR report
+        wait:
+            JMP wait
+        text:
+            .byte "Running", 0
+        "#,
+        Duration::from_millis(100),
+    );
+    assert_eq!(result.status, TestRomStatus::Timeout);
+    assert_eq!(result.message, "Running");
+}
+
+#[test]
+fn test_jam() {
+    // Stops on jam, with NMI disabled or enabled
+    for nmi in [0x00, 0x80] {
+        let result = run(
+            &format!(
+                "
+                reset:
+                    LDA #$80
+                    STA $6000
+                    JSR report
+                    LDA #${:02X}
+                    STA $2000
+                    JAM
+                text:
+                    .byte \"Running\", 0
+                ",
+                nmi
+            ),
+            Duration::from_secs(1),
+        );
+        assert_eq!(result.status, TestRomStatus::Jammed);
+        assert_eq!(result.message, "Running");
+    }
+}
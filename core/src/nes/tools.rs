@@ -13,7 +13,9 @@ where
 {
     let file = File::open(file).map_err(|e| e.to_string())?;
     let buf = BufReader::new(file);
-    Ok(buf.lines().map(|l| l.unwrap()).collect())
+    buf.lines()
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())
 }
 
 /// Renders cartridge tiles of a given bank (0 or 1) to a frame.
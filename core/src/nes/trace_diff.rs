@@ -0,0 +1,329 @@
+use std::{fmt, str::FromStr};
+
+use crate::cpu::trace::Trace;
+
+use super::{Nes, NesError};
+
+/// Actual line of a mismatch caused by a CPU jam.
+pub const JAMMED: &str = "(CPU jammed)";
+
+/// Column of a nestest.log trace line.
+/// C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceColumn {
+    ProgramCounter,
+    Bytes,
+    Disassembly,
+    A,
+    X,
+    Y,
+    P,
+    SP,
+    Ppu,
+    Cycles,
+}
+
+impl TraceColumn {
+    /// Register columns, with their key.
+    const REGISTERS: [(TraceColumn, &'static str); 7] = [
+        (TraceColumn::A, "A:"),
+        (TraceColumn::X, "X:"),
+        (TraceColumn::Y, "Y:"),
+        (TraceColumn::P, "P:"),
+        (TraceColumn::SP, "SP:"),
+        (TraceColumn::Ppu, "PPU:"),
+        (TraceColumn::Cycles, "CYC:"),
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            TraceColumn::ProgramCounter => "PC",
+            TraceColumn::Bytes => "bytes",
+            TraceColumn::Disassembly => "disassembly",
+            TraceColumn::A => "A",
+            TraceColumn::X => "X",
+            TraceColumn::Y => "Y",
+            TraceColumn::P => "P",
+            TraceColumn::SP => "SP",
+            TraceColumn::Ppu => "PPU",
+            TraceColumn::Cycles => "CYC",
+        }
+    }
+}
+
+impl fmt::Display for TraceColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for TraceColumn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pc" => Ok(TraceColumn::ProgramCounter),
+            "bytes" => Ok(TraceColumn::Bytes),
+            "disassembly" | "asm" => Ok(TraceColumn::Disassembly),
+            "a" => Ok(TraceColumn::A),
+            "x" => Ok(TraceColumn::X),
+            "y" => Ok(TraceColumn::Y),
+            "p" => Ok(TraceColumn::P),
+            "sp" => Ok(TraceColumn::SP),
+            "ppu" => Ok(TraceColumn::Ppu),
+            "cyc" | "cycles" => Ok(TraceColumn::Cycles),
+            _ => Err(format!("unknown trace column '{}'", s)),
+        }
+    }
+}
+
+/// Splits a nestest.log line into columns.
+/// Missing columns are omitted (e.g. PPU column of traces without video chip).
+pub fn parse_columns(line: &str) -> Vec<(TraceColumn, &str)> {
+    // Registers start at first " A:", after disassembly
+    let registers = line.find(" A:").unwrap_or(line.len());
+    let instruction = &line[..registers];
+    let mut columns = vec![];
+    for (column, range) in [
+        (TraceColumn::ProgramCounter, 0..4),
+        (TraceColumn::Bytes, 6..15),
+        (TraceColumn::Disassembly, 15..instruction.len()),
+    ] {
+        if let Some(value) = instruction.get(range) {
+            columns.push((column, value.trim()));
+        }
+    }
+
+    // Register values go up to next key
+    let registers = &line[registers..];
+    let keys: Vec<(TraceColumn, usize, usize)> = TraceColumn::REGISTERS
+        .iter()
+        .filter_map(|(column, key)| {
+            registers
+                .find(&format!(" {}", key))
+                .map(|pos| (*column, pos, pos + 1 + key.len()))
+        })
+        .collect();
+    for (i, (column, _, value_start)) in keys.iter().enumerate() {
+        let value_end = keys
+            .iter()
+            .skip(i + 1)
+            .map(|(_, pos, _)| *pos)
+            .min()
+            .unwrap_or(registers.len());
+        columns.push((*column, registers[*value_start..value_end].trim()));
+    }
+    columns
+}
+
+/// Trace diff options.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceDiffOptions {
+    /// Columns that may differ.
+    pub ignored_columns: Vec<TraceColumn>,
+    /// Lines shown before and after the first mismatch.
+    pub context: usize,
+    /// Program counter set before first instruction (e.g. $C000 for nestest automation mode).
+    pub start_at: Option<u16>,
+}
+
+impl TraceDiffOptions {
+    /// Creates options comparing all columns.
+    pub fn new() -> Self {
+        Self {
+            ignored_columns: vec![],
+            context: 3,
+            start_at: None,
+        }
+    }
+}
+
+impl Default for TraceDiffOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Column value difference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnDifference {
+    pub column: TraceColumn,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for ColumnDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: expected {}, actual {}",
+            self.column, self.expected, self.actual
+        )?;
+        // Changed status flags
+        if self.column == TraceColumn::P {
+            if let (Ok(expected), Ok(actual)) = (
+                u8::from_str_radix(&self.expected, 16),
+                u8::from_str_radix(&self.actual, 16),
+            ) {
+                let changed: String = "NVUBDIZC"
+                    .chars()
+                    .enumerate()
+                    .filter(|(i, _)| (expected ^ actual) & (0x80 >> i) != 0)
+                    .map(|(_, flag)| flag)
+                    .collect();
+                write!(f, " (flags {})", changed)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// First trace line that differs from reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceMismatch {
+    /// Line number in reference trace, from 1.
+    pub line: usize,
+    pub expected: String,
+    pub actual: String,
+    /// Differing columns.
+    pub differences: Vec<ColumnDifference>,
+    /// Matching lines before mismatch.
+    pub before: Vec<String>,
+    /// Reference and actual lines after mismatch.
+    pub expected_after: Vec<String>,
+    pub actual_after: Vec<String>,
+}
+
+impl TraceMismatch {
+    /// Mismatch of reference line at index, with context lines before.
+    fn new(
+        reference: &[String],
+        index: usize,
+        actual: String,
+        differences: Vec<ColumnDifference>,
+        context: usize,
+    ) -> Self {
+        let line = index + 1;
+        Self {
+            line,
+            expected: reference[index].clone(),
+            actual,
+            differences,
+            before: reference[index.saturating_sub(context)..index].to_vec(),
+            expected_after: reference.iter().skip(line).take(context).cloned().collect(),
+            actual_after: vec![],
+        }
+    }
+}
+
+impl fmt::Display for TraceMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Trace mismatch at line {}", self.line)?;
+        for line in &self.before {
+            writeln!(f, "  {}", line)?;
+        }
+        writeln!(f, "- {}", self.expected)?;
+        writeln!(f, "+ {}", self.actual)?;
+        for difference in &self.differences {
+            writeln!(f, "    {}", difference)?;
+        }
+        writeln!(f, "Next lines:")?;
+        for line in &self.expected_after {
+            writeln!(f, "- {}", line)?;
+        }
+        for line in &self.actual_after {
+            writeln!(f, "+ {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares trace lines (nestest.log format), column by column.
+/// Columns missing from the expected line or ignored are not compared.
+pub fn compare_lines(
+    expected: &str,
+    actual: &str,
+    ignored_columns: &[TraceColumn],
+) -> Vec<ColumnDifference> {
+    let actual = parse_columns(actual);
+    parse_columns(expected)
+        .into_iter()
+        .filter(|(column, _)| !ignored_columns.contains(column))
+        .filter_map(|(column, expected)| {
+            let actual = actual
+                .iter()
+                .find(|(c, _)| *c == column)
+                .map(|(_, value)| *value)
+                .unwrap_or("");
+            (expected != actual).then(|| ColumnDifference {
+                column,
+                expected: expected.to_string(),
+                actual: actual.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Runs console (cartridge inserted and reset) and compares its nestest.log trace to reference,
+/// until reference ends or first mismatch.
+/// Returns first mismatch, or None if all reference lines match.
+/// A CPU jam before reference ends is a mismatch, with JAMMED as actual line.
+pub fn diff_trace(
+    nes: &mut Nes,
+    reference: &[String],
+    options: &TraceDiffOptions,
+) -> Result<Option<TraceMismatch>, NesError> {
+    if reference.is_empty() {
+        return Ok(None);
+    }
+
+    let mut counter = 0;
+    let mut mismatch: Option<TraceMismatch> = None;
+    let stop_on_jam = nes.stop_on_jam();
+    nes.set_stop_on_jam(true);
+    let result = nes.run(
+        |cpu| {
+            if let (0, Some(start_at)) = (counter, options.start_at) {
+                cpu.set_program_counter(start_at);
+            }
+            let actual = cpu.trace();
+            match &mut mismatch {
+                // Collects lines after mismatch
+                Some(mismatch) => mismatch.actual_after.push(actual),
+                None => {
+                    let expected = &reference[counter];
+                    let differences = compare_lines(expected, &actual, &options.ignored_columns);
+                    if !differences.is_empty() {
+                        mismatch = Some(TraceMismatch::new(
+                            reference,
+                            counter,
+                            actual,
+                            differences,
+                            options.context,
+                        ));
+                    }
+                }
+            }
+            counter += 1;
+            match &mismatch {
+                Some(mismatch) => mismatch.actual_after.len() < options.context,
+                None => counter < reference.len(),
+            }
+        },
+        |_, _, _| true,
+    );
+    nes.set_stop_on_jam(stop_on_jam);
+    result?;
+
+    // Run stopped before reference end
+    if mismatch.is_none() && counter < reference.len() {
+        mismatch = Some(TraceMismatch::new(
+            reference,
+            counter,
+            String::from(JAMMED),
+            vec![],
+            options.context,
+        ));
+    }
+    Ok(mismatch)
+}
@@ -0,0 +1,156 @@
+use std::{fs, path::PathBuf};
+
+use crate::{
+    cartridge::Cartridge,
+    nes::{
+        tools::load_trace,
+        trace_diff::{
+            compare_lines, diff_trace, parse_columns, ColumnDifference, TraceColumn,
+            TraceDiffOptions, JAMMED,
+        },
+        Nes,
+    },
+};
+
+const LINE: &str =
+    "C72A  D0 E0     BNE $C70C                       A:01 X:05 Y:00 P:25 SP:FB PPU: 14,245 CYC:1619";
+
+/// Loads first lines of nestest.log, and nestest cartridge in automation mode.
+fn nestest(lines: usize) -> (Nes, Vec<String>, TraceDiffOptions) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let mut reference = load_trace(path.join("res/nestest.log")).unwrap();
+    reference.truncate(lines);
+    let mut nes = Nes::new(None, None);
+    nes.insert(Cartridge::from_file(path.join("res/nestest.nes")).unwrap());
+    nes.reset();
+    let mut options = TraceDiffOptions::new();
+    options.start_at = Some(0xc000);
+    (nes, reference, options)
+}
+
+#[test]
+fn test_parse_columns() {
+    assert_eq!(
+        parse_columns(LINE),
+        vec![
+            (TraceColumn::ProgramCounter, "C72A"),
+            (TraceColumn::Bytes, "D0 E0"),
+            (TraceColumn::Disassembly, "BNE $C70C"),
+            (TraceColumn::A, "01"),
+            (TraceColumn::X, "05"),
+            (TraceColumn::Y, "00"),
+            (TraceColumn::P, "25"),
+            (TraceColumn::SP, "FB"),
+            (TraceColumn::Ppu, "14,245"),
+            (TraceColumn::Cycles, "1619"),
+        ]
+    );
+
+    // Unofficial opcode, without PPU column
+    let columns = parse_columns(
+        "C6BD  04 A9    *NOP $A9 = 00                    A:AA X:97 Y:4E P:EF SP:F5 CYC:14",
+    );
+    assert_eq!(columns[2], (TraceColumn::Disassembly, "*NOP $A9 = 00"));
+    assert!(columns
+        .iter()
+        .all(|(column, _)| *column != TraceColumn::Ppu));
+    assert_eq!(columns.last(), Some(&(TraceColumn::Cycles, "14")));
+}
+
+#[test]
+fn test_compare_lines() {
+    let actual = LINE.replace("A:01", "A:02").replace("P:25", "P:A7");
+    assert_eq!(
+        compare_lines(LINE, &actual, &[]),
+        vec![
+            ColumnDifference {
+                column: TraceColumn::A,
+                expected: String::from("01"),
+                actual: String::from("02"),
+            },
+            ColumnDifference {
+                column: TraceColumn::P,
+                expected: String::from("25"),
+                actual: String::from("A7"),
+            },
+        ]
+    );
+    assert_eq!(
+        compare_lines(LINE, &actual, &[TraceColumn::A])[0].to_string(),
+        "P: expected 25, actual A7 (flags NZ)"
+    );
+    assert!(compare_lines(LINE, &actual, &[TraceColumn::A, TraceColumn::P]).is_empty());
+
+    // Columns missing from expected line are not compared
+    let expected = LINE.replace(" PPU: 14,245", "");
+    assert!(compare_lines(&expected, LINE, &[]).is_empty());
+    assert_eq!(compare_lines(LINE, &expected, &[]).len(), 1);
+}
+
+#[test]
+fn test_diff_trace_match() {
+    let (mut nes, reference, options) = nestest(500);
+    assert_eq!(diff_trace(&mut nes, &reference, &options).unwrap(), None);
+}
+
+#[test]
+fn test_diff_trace_mismatch() {
+    let (mut nes, mut reference, options) = nestest(500);
+    let expected = reference[99].replace(" X:", " X:1");
+    reference[99] = expected.clone();
+
+    let mismatch = diff_trace(&mut nes, &reference, &options).unwrap().unwrap();
+    assert_eq!(mismatch.line, 100);
+    assert_eq!(mismatch.expected, expected);
+    assert_eq!(mismatch.actual, reference[99].replace(" X:1", " X:"));
+    assert_eq!(mismatch.differences.len(), 1);
+    assert_eq!(mismatch.differences[0].column, TraceColumn::X);
+    assert_eq!(mismatch.before, reference[96..99]);
+    assert_eq!(mismatch.expected_after, reference[100..103]);
+    assert_eq!(mismatch.actual_after, reference[100..103]);
+
+    let report = mismatch.to_string();
+    assert!(report.starts_with("Trace mismatch at line 100\n"));
+    assert!(report.contains(&format!("- {}\n+ {}\n", mismatch.expected, mismatch.actual)));
+}
+
+#[test]
+fn test_diff_trace_ignored_columns() {
+    let (mut nes, mut reference, mut options) = nestest(500);
+    reference[99] = reference[99].replace("CYC:", "CYC:1");
+    options.ignored_columns = vec![TraceColumn::Cycles];
+    assert_eq!(diff_trace(&mut nes, &reference, &options).unwrap(), None);
+}
+
+#[test]
+fn test_diff_trace_jam() {
+    // NOP, JAM
+    let mut nes = Nes::new(None, None);
+    nes.insert(Cartridge::from_code(&[0xea, 0x02]));
+    nes.reset();
+
+    // Program counter column only
+    let reference: Vec<String> = ["8000", "8001", "8002"].map(String::from).to_vec();
+    let mismatch = diff_trace(&mut nes, &reference, &TraceDiffOptions::new())
+        .unwrap()
+        .unwrap();
+    assert_eq!(mismatch.line, 3);
+    assert_eq!(mismatch.expected, "8002");
+    assert_eq!(mismatch.actual, JAMMED);
+    assert_eq!(mismatch.before, ["8000", "8001"]);
+    assert!(!nes.stop_on_jam());
+}
+
+#[test]
+fn test_load_trace_error() {
+    let dir = std::env::temp_dir().join("emultendo_test_load_trace");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("invalid.log");
+    fs::write(&path, b"C000  4C F5 C5  JMP $C5F5\n\xff\xfe\n").unwrap();
+
+    // Invalid UTF-8 is an error, as a missing file is
+    assert!(load_trace(&path).is_err());
+    assert!(load_trace(dir.join("missing.log")).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
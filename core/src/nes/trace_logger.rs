@@ -0,0 +1,150 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::RangeInclusive,
+    path::Path,
+};
+
+use crate::cpu::{trace::TraceRecord, trace_format::TraceFormatter};
+
+/// Instruction filter: only matching instructions are logged.
+pub enum TraceFilter {
+    /// Program counter in range.
+    ProgramCounter(RangeInclusive<u16>),
+    /// Program counter in a memory bank (16KB PRG ROM bank on NES).
+    Bank(u16),
+    /// Custom condition on CPU state and instruction.
+    Condition(Box<dyn Fn(&TraceRecord) -> bool>),
+}
+
+impl TraceFilter {
+    fn matches(&self, record: &TraceRecord) -> bool {
+        match self {
+            TraceFilter::ProgramCounter(range) => range.contains(&record.program_counter),
+            TraceFilter::Bank(bank) => record.bank == Some(*bank),
+            TraceFilter::Condition(condition) => condition(record),
+        }
+    }
+}
+
+/// Logs executed instructions: streams formatted lines to an output,
+/// and keeps last instructions for post-mortem dumps.
+/// Instructions are kept as records, and only formatted when written.
+pub struct TraceLogger {
+    formatter: Box<dyn TraceFormatter>,
+    output: Option<Box<dyn Write>>,
+    /// Last logged instructions, oldest first.
+    history: VecDeque<TraceRecord>,
+    history_len: usize,
+    /// History is dumped there on emulation errors.
+    post_mortem_output: Option<Box<dyn Write>>,
+    /// All filters must match.
+    filters: Vec<TraceFilter>,
+}
+
+impl TraceLogger {
+    /// Creates a logger without output nor history: see set_output and set_history_len.
+    pub fn new<F: TraceFormatter + 'static>(formatter: F) -> Self {
+        Self {
+            formatter: Box::new(formatter),
+            output: None,
+            history: VecDeque::new(),
+            history_len: 0,
+            post_mortem_output: None,
+            filters: vec![],
+        }
+    }
+
+    /// Creates a logger streaming to a file.
+    pub fn to_file<F: TraceFormatter + 'static, P: AsRef<Path>>(
+        formatter: F,
+        path: P,
+    ) -> io::Result<Self> {
+        let mut logger = Self::new(formatter);
+        logger.set_output(Box::new(BufWriter::new(File::create(path)?)));
+        Ok(logger)
+    }
+
+    /// Streams formatted instructions to output.
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = Some(output);
+    }
+
+    /// Keeps last len logged instructions (ring buffer), 0 to keep none.
+    pub fn set_history_len(&mut self, len: usize) {
+        self.history_len = len;
+        while self.history.len() > len {
+            self.history.pop_front();
+        }
+    }
+
+    /// Dumps history to output when an emulation error occurs.
+    pub fn set_post_mortem_output(&mut self, output: Box<dyn Write>) {
+        self.post_mortem_output = Some(output);
+    }
+
+    /// Logs only instructions matching filter (and previously added ones).
+    pub fn add_filter(&mut self, filter: TraceFilter) {
+        self.filters.push(filter);
+    }
+
+    pub fn clear_filters(&mut self) {
+        self.filters.clear();
+    }
+
+    /// Logs instruction, if it matches filters.
+    pub fn log(&mut self, record: &TraceRecord) -> io::Result<()> {
+        if !self.filters.iter().all(|filter| filter.matches(record)) {
+            return Ok(());
+        }
+
+        if self.history_len > 0 {
+            if self.history.len() == self.history_len {
+                self.history.pop_front();
+            }
+            self.history.push_back(*record);
+        }
+        if let Some(output) = &mut self.output {
+            writeln!(output, "{}", self.formatter.format(record))?;
+        }
+        Ok(())
+    }
+
+    /// Last logged instructions, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &TraceRecord> {
+        self.history.iter()
+    }
+
+    /// Writes formatted history, oldest first.
+    pub fn dump_history(&self, output: &mut dyn Write) -> io::Result<()> {
+        for record in &self.history {
+            writeln!(output, "{}", self.formatter.format(record))?;
+        }
+        Ok(())
+    }
+
+    /// Dumps history to post-mortem output, if any.
+    pub(super) fn post_mortem(&mut self, error: &str) -> io::Result<()> {
+        if let Some(mut output) = self.post_mortem_output.take() {
+            writeln!(
+                output,
+                "{}, last {} instructions:",
+                error,
+                self.history.len()
+            )?;
+            let result = self.dump_history(&mut output).and_then(|_| output.flush());
+            self.post_mortem_output = Some(output);
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Flushes output.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let Some(output) = &mut self.output {
+            output.flush()?;
+        }
+        Ok(())
+    }
+}
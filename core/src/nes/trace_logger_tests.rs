@@ -0,0 +1,270 @@
+use std::{cell::RefCell, fs, io, io::Write, rc::Rc};
+
+use crate::{
+    cartridge::Cartridge,
+    cpu::trace_format::TraceFormat,
+    nes::{
+        trace_logger::{TraceFilter, TraceLogger},
+        ErrorPolicy, Nes, NesError,
+    },
+};
+
+/// Writer to a shared buffer, readable while the logger owns the writer.
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.borrow().clone())
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs instruction_count instructions of a counting loop, at $8000.
+fn run(trace_logger: TraceLogger, instruction_count: usize) -> Nes {
+    let code = [
+        0xa2, 0x00, // LDX #$00
+        0xe8, // INX
+        0x4c, 0x02, 0x80, // JMP $8002
+    ];
+    let mut nes = Nes::new(None, None);
+    nes.set_trace_logger(trace_logger);
+    nes.insert(Cartridge::from_code(&code));
+    nes.reset();
+    let mut inst_count = 0;
+    nes.run(
+        |_| {
+            inst_count += 1;
+            inst_count <= instruction_count
+        },
+        |_, _, _| true,
+    )
+    .unwrap();
+    nes
+}
+
+#[test]
+fn test_stream() {
+    let output = SharedBuffer::default();
+    let mut trace_logger = TraceLogger::new(TraceFormat::Nestest);
+    trace_logger.set_output(Box::new(output.clone()));
+    run(trace_logger, 5);
+
+    let lines = output.lines();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(
+        lines[0],
+        "8000  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7"
+    );
+    assert!(lines[1].starts_with("8002  E8        INX "));
+    assert!(lines[2].starts_with("8003  4C 02 80  JMP $8002 "));
+    assert!(lines[4].starts_with("8003  4C 02 80  JMP $8002 "));
+}
+
+#[test]
+fn test_history() {
+    let mut trace_logger = TraceLogger::new(TraceFormat::Nestest);
+    trace_logger.set_history_len(3);
+    let mut nes = run(trace_logger, 100);
+
+    // Last 3 of 100 instructions: INX, JMP, INX
+    let trace_logger = nes.remove_trace_logger().unwrap();
+    let history: Vec<_> = trace_logger.history().collect();
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[0].program_counter, 0x8002);
+    assert_eq!(history[0].register_x, 48);
+    assert_eq!(history[1].program_counter, 0x8003);
+    assert_eq!(history[2].program_counter, 0x8002);
+    assert_eq!(history[2].register_x, 49);
+
+    let mut dump = vec![];
+    trace_logger.dump_history(&mut dump).unwrap();
+    let dump = String::from_utf8(dump).unwrap();
+    assert_eq!(dump.lines().count(), 3);
+    assert!(dump.starts_with("8002  E8        INX "));
+}
+
+#[test]
+fn test_post_mortem() {
+    let output = SharedBuffer::default();
+    let mut trace_logger = TraceLogger::new(TraceFormat::Nestest);
+    trace_logger.set_history_len(2);
+    trace_logger.set_post_mortem_output(Box::new(output.clone()));
+    run(trace_logger, 10);
+    assert!(output.lines().is_empty());
+
+    // Runs code at $8000 until third frame, dumping last 2 instructions
+    let run_until_failure = |code: &[u8], error_policy| {
+        let output = SharedBuffer::default();
+        let mut trace_logger = TraceLogger::new(TraceFormat::Nestest);
+        trace_logger.set_history_len(2);
+        trace_logger.set_post_mortem_output(Box::new(output.clone()));
+        let mut nes = Nes::new(None, None);
+        nes.set_trace_logger(trace_logger);
+        nes.set_error_policy(error_policy);
+        nes.insert(Cartridge::from_code(code));
+        nes.reset();
+        let mut frame_count = 0;
+        nes.run(
+            |_| true,
+            |_, _, _| {
+                frame_count += 1;
+                frame_count < 3
+            },
+        )
+        .unwrap();
+        output.lines()
+    };
+
+    // JAM: dumped once
+    let lines = run_until_failure(
+        &[
+            0xa9, 0x80, // LDA #$80
+            0x8d, 0x00, 0x20, // STA $2000 (NMI on vblank)
+            0xe8, // INX
+            0x02, // JAM
+        ],
+        ErrorPolicy::Error,
+    );
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "CPU jammed, last 2 instructions:");
+    assert!(lines[1].starts_with("8005  E8        INX "));
+    assert!(lines[2].starts_with("8006  02       *JAM "));
+
+    // Error halting the CPU: dumped once, as an error
+    let lines = run_until_failure(
+        &[
+            0xa9, 0x80, // LDA #$80
+            0x8d, 0x00, 0x20, // STA $2000 (NMI on vblank)
+            0x8d, 0x00, 0x80, // STA $8000 (PRG ROM write)
+        ],
+        ErrorPolicy::Halt,
+    );
+    assert_eq!(lines.len(), 3);
+    assert_eq!(
+        lines[0],
+        "Emulation error: Bus(PrgRomWrite { addr: 32768, data: 128 }), last 2 instructions:"
+    );
+    assert!(lines[2].starts_with("8005  8D 00 80  STA $8000 "));
+}
+
+#[test]
+fn test_program_counter_filter() {
+    let mut trace_logger = TraceLogger::new(TraceFormat::Nestest);
+    trace_logger.set_history_len(100);
+    trace_logger.add_filter(TraceFilter::ProgramCounter(0x8001..=0x8002));
+    let nes = run(trace_logger, 20);
+
+    // INX only
+    let trace_logger = nes.trace_logger().unwrap();
+    assert_eq!(trace_logger.history().count(), 10);
+    assert!(trace_logger.history().all(|r| r.mnemonic == "INX"));
+}
+
+#[test]
+fn test_bank_filter() {
+    let mut trace_logger = TraceLogger::new(TraceFormat::Nestest);
+    trace_logger.set_history_len(100);
+    trace_logger.add_filter(TraceFilter::Bank(0));
+    let nes = run(trace_logger, 20);
+    assert_eq!(nes.trace_logger().unwrap().history().count(), 20);
+
+    // Code is in first bank ($8000-$BFFF)
+    let mut trace_logger = TraceLogger::new(TraceFormat::Nestest);
+    trace_logger.set_history_len(100);
+    trace_logger.add_filter(TraceFilter::Bank(1));
+    let nes = run(trace_logger, 20);
+    assert_eq!(nes.trace_logger().unwrap().history().count(), 0);
+}
+
+#[test]
+fn test_condition_filter() {
+    let output = SharedBuffer::default();
+    let mut trace_logger = TraceLogger::new(TraceFormat::Nestest);
+    trace_logger.set_output(Box::new(output.clone()));
+    trace_logger.add_filter(TraceFilter::Condition(Box::new(|r| r.register_x >= 8)));
+    trace_logger.add_filter(TraceFilter::Condition(Box::new(|r| r.mnemonic == "INX")));
+    run(trace_logger, 20);
+
+    // Filters are combined: INX with X = 8 and 9
+    let lines = output.lines();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains(" X:08 "));
+    assert!(lines[1].contains(" X:09 "));
+}
+
+#[test]
+fn test_to_file() {
+    let dir = std::env::temp_dir().join("emultendo_test_trace_logger");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("trace.jsonl");
+
+    let trace_logger = TraceLogger::to_file(TraceFormat::JsonLines, &path).unwrap();
+    // Output is flushed when run returns
+    let _nes = run(trace_logger, 5);
+
+    let trace = fs::read_to_string(&path).unwrap();
+    assert_eq!(trace.lines().count(), 5);
+    assert!(trace.starts_with("{\"pc\":32768,\"bytes\":[162,0],\"mnemonic\":\"LDX\""));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Writer buffering data until flushed, and failing once write limit is reached.
+struct FailingWriter {
+    output: SharedBuffer,
+    pending: Vec<u8>,
+    writes_left: usize,
+}
+
+impl Write for FailingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.writes_left == 0 {
+            return Err(io::Error::other("disk full"));
+        }
+        self.writes_left -= 1;
+        self.pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.write_all(&self.pending)?;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+#[test]
+fn test_flush_on_error() {
+    let output = SharedBuffer::default();
+    let mut trace_logger = TraceLogger::new(TraceFormat::Nestest);
+    trace_logger.set_output(Box::new(FailingWriter {
+        output: output.clone(),
+        pending: vec![],
+        writes_left: 20,
+    }));
+
+    let mut nes = Nes::new(None, None);
+    nes.set_trace_logger(trace_logger);
+    nes.insert(Cartridge::from_code(&[]));
+    nes.reset();
+    let result = nes.run(|_| true, |_, _, _| true);
+
+    // Lines written before the error are delivered
+    assert!(matches!(result, Err(NesError::Io(_))));
+    assert!(!output.lines().is_empty());
+}
@@ -0,0 +1,46 @@
+/// Frames after which a latch bit that is not refreshed decays to 0 (about 600 ms).
+/// Source: https://www.nesdev.org/wiki/Open_bus_behavior#PPU_open_bus
+pub const DECAY_FRAMES: u8 = 36;
+
+/// PPU I/O data bus latch.
+/// Holds last value written to or read from any PPU register,
+/// returned by reads of write-only registers and unused bits of readable ones.
+#[derive(Debug, Clone)]
+pub struct IoLatch {
+    value: u8,
+    /// Frames since each bit was refreshed.
+    ages: [u8; 8],
+}
+
+impl IoLatch {
+    pub fn new() -> Self {
+        Self {
+            value: 0,
+            ages: [0; 8],
+        }
+    }
+
+    pub fn get(&self) -> u8 {
+        self.value
+    }
+
+    /// Refreshes bits selected by mask with value bits.
+    pub fn refresh(&mut self, value: u8, mask: u8) {
+        self.value = (self.value & !mask) | (value & mask);
+        for (bit, age) in self.ages.iter_mut().enumerate() {
+            if mask & (1 << bit) != 0 {
+                *age = 0;
+            }
+        }
+    }
+
+    /// Ages bits by one frame, old bits decay to 0.
+    pub fn end_frame(&mut self) {
+        for (bit, age) in self.ages.iter_mut().enumerate() {
+            *age = age.saturating_add(1);
+            if *age >= DECAY_FRAMES {
+                self.value &= !(1 << bit);
+            }
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use crate::ppu::io_latch::{IoLatch, DECAY_FRAMES};
+
+#[test]
+fn test_refresh() {
+    let mut latch = IoLatch::new();
+    latch.refresh(0xff, 0xff);
+    assert_eq!(latch.get(), 0xff);
+    latch.refresh(0x00, 0xe0);
+    assert_eq!(latch.get(), 0x1f);
+}
+
+#[test]
+fn test_decay() {
+    let mut latch = IoLatch::new();
+    latch.refresh(0xff, 0xff);
+    for _ in 0..DECAY_FRAMES - 1 {
+        latch.end_frame();
+    }
+    assert_eq!(latch.get(), 0xff);
+
+    // Refreshed bits are kept
+    latch.refresh(0xff, 0x0f);
+    latch.end_frame();
+    assert_eq!(latch.get(), 0x0f);
+    for _ in 0..DECAY_FRAMES {
+        latch.end_frame();
+    }
+    assert_eq!(latch.get(), 0x00);
+}
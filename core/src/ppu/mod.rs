@@ -6,6 +6,7 @@ use self::{
     addr_register::AddrRegister,
     control_register::ControlRegister,
     frame::Frame,
+    io_latch::IoLatch,
     mask_register::MaskRegister,
     render::{render_background_sync, render_sprites},
     scroll_register::ScrollRegister,
@@ -20,6 +21,10 @@ mod control_register;
 #[cfg(test)]
 mod control_register_tests;
 
+mod io_latch;
+#[cfg(test)]
+mod io_latch_tests;
+
 mod mask_register;
 mod scroll_register;
 #[cfg(test)]
@@ -49,6 +54,7 @@ pub struct Ppu {
     scroll: ScrollRegister,
     oam_addr: u8,
     oam_data: [u8; 256],
+    io_latch: IoLatch,
     scanline: u16,
     cycle: usize,
     nmi_interrupt: bool,
@@ -67,6 +73,7 @@ impl Ppu {
             scroll: ScrollRegister::new(),
             oam_addr: 0,
             oam_data: [0; 64 * 4],
+            io_latch: IoLatch::new(),
             scanline: 0,
             cycle: 0,
             nmi_interrupt: false,
@@ -107,6 +114,11 @@ impl Ppu {
         self.cycle
     }
 
+    /// I/O data bus latch value, read from write-only registers.
+    pub fn io_latch(&self) -> u8 {
+        self.io_latch.get()
+    }
+
     pub fn scanline(&self) -> u16 {
         self.scanline
     }
@@ -116,28 +128,49 @@ impl Ppu {
         self.bus = Some(Rc::clone(bus));
     }
 
+    /// Writes to status register: it is read-only, only I/O latch is refreshed.
+    pub fn write_to_status(&mut self, value: u8) {
+        self.io_latch.refresh(value, 0xff);
+    }
+
     /// Reads status register.
+    /// Low bits are not driven: they come from I/O latch.
     pub fn read_status(&mut self) -> u8 {
-        let data = self.status.snapshot();
+        let data = self.peek_status();
+        self.io_latch.refresh(data, 0b1110_0000);
         self.status.reset_vblank_status();
         self.addr.reset_latch();
         self.scroll.reset_latch();
         data
     }
 
+    /// Status register value, without side effects (vblank flag and write latches are kept).
+    pub fn peek_status(&self) -> u8 {
+        (self.status.snapshot() & 0b1110_0000) | (self.io_latch.get() & 0b0001_1111)
+    }
+
     /// Writes to OAM address register.
     pub fn write_to_oam_addr(&mut self, value: u8) {
+        self.io_latch.refresh(value, 0xff);
         self.oam_addr = value;
     }
 
     /// Writes to OAM data.
     pub fn write_to_oam_data(&mut self, value: u8) {
+        self.io_latch.refresh(value, 0xff);
         self.oam_data[self.oam_addr as usize] = value;
         self.oam_addr = self.oam_addr.wrapping_add(1);
     }
 
     /// Reads OAM data.
-    pub fn read_oam_data(&self) -> u8 {
+    pub fn read_oam_data(&mut self) -> u8 {
+        let data = self.peek_oam_data();
+        self.io_latch.refresh(data, 0xff);
+        data
+    }
+
+    /// OAM data at OAM address, without side effects.
+    pub fn peek_oam_data(&self) -> u8 {
         self.oam_data[self.oam_addr as usize]
     }
 
@@ -151,11 +184,13 @@ impl Ppu {
 
     /// Writes to address register.
     pub fn write_to_ppu_addr(&mut self, value: u8) {
+        self.io_latch.refresh(value, 0xff);
         self.addr.update(value);
     }
 
     /// Writes to control register.
     pub fn write_to_ctrl(&mut self, value: u8) {
+        self.io_latch.refresh(value, 0xff);
         let before_nmi_status = self.ctrl.generate_vblank_nmi();
         self.ctrl.update(value);
         if !before_nmi_status && self.ctrl.generate_vblank_nmi() && self.status.is_in_vblank() {
@@ -165,11 +200,13 @@ impl Ppu {
 
     /// Writes to mask register.
     pub fn write_to_mask(&mut self, value: u8) {
+        self.io_latch.refresh(value, 0xff);
         self.mask.update(value);
     }
 
     /// Writes to scroll register.
     pub fn write_to_scroll(&mut self, value: u8) {
+        self.io_latch.refresh(value, 0xff);
         self.scroll.write(value);
     }
 
@@ -179,19 +216,46 @@ impl Ppu {
     }
 
     /// Reads PPU data.
+    /// Palette entries are 6 bits: high bits come from I/O latch.
     pub fn read_data(&mut self) -> u8 {
         let addr = self.addr.get();
         self.increment_vram_addr();
 
-        if let Some(bus) = &self.bus {
-            return bus.borrow_mut().read_data(addr);
+        let data = if let Some(bus) = &self.bus {
+            bus.borrow_mut().read_data(addr)
+        } else {
+            panic!("PPU is not connected to bus");
+        };
+
+        if addr >= 0x3f00 {
+            let data = (data & 0b0011_1111) | (self.io_latch.get() & 0b1100_0000);
+            self.io_latch.refresh(data, 0b0011_1111);
+            data
+        } else {
+            self.io_latch.refresh(data, 0xff);
+            data
+        }
+    }
+
+    /// PPU data read would return, without side effects (VRAM address and read buffer are kept).
+    pub fn peek_data(&self) -> u8 {
+        let addr = self.addr.get();
+        let data = if let Some(bus) = &self.bus {
+            bus.borrow().peek_data(addr)
         } else {
             panic!("PPU is not connected to bus");
+        };
+
+        if addr >= 0x3f00 {
+            (data & 0b0011_1111) | (self.io_latch.get() & 0b1100_0000)
+        } else {
+            data
         }
     }
 
     /// Write PPU data.
     pub fn write_to_data(&mut self, value: u8) {
+        self.io_latch.refresh(value, 0xff);
         let addr = self.addr.get();
         if let Some(bus) = &self.bus {
             bus.borrow_mut().write_to_data(addr, value);
@@ -229,6 +293,7 @@ impl Ppu {
 
             if self.scanline >= 262 {
                 self.scanline = 0;
+                self.io_latch.end_frame();
                 self.nmi_interrupt = false;
                 self.status.reset_vblank_status();
                 self.status.set_sprite_zero_hit(false);
@@ -246,6 +311,18 @@ impl Ppu {
         Ok(false)
     }
 
+    /// Raises NMI, as on vertical blank start.
+    #[cfg(test)]
+    pub fn trigger_nmi(&mut self) {
+        self.nmi_interrupt = true;
+    }
+
+    /// Sets vertical blank status flag, as on vertical blank start.
+    #[cfg(test)]
+    pub fn set_vblank(&mut self) {
+        self.status.set_vblank_status(true);
+    }
+
     /// Poll NMI interrupt status.
     /// Sets to false after call.
     pub fn poll_nmi_status(&mut self) -> bool {
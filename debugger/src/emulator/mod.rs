@@ -8,7 +8,7 @@ use self::state::{CpuState, EmulatorState, PpuState};
 use emultendo_core::{
     cartridge::Cartridge,
     controller::{Joypad, JoypadButton},
-    nes::Nes,
+    nes::{ErrorPolicy, Nes, Pacing},
 };
 
 pub mod state;
@@ -22,6 +22,11 @@ pub fn start_emulator(state: &Arc<RwLock<EmulatorState>>) {
         // Create console
         // plug only joypad1, otherwise Super Mario does not work
         let mut nes = Nes::new(Some(Joypad::new()), None);
+        // Emulation speed follows the CPU clock setting
+        nes.set_pacing(Pacing::CpuClock);
+        // Unexpected emulation errors freeze the game instead of closing the window
+        nes.set_error_policy(ErrorPolicy::Halt);
+        let apu = Rc::clone(nes.apu());
 
         // Initial cartridge insertion detection
         while state.read().unwrap().cartridge.is_none() {
@@ -55,6 +60,7 @@ pub fn start_emulator(state: &Arc<RwLock<EmulatorState>>) {
 
                         // Update CPU state
                         state_lock.cpu = CpuState::from_cpu(cpu);
+                        state_lock.apu.sample_waveforms(&apu.borrow());
 
                         // If CPU Mhz changed: restart run loop
                         if state_lock.cpu_mhz != initial_cpu_mhz {
@@ -79,6 +85,9 @@ pub fn start_emulator(state: &Arc<RwLock<EmulatorState>>) {
                         // Update frame in state
                         state_lock.ppu = PpuState::from_ppu(ppu);
 
+                        // Update APU state, and apply mixer settings
+                        state_lock.apu.update(&mut apu.borrow_mut());
+
                         // Update Joypad from state
                         if let Some(joypad1) = &joypad1 {
                             joypad1.borrow_mut().set_button_pressed_status(
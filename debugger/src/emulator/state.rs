@@ -1,8 +1,10 @@
 use std::path::PathBuf;
 
 use emultendo_core::{
+    apu::{Apu, Channel},
     cartridge::{Cartridge, Mirroring},
     cpu::{Cpu, CpuFlags},
+    memory::Memory,
     nes::CPU_MHZ,
     ppu::{frame::Frame, Ppu},
 };
@@ -34,6 +36,9 @@ impl JoypadState {
     }
 }
 
+/// Stack bytes shown by debugger.
+const STACK_VIEW_LEN: usize = 8;
+
 /// CPU state.
 pub struct CpuState {
     pub register_a: u8,
@@ -42,6 +47,8 @@ pub struct CpuState {
     pub status: CpuFlags,
     pub program_counter: u16,
     pub stack_pointer: u8,
+    /// Top of stack, most recently pushed first.
+    pub stack: Vec<u8>,
 }
 
 impl CpuState {
@@ -53,6 +60,7 @@ impl CpuState {
             status: CpuFlags::from_bits_truncate(0b100100),
             program_counter: 0,
             stack_pointer: 0,
+            stack: vec![],
         }
     }
 
@@ -64,6 +72,10 @@ impl CpuState {
             status: cpu.status(),
             program_counter: cpu.program_counter(),
             stack_pointer: cpu.stack_pointer(),
+            stack: (cpu.stack_pointer() as u16 + 1..=0xff)
+                .take(STACK_VIEW_LEN)
+                .map(|offset| cpu.mem_peek(0x0100 + offset))
+                .collect(),
         }
     }
 }
@@ -161,10 +173,128 @@ impl PpuScrollState {
     }
 }
 
+/// Samples kept per channel for waveform display.
+pub const WAVEFORM_LENGTH: usize = 512;
+/// CPU cycles between two waveform samples (a waveform spans about one frame).
+const WAVEFORM_PERIOD: u64 = 64;
+
+/// APU channel state.
+pub struct ApuChannelState {
+    pub channel: Channel,
+    /// Last values written to the 4 channel registers.
+    pub registers: [u8; 4],
+    pub period: u16,
+    /// Envelope volume (pulse, noise) or output level (DMC).
+    pub volume: Option<u8>,
+    /// Length counter (pulse, triangle, noise) or sample bytes remaining (DMC).
+    pub length_counter: u16,
+    /// Output levels ring buffer, oldest sample at waveform_offset.
+    pub waveform: Vec<f32>,
+    pub waveform_offset: usize,
+    /// Mute and solo settings, applied to the APU mixer.
+    pub muted: bool,
+    pub solo: bool,
+}
+
+impl ApuChannelState {
+    pub fn new(channel: Channel) -> Self {
+        Self {
+            channel,
+            registers: [0; 4],
+            period: 0,
+            volume: None,
+            length_counter: 0,
+            waveform: vec![0.0; WAVEFORM_LENGTH],
+            waveform_offset: 0,
+            muted: false,
+            solo: false,
+        }
+    }
+
+    /// Maximum output level of the channel.
+    pub fn max_level(&self) -> u8 {
+        match self.channel {
+            Channel::Dmc => 127,
+            _ => 15,
+        }
+    }
+}
+
+/// APU state.
+pub struct ApuState {
+    /// Channels, in Channel::ALL order.
+    pub channels: Vec<ApuChannelState>,
+    next_sample_cycle: u64,
+}
+
+impl ApuState {
+    pub fn new() -> Self {
+        Self {
+            channels: Channel::ALL.into_iter().map(ApuChannelState::new).collect(),
+            next_sample_cycle: 0,
+        }
+    }
+
+    /// Updates channel registers and counters from APU,
+    /// and applies mute and solo settings to APU.
+    pub fn update(&mut self, apu: &mut Apu) {
+        for (i, state) in self.channels.iter_mut().enumerate() {
+            let base = 0x4000 + i as u16 * 4;
+            for (j, register) in state.registers.iter_mut().enumerate() {
+                *register = apu.register(base + j as u16);
+            }
+            (state.period, state.volume, state.length_counter) = match state.channel {
+                Channel::Pulse1 => (
+                    apu.pulse1().timer_period(),
+                    Some(apu.pulse1().envelope().output()),
+                    apu.pulse1().length_counter().counter() as u16,
+                ),
+                Channel::Pulse2 => (
+                    apu.pulse2().timer_period(),
+                    Some(apu.pulse2().envelope().output()),
+                    apu.pulse2().length_counter().counter() as u16,
+                ),
+                Channel::Triangle => (
+                    apu.triangle().timer_period(),
+                    None,
+                    apu.triangle().length_counter().counter() as u16,
+                ),
+                Channel::Noise => (
+                    apu.noise().timer_period(),
+                    Some(apu.noise().envelope().output()),
+                    apu.noise().length_counter().counter() as u16,
+                ),
+                Channel::Dmc => (
+                    apu.dmc().timer_period(),
+                    Some(apu.dmc().output_level()),
+                    apu.dmc().bytes_remaining(),
+                ),
+            };
+
+            apu.set_channel_muted(state.channel, state.muted);
+            apu.set_channel_solo(state.channel, state.solo);
+        }
+    }
+
+    /// Records channel output levels for waveforms, every WAVEFORM_PERIOD CPU cycles.
+    pub fn sample_waveforms(&mut self, apu: &Apu) {
+        if apu.cycles() < self.next_sample_cycle {
+            return;
+        }
+        self.next_sample_cycle = (apu.cycles() / WAVEFORM_PERIOD + 1) * WAVEFORM_PERIOD;
+
+        for state in self.channels.iter_mut() {
+            state.waveform[state.waveform_offset] = apu.channel_level(state.channel) as f32;
+            state.waveform_offset = (state.waveform_offset + 1) % WAVEFORM_LENGTH;
+        }
+    }
+}
+
 /// Emulator state.
 pub struct EmulatorState {
     pub ppu: PpuState,
     pub cpu: CpuState,
+    pub apu: ApuState,
     pub joypad1: JoypadState,
     pub cartridge: Option<CartridgeState>,
     pub reset: bool,
@@ -177,6 +307,7 @@ impl EmulatorState {
         Self {
             ppu: PpuState::new(),
             cpu: CpuState::new(),
+            apu: ApuState::new(),
             joypad1: JoypadState::new(),
             cartridge: None,
             reset: false,
@@ -4,7 +4,7 @@ use emulator::{start_emulator, state::EmulatorState};
 use glium::backend::Facade;
 use widget::Widget;
 use window::{
-    cartridge::CartridgeWindow, control::ControlWindow, cpu::CpuWindow, display::DisplayWindow,
+    apu::ApuWindow, cartridge::CartridgeWindow, control::ControlWindow, cpu::CpuWindow, display::DisplayWindow,
     ppu::PpuWindow,
 };
 
@@ -40,6 +40,9 @@ fn main() {
     // Cartridge window
     let cartridge_window = CartridgeWindow::new(350.0, 580.0);
 
+    // APU window
+    let apu_window = ApuWindow::new(700.0, 580.0);
+
     // Control window
     let control_window = ControlWindow::new(20.0, 700.0);
 
@@ -49,6 +52,7 @@ fn main() {
         ppu_window.render(ui, renderer.textures(), &mut state);
         cartridge_window.render(ui, renderer.textures(), &mut state);
         control_window.render(ui, renderer.textures(), &mut state);
+        apu_window.render(ui, renderer.textures(), &mut state);
         display_window.render(ui, renderer.textures(), &mut state);
     });
 }
@@ -0,0 +1,101 @@
+use std::sync::{Arc, RwLock};
+
+use imgui::{Condition, Textures, Ui};
+use imgui_glium_renderer::Texture;
+
+use crate::{emulator::state::EmulatorState, widget::Widget};
+
+pub struct ApuWindow {
+    start_pos: [f32; 2],
+}
+
+impl ApuWindow {
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { start_pos: [x, y] }
+    }
+}
+
+impl Widget for ApuWindow {
+    fn render(
+        &self,
+        ui: &Ui,
+        _textures: &Textures<Texture>,
+        state: &mut Arc<RwLock<EmulatorState>>,
+    ) {
+        ui.window("APU")
+            .resizable(true)
+            .position(self.start_pos, Condition::FirstUseEver)
+            .build(|| {
+                let mut state_lock = state.write().unwrap();
+
+                ui.text("Channels");
+
+                let num_cols = 7;
+
+                let flags = imgui::TableFlags::ROW_BG
+                    | imgui::TableFlags::BORDERS_H
+                    | imgui::TableFlags::BORDERS_V;
+
+                if let Some(_t) =
+                    ui.begin_table_with_sizing("apu_channels", num_cols, flags, [520.0, 10.0], 0.0)
+                {
+                    ui.table_setup_column("Channel");
+                    ui.table_setup_column("Registers");
+                    ui.table_setup_column("Period");
+                    ui.table_setup_column("Volume");
+                    ui.table_setup_column("Length");
+                    ui.table_setup_column("Mute");
+                    ui.table_setup_column("Solo");
+
+                    ui.table_headers_row();
+
+                    for channel in state_lock.apu.channels.iter_mut() {
+                        let name = channel.channel.name();
+
+                        ui.table_next_row();
+
+                        ui.table_set_column_index(0);
+                        ui.text(name);
+                        ui.table_set_column_index(1);
+                        ui.text(format!(
+                            "{:02x} {:02x} {:02x} {:02x}",
+                            channel.registers[0],
+                            channel.registers[1],
+                            channel.registers[2],
+                            channel.registers[3]
+                        ));
+                        ui.table_set_column_index(2);
+                        ui.text(format!("{}", channel.period));
+                        ui.table_set_column_index(3);
+                        ui.text(match channel.volume {
+                            Some(volume) => format!("{}", volume),
+                            None => "-".to_string(),
+                        });
+                        ui.table_set_column_index(4);
+                        ui.text(format!("{}", channel.length_counter));
+                        ui.table_set_column_index(5);
+                        ui.checkbox(format!("##mute_{}", name), &mut channel.muted);
+                        ui.table_set_column_index(6);
+                        ui.checkbox(format!("##solo_{}", name), &mut channel.solo);
+                    }
+                }
+
+                ui.separator();
+
+                ui.text("Waveforms");
+
+                for channel in state_lock.apu.channels.iter() {
+                    ui.plot_lines(
+                        format!("##waveform_{}", channel.channel.name()),
+                        &channel.waveform,
+                    )
+                    .values_offset(channel.waveform_offset)
+                    .overlay_text(channel.channel.name())
+                    .scale_min(0.0)
+                    .scale_max(channel.max_level() as f32)
+                    .graph_size([520.0, 40.0])
+                    .build();
+                }
+            });
+    }
+}
@@ -63,6 +63,15 @@ impl Widget for CpuWindow {
                     ui.text(format!("0x{:02x}", state_lock.cpu.stack_pointer));
                 }
 
+                let stack = state_lock
+                    .cpu
+                    .stack
+                    .iter()
+                    .map(|data| format!("{:02x}", data))
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                ui.text(format!("Stack: {}", stack));
+
                 ui.separator();
 
                 ui.slider_config("Clock", 0.1, 10.0)
@@ -1,3 +1,4 @@
+pub mod apu;
 pub mod cpu;
 pub mod display;
 pub mod cartridge;
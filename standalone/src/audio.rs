@@ -0,0 +1,147 @@
+use std::{thread, time::Duration};
+
+use emultendo_core::audio::{AudioConfig, AudioSink, Samples};
+use sdl2::{
+    audio::{AudioQueue, AudioSpecDesired},
+    Sdl,
+};
+
+/// Requested output sample rate.
+const SAMPLE_RATE: i32 = 44_100;
+/// Samples per buffer sent by the emulator.
+const BUFFER_SIZE: usize = 512;
+/// Queued audio (in samples) the emulator is paced on.
+const TARGET_QUEUED_SAMPLES: u32 = BUFFER_SIZE as u32 * 4;
+/// Maximum queued audio (in samples) before dropping buffers, to bound latency.
+const MAX_QUEUED_SAMPLES: u32 = TARGET_QUEUED_SAMPLES * 2;
+/// Volume step for volume hotkeys.
+const VOLUME_STEP: f32 = 0.1;
+
+/// SDL audio output.
+/// Plays silence if the audio device cannot be opened.
+/// Emulation is paced on the audio queue fill level: see wait.
+pub struct SdlAudio {
+    queue: Option<AudioQueue<f32>>,
+    volume: f32,
+    muted: bool,
+    buffer: Vec<f32>,
+}
+
+impl SdlAudio {
+    /// Opens default audio device.
+    pub fn new(sdl_context: &Sdl) -> Self {
+        let queue = sdl_context.audio().and_then(|audio_subsystem| {
+            audio_subsystem.open_queue::<f32, _>(
+                None,
+                &AudioSpecDesired {
+                    freq: Some(SAMPLE_RATE),
+                    channels: Some(1),
+                    samples: Some(BUFFER_SIZE as u16),
+                },
+            )
+        });
+
+        let queue = match queue {
+            Ok(queue) => {
+                queue.resume();
+                Some(queue)
+            }
+            Err(e) => {
+                eprintln!("Cannot open audio device, sound is disabled: {}", e);
+                None
+            }
+        };
+
+        Self {
+            queue,
+            volume: 1.0,
+            muted: false,
+            buffer: Vec::with_capacity(BUFFER_SIZE),
+        }
+    }
+
+    /// Emulator audio configuration matching the opened device.
+    pub fn config(&self) -> AudioConfig {
+        let sample_rate = match &self.queue {
+            Some(queue) => queue.spec().freq,
+            None => SAMPLE_RATE,
+        };
+        let mut config = AudioConfig::new(sample_rate as u32);
+        config.buffer_size = BUFFER_SIZE;
+        config
+    }
+
+    /// Indicates if an audio device is opened.
+    pub fn is_available(&self) -> bool {
+        self.queue.is_some()
+    }
+
+    /// Queued samples not played yet.
+    fn queued_samples(&self) -> u32 {
+        match &self.queue {
+            Some(queue) => queue.size() / std::mem::size_of::<f32>() as u32,
+            None => 0,
+        }
+    }
+
+    /// Waits until the audio queue drains below its target level.
+    /// Returns immediately if no audio device is opened.
+    pub fn wait(&self) {
+        while self.queued_samples() > TARGET_QUEUED_SAMPLES {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+    }
+
+    pub fn volume_up(&mut self) {
+        self.volume = (self.volume + VOLUME_STEP).min(1.0);
+    }
+
+    pub fn volume_down(&mut self) {
+        self.volume = (self.volume - VOLUME_STEP).max(0.0);
+    }
+}
+
+impl AudioSink for SdlAudio {
+    fn write_samples(&mut self, samples: Samples) {
+        let queue = match &self.queue {
+            Some(queue) => queue,
+            None => return,
+        };
+
+        // Drop buffer if the device is late, rather than accumulating latency
+        if self.queued_samples() > MAX_QUEUED_SAMPLES {
+            return;
+        }
+
+        let volume = if self.muted { 0.0 } else { self.volume };
+        self.buffer.clear();
+        match samples {
+            Samples::F32(s) => self.buffer.extend(s.iter().map(|x| x * volume)),
+            Samples::I16(s) => self
+                .buffer
+                .extend(s.iter().map(|&x| x as f32 / i16::MAX as f32 * volume)),
+        }
+
+        if let Err(e) = queue.queue_audio(&self.buffer) {
+            eprintln!("Cannot queue audio: {}", e);
+        }
+    }
+
+    fn fill_level(&self) -> Option<f32> {
+        self.queue
+            .as_ref()
+            .map(|_| self.queued_samples() as f32 / MAX_QUEUED_SAMPLES as f32)
+    }
+}
@@ -1 +1,2 @@
+pub mod audio;
 pub mod util;
\ No newline at end of file
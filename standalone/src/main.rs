@@ -1,13 +1,49 @@
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use emultendo_core::{
+    audio::recorder::RecordingOptions,
     cartridge::Cartridge,
     controller::{Joypad, JoypadButton},
-    nes::Nes,
+    nes::{ErrorPolicy, Nes, Pacing},
     ppu::frame::Frame,
 };
 
-use sdl2::{event::Event, keyboard::Keycode, pixels::PixelFormatEnum, video::GLProfile};
+use emultendo_standalone::audio::SdlAudio;
+use sdl2::{
+    event::Event,
+    keyboard::{Keycode, Mod},
+    pixels::PixelFormatEnum,
+    video::GLProfile,
+};
+
+/// Starts recording sound to a WAV file named after current time,
+/// or stops recording in progress.
+fn toggle_recording(nes: &mut Nes, sample_rate: u32, split_channels: bool) {
+    if nes.is_recording() {
+        match nes.stop_recording() {
+            Ok(()) => println!("Sound recording stopped"),
+            Err(e) => eprintln!("Cannot complete sound recording: {:?}", e),
+        }
+        return;
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let filename = format!("emultendo-{}.wav", timestamp);
+    let mut options = RecordingOptions::new(sample_rate);
+    options.split_channels = split_channels;
+    match nes.start_recording(&filename, options) {
+        Ok(()) => println!("Recording sound to {}", filename),
+        Err(e) => eprintln!("Cannot record sound: {:?}", e),
+    }
+}
 
 fn main() {
     // Pixel scale
@@ -55,16 +91,32 @@ fn main() {
         )
         .unwrap();
 
+    // Open audio device
+    let audio = Rc::new(RefCell::new(SdlAudio::new(&sdl_context)));
+
     // Create event pump
     let mut event_pump = sdl_context.event_pump().unwrap();
 
     // Game filename
     let mut game_filename: Option<Box<String>> = None;
 
-    loop {
+    // Window closed or Escape pressed
+    let mut quit = false;
+
+    while !quit {
         // Create console
         // plug only joypad1, other Super Mario does not work
         let mut nes = Nes::new(Some(Joypad::new()), None);
+        let audio_config = audio.borrow().config();
+        nes.set_audio_sink(audio_config, Box::new(Rc::clone(&audio)));
+        // Unexpected emulation errors freeze the game instead of closing the window
+        nes.set_error_policy(ErrorPolicy::Halt);
+
+        // Emulation is paced by audio and display refresh (vsync)
+        // Fallback to CPU clock pacing when sound is not available
+        if !audio.borrow().is_available() {
+            nes.set_pacing(Pacing::CpuClock);
+        }
 
         // Load game to cartridge (if game file)
         // then insert cartridge and reset
@@ -74,58 +126,108 @@ fn main() {
             nes.reset();
         }
 
-        // Run
-        nes.run(
-            |_| {true},
-            |ppu, joypad1, _| {
-                // Update canvas with frame
-                texture.update(None, &ppu.frame().borrow().data(), 256 * 3).unwrap();
-                canvas.copy(&texture, None, None).unwrap();
-                canvas.present();
-
-                // Run event loop
-                let mut cont = true;
-                for event in event_pump.poll_iter() {
-                    match event {
-                        Event::Quit { .. }
-                        | Event::KeyDown {
-                            keycode: Some(Keycode::Escape),
-                            ..
-                        } => std::process::exit(0),
-
-                        Event::KeyDown {
-                            keycode: Some(Keycode::Tab),
-                            ..
-                        } => cont = false,
-
-                        Event::DropFile { filename, .. } => {
-                            game_filename = Some(Box::new(filename));
-                            cont = false;
-                        }
+        // Run until reset, game change or quit
+        // Emulation is interrupted to start or stop sound recording
+        let mut reset = false;
+        while !reset && !quit {
+            let mut recording_requested = false;
+            let mut split_channels = false;
+            nes.run(
+                |_| {true},
+                |ppu, joypad1, _| {
+                    // Update canvas with frame
+                    texture.update(None, &ppu.frame().borrow().data(), 256 * 3).unwrap();
+                    canvas.copy(&texture, None, None).unwrap();
+                    canvas.present();
+
+                    // Wait for audio device to consume queued samples
+                    audio.borrow().wait();
+
+                    // Run event loop
+                    let mut cont = true;
+                    for event in event_pump.poll_iter() {
+                        match event {
+                            Event::Quit { .. }
+                            | Event::KeyDown {
+                                keycode: Some(Keycode::Escape),
+                                ..
+                            } => {
+                                quit = true;
+                                cont = false;
+                            }
+
+                            Event::KeyDown {
+                                keycode: Some(Keycode::Tab),
+                                ..
+                            } => {
+                                reset = true;
+                                cont = false;
+                            }
+
+                            Event::KeyDown {
+                                keycode: Some(Keycode::R),
+                                keymod,
+                                ..
+                            } => {
+                                recording_requested = true;
+                                split_channels = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                                cont = false;
+                            }
 
-                        Event::KeyDown { keycode, .. } => {
-                            if let Some(key) = key_map.get(&keycode.unwrap_or(Keycode::Ampersand)) {
-                                if let Some(joypad1) = &joypad1 {
-                                    joypad1.borrow_mut().set_button_pressed_status(*key, true);
+                            Event::KeyDown {
+                                keycode: Some(Keycode::M),
+                                ..
+                            } => audio.borrow_mut().toggle_mute(),
+
+                            Event::KeyDown {
+                                keycode: Some(Keycode::Equals | Keycode::KpPlus),
+                                ..
+                            } => audio.borrow_mut().volume_up(),
+
+                            Event::KeyDown {
+                                keycode: Some(Keycode::Minus | Keycode::KpMinus),
+                                ..
+                            } => audio.borrow_mut().volume_down(),
+
+                            Event::DropFile { filename, .. } => {
+                                game_filename = Some(Box::new(filename));
+                                reset = true;
+                                cont = false;
+                            }
+
+                            Event::KeyDown { keycode, .. } => {
+                                if let Some(key) = key_map.get(&keycode.unwrap_or(Keycode::Ampersand)) {
+                                    if let Some(joypad1) = &joypad1 {
+                                        joypad1.borrow_mut().set_button_pressed_status(*key, true);
+                                    }
                                 }
                             }
-                        }
-                        Event::KeyUp { keycode, .. } => {
-                            if let Some(key) = key_map.get(&keycode.unwrap_or(Keycode::Ampersand)) {
-                                if let Some(joypad1) = &joypad1 {
-                                    joypad1.borrow_mut().set_button_pressed_status(*key, false);
+                            Event::KeyUp { keycode, .. } => {
+                                if let Some(key) = key_map.get(&keycode.unwrap_or(Keycode::Ampersand)) {
+                                    if let Some(joypad1) = &joypad1 {
+                                        joypad1.borrow_mut().set_button_pressed_status(*key, false);
+                                    }
                                 }
                             }
-                        }
 
-                        _ => { /* Do nothing */ }
+                            _ => { /* Do nothing */ }
+                        }
                     }
-                }
 
-                // Continue ?
-                cont
-            },
-        )
-        .unwrap();
+                    // Continue ?
+                    cont
+                },
+            )
+            .unwrap();
+
+            if recording_requested {
+                toggle_recording(&mut nes, audio_config.sample_rate, split_channels);
+            }
+        }
+
+        // Complete recording before console is replaced or emulator quits
+        if nes.is_recording() {
+            toggle_recording(&mut nes, audio_config.sample_rate, false);
+        }
     }
 }